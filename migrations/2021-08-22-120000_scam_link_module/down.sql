@@ -0,0 +1,24 @@
+CREATE TYPE module_kind_new AS ENUM (
+    'mass_ping',
+    'crosspost',
+    'emoji_spam',
+    'mention_spam',
+    'selfbot',
+    'invite_link',
+    'channel_activity',
+    'user_activity',
+    'word_filter'
+);
+
+DELETE FROM module_settings WHERE module = 'scam_link';
+DELETE FROM actions WHERE module = 'scam_link';
+DELETE FROM modules WHERE module = 'scam_link';
+DELETE FROM module_exclusions WHERE module = 'scam_link';
+
+ALTER TABLE module_settings ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE actions ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE modules ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE module_exclusions ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+
+DROP TYPE module_kind;
+ALTER TYPE module_kind_new RENAME TO module_kind;
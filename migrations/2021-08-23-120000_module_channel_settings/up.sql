@@ -0,0 +1,8 @@
+CREATE TABLE "module_channel_settings" (
+    "guild" BIGINT NOT NULL,
+    "module" module_kind NOT NULL,
+    "channel" BIGINT NOT NULL,
+    "setting" TEXT NOT NULL,
+    "value" TEXT NOT NULL,
+    PRIMARY KEY ("guild", "module", "channel", "setting")
+);
@@ -0,0 +1,28 @@
+CREATE TYPE module_kind_new AS ENUM (
+    'mass_ping',
+    'crosspost',
+    'emoji_spam',
+    'mention_spam',
+    'selfbot',
+    'invite_link',
+    'channel_activity',
+    'user_activity',
+    'word_filter',
+    'scam_link',
+    'link_policy'
+);
+
+DELETE FROM module_settings WHERE module = 'caps';
+DELETE FROM actions WHERE module = 'caps';
+DELETE FROM modules WHERE module = 'caps';
+DELETE FROM module_exclusions WHERE module = 'caps';
+DELETE FROM module_channel_settings WHERE module = 'caps';
+
+ALTER TABLE module_settings ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE actions ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE modules ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE module_exclusions ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE module_channel_settings ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+
+DROP TYPE module_kind;
+ALTER TYPE module_kind_new RENAME TO module_kind;
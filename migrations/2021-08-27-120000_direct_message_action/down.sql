@@ -0,0 +1,11 @@
+CREATE TYPE action_kind_new AS ENUM (
+    'remove_message',
+    'notify'
+);
+
+DELETE FROM actions WHERE action = 'direct_message';
+
+ALTER TABLE actions ALTER COLUMN action TYPE action_kind_new USING (action::text::action_kind_new);
+
+DROP TYPE action_kind;
+ALTER TYPE action_kind_new RENAME TO action_kind;
@@ -0,0 +1 @@
+ALTER TYPE action_kind ADD VALUE 'direct_message';
@@ -0,0 +1 @@
+ALTER TYPE module_kind ADD VALUE 'new_account';
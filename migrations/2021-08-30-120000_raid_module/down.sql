@@ -0,0 +1,34 @@
+CREATE TYPE module_kind_new AS ENUM (
+    'mass_ping',
+    'crosspost',
+    'emoji_spam',
+    'mention_spam',
+    'selfbot',
+    'invite_link',
+    'channel_activity',
+    'user_activity',
+    'word_filter',
+    'scam_link',
+    'link_policy',
+    'caps',
+    'unicode_abuse',
+    'attachments',
+    'secret_leak',
+    'new_account',
+    'ghost_ping'
+);
+
+DELETE FROM module_settings WHERE module = 'raid';
+DELETE FROM actions WHERE module = 'raid';
+DELETE FROM modules WHERE module = 'raid';
+DELETE FROM module_exclusions WHERE module = 'raid';
+DELETE FROM module_channel_settings WHERE module = 'raid';
+
+ALTER TABLE module_settings ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE actions ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE modules ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE module_exclusions ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE module_channel_settings ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+
+DROP TYPE module_kind;
+ALTER TYPE module_kind_new RENAME TO module_kind;
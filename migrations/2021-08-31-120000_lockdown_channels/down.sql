@@ -0,0 +1 @@
+DROP TABLE "lockdown_channels";
@@ -0,0 +1,9 @@
+-- the @everyone permission overwrite of each locked channel from before it was locked. allow and deny are NULL if the
+-- channel had no @everyone overwrite to begin with
+CREATE TABLE "lockdown_channels" (
+    "guild" BIGINT NOT NULL,
+    "channel" BIGINT NOT NULL,
+    "allow" BIGINT,
+    "deny" BIGINT,
+    PRIMARY KEY ("guild", "channel")
+);
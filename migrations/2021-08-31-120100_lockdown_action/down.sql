@@ -0,0 +1,12 @@
+CREATE TYPE action_kind_new AS ENUM (
+    'remove_message',
+    'notify',
+    'direct_message'
+);
+
+DELETE FROM actions WHERE action = 'lockdown';
+
+ALTER TABLE actions ALTER COLUMN action TYPE action_kind_new USING (action::text::action_kind_new);
+
+DROP TYPE action_kind;
+ALTER TYPE action_kind_new RENAME TO action_kind;
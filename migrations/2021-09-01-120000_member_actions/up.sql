@@ -0,0 +1,2 @@
+ALTER TYPE action_kind ADD VALUE 'kick';
+ALTER TYPE action_kind ADD VALUE 'reset_nickname';
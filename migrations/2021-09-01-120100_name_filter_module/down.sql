@@ -0,0 +1,35 @@
+CREATE TYPE module_kind_new AS ENUM (
+    'mass_ping',
+    'crosspost',
+    'emoji_spam',
+    'mention_spam',
+    'selfbot',
+    'invite_link',
+    'channel_activity',
+    'user_activity',
+    'word_filter',
+    'scam_link',
+    'link_policy',
+    'caps',
+    'unicode_abuse',
+    'attachments',
+    'secret_leak',
+    'new_account',
+    'ghost_ping',
+    'raid'
+);
+
+DELETE FROM module_settings WHERE module = 'name_filter';
+DELETE FROM actions WHERE module = 'name_filter';
+DELETE FROM modules WHERE module = 'name_filter';
+DELETE FROM module_exclusions WHERE module = 'name_filter';
+DELETE FROM module_channel_settings WHERE module = 'name_filter';
+
+ALTER TABLE module_settings ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE actions ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE modules ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE module_exclusions ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+ALTER TABLE module_channel_settings ALTER COLUMN module TYPE module_kind_new USING (module::text::module_kind_new);
+
+DROP TYPE module_kind;
+ALTER TYPE module_kind_new RENAME TO module_kind;
@@ -0,0 +1,5 @@
+-- the persistent key-value store of each guild's script, as a JSON object
+CREATE TABLE "script_stores" (
+    "guild" BIGINT PRIMARY KEY,
+    "store" TEXT NOT NULL
+);
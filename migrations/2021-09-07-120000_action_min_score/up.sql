@@ -0,0 +1,2 @@
+-- the lowest match score the action is run for. 0 runs it for every match
+ALTER TABLE "actions" ADD COLUMN "min_score" SMALLINT NOT NULL DEFAULT 0;
@@ -10,6 +10,10 @@ pub struct Config {
     pub log_level: logging::LogLevel,
     pub log_timestamps: bool,
     pub log_colored: bool,
+    pub matcher_state_dir: Option<String>,
+    pub matcher_state_max_age: u64,
+    pub scam_blocklist_path: Option<String>,
+    pub message_cache_size: usize,
 
     #[serde(flatten)]
     pub database: DatabaseConfig,
@@ -33,6 +37,12 @@ impl Default for Config {
             log_level: Default::default(),
             log_timestamps: true,
             log_colored: true,
+            matcher_state_dir: None,
+            // snapshots older than an hour are about as useful as no snapshot at all
+            matcher_state_max_age: 3600,
+            scam_blocklist_path: None,
+            // per channel
+            message_cache_size: 100,
             database: Default::default(),
         }
     }
@@ -1,3 +1,4 @@
+use crate::{lockdown::SkipReason, module::action::ActionKind};
 use serenity::model::id::ChannelId;
 use thiserror::Error;
 
@@ -19,6 +20,12 @@ pub enum InternalError {
     ConversionFailed(&'static str),
     #[error("Missing guild ID")]
     MissingGuildID,
+    #[error("The {0} action can only be run against a message")]
+    ActionNeedsMessage(ActionKind),
+    #[error("The {0} action needs a channel to be run against a member")]
+    ActionNeedsChannel(ActionKind),
+    #[error("The channel {0} wasn't locked down: it {1}")]
+    ChannelNotLocked(ChannelId, SkipReason),
 }
 
 #[derive(Error, Debug)]
@@ -33,6 +40,10 @@ pub enum ArgumentError {
     ChannelNotInGuild(ChannelId),
     #[error("No such setting: {0}")]
     NoSuchSetting(String),
+    #[error("The setting {0} isn't overridden in <#{1}>")]
+    NoSuchChannelOverride(String, ChannelId),
+    #[error("The setting {0} can't be overridden per channel")]
+    SettingNotOverridable(String),
     #[error("Invalid notify message format: {0}")]
     InvalidNotifyFormat(String),
     #[error("You do not have permission to run that command")]
@@ -45,4 +56,34 @@ pub enum ArgumentError {
     ExclusionLimit(usize, usize),
     #[error("The module already has the maximum amount of actions ({0} out of {1})")]
     ActionLimit(usize, usize),
+    #[error("The minimum score {0} isn't between 0 and {1}")]
+    InvalidMinScore(i64, u8),
+    #[error("The image distance {0} isn't between 0 and {1}")]
+    InvalidImageDistance(u32, u32),
+    #[error("Invalid filter pattern: {0}")]
+    InvalidFilterPattern(String),
+    #[error("Filter pattern already exists")]
+    FilterPatternAlreadyExists,
+    #[error("No such filter pattern")]
+    NoSuchFilterPattern,
+    #[error("The filter already has the maximum amount of patterns ({0} out of {1})")]
+    FilterPatternLimit(usize, usize),
+    #[error(
+        "Invalid domain: {0}. Use either a plain domain such as example.com or *.example.com to include subdomains"
+    )]
+    InvalidDomainPattern(String),
+    #[error("Not a channel mention or ID: {0}")]
+    InvalidChannel(String),
+    #[error("There is no raid going on")]
+    NoActiveRaid,
+    #[error("Invalid rule: {0}")]
+    InvalidRule(String),
+    #[error("Rule already exists")]
+    RuleAlreadyExists,
+    #[error("The module already has the maximum amount of rules ({0} out of {1})")]
+    RuleLimit(usize, usize),
+    #[error("Invalid script: {0}")]
+    InvalidScript(String),
+    #[error("The script failed: {0}")]
+    ScriptFailed(String),
 }
@@ -1,7 +1,13 @@
 mod interaction;
+mod member;
 mod message;
+mod reaction;
+mod thread;
 
-use crate::{ShardMetadata, VERSION};
+use crate::{
+    matcher::{member::MemberEvent, MatcherResponse, MessageEvent},
+    raid, ShardMetadata, VERSION,
+};
 use chrono::Utc;
 use log::*;
 use serenity::{
@@ -10,11 +16,12 @@ use serenity::{
     gateway::ConnectionStage,
     model::prelude::*,
 };
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 
 pub struct Handler {
-    msg_tx: broadcast::Sender<Arc<Message>>,
+    msg_tx: broadcast::Sender<MessageEvent>,
+    member_tx: broadcast::Sender<MemberEvent>,
+    action_tx: mpsc::Sender<MatcherResponse>,
 }
 
 #[async_trait]
@@ -66,14 +73,57 @@ impl EventHandler for Handler {
         message::process(&ctx, msg, &self.msg_tx).await;
     }
 
+    async fn message_update(
+        &self,
+        ctx: Context,
+        old: Option<Message>,
+        new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        message::process_update(&ctx, old, new, &event, &self.msg_tx).await;
+    }
+
+    // only single deletions can be ghost pings. bulk deletions are only ever done by bots and moderators, never by the
+    // messages' authors
+    async fn message_delete(&self, ctx: Context, channel: ChannelId, id: MessageId, guild: Option<GuildId>) {
+        if let Some(guild) = guild {
+            message::process_delete(&ctx, guild, channel, id).await;
+        }
+    }
+
+    async fn guild_member_addition(&self, ctx: Context, guild: GuildId, member: Member) {
+        raid::process_join(&ctx, guild, &member).await;
+        member::process_join(member, &self.member_tx);
+    }
+
+    async fn guild_member_update(&self, _: Context, old: Option<Member>, new: Member) {
+        member::process_update(old, new, &self.member_tx);
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        reaction::process_add(&ctx, &reaction, &self.action_tx).await;
+    }
+
+    async fn thread_create(&self, ctx: Context, thread: GuildChannel) {
+        thread::process_create(&ctx, thread, &self.msg_tx).await;
+    }
+
     async fn interaction_create(&self, ctx: Context, interact: Interaction) {
         interaction::process(ctx, interact).await;
     }
 }
 
 impl Handler {
-    pub fn new(msg_tx: broadcast::Sender<Arc<Message>>) -> Self {
-        Self { msg_tx }
+    pub fn new(
+        msg_tx: broadcast::Sender<MessageEvent>,
+        member_tx: broadcast::Sender<MemberEvent>,
+        action_tx: mpsc::Sender<MatcherResponse>,
+    ) -> Self {
+        Self {
+            msg_tx,
+            member_tx,
+            action_tx,
+        }
     }
 
     async fn set_info_activity(&self, ctx: &Context, shard: u64, shards: u64) {
@@ -42,6 +42,8 @@ async fn create_application_commands(ctx: &Context) -> anyhow::Result<()> {
         .await?,
         ApplicationCommand::create_global_application_command(&ctx.http, build_module_subcommand).await?,
         ApplicationCommand::create_global_application_command(&ctx.http, build_admin_subcommand).await?,
+        ApplicationCommand::create_global_application_command(&ctx.http, build_raid_subcommand).await?,
+        ApplicationCommand::create_global_application_command(&ctx.http, build_lockdown_subcommand).await?,
     ];
 
     trace!("Registered commands: {:#?}", cmds);
@@ -64,6 +66,22 @@ fn module_option(
             .add_string_choice("Invite link", "invite-link")
             .add_string_choice("Channel activity", "channel-activity")
             .add_string_choice("User activity", "user-activity")
+            .add_string_choice("Word filter", "word-filter")
+            .add_string_choice("Scam link", "scam-link")
+            .add_string_choice("Link policy", "link-policy")
+            .add_string_choice("Caps", "caps")
+            .add_string_choice("Unicode abuse", "unicode-abuse")
+            .add_string_choice("Attachments", "attachments")
+            .add_string_choice("Secret leak", "secret-leak")
+            .add_string_choice("New account", "new-account")
+            .add_string_choice("Ghost ping", "ghost-ping")
+            .add_string_choice("Raid", "raid")
+            .add_string_choice("Name filter", "name-filter")
+            .add_string_choice("Impersonation", "impersonation")
+            .add_string_choice("Reaction spam", "reaction-spam")
+            .add_string_choice("Thread spam", "thread-spam")
+            .add_string_choice("Custom rule", "custom-rule")
+            .add_string_choice("Script", "script")
     }
 }
 
@@ -74,6 +92,9 @@ fn build_module_subcommand(cmd: &mut CreateApplicationCommand) -> &mut CreateApp
         .create_option(build_exclusion_subcommand)
         .create_option(build_action_subcommand)
         .create_option(build_setting_subcommand)
+        .create_option(build_filter_subcommand)
+        .create_option(build_rule_subcommand)
+        .create_option(build_script_subcommand)
 }
 
 fn build_enabled_subcommand(opt: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
@@ -157,17 +178,26 @@ fn build_action_subcommand(opt: &mut CreateApplicationCommandOption) -> &mut Cre
                         .description("The action to add")
                         .add_string_choice("Remove message", "remove-message")
                         .add_string_choice("Notify", "notify")
+                        .add_string_choice("Direct message", "direct-message")
+                        .add_string_choice("Lockdown", "lockdown")
+                        .add_string_choice("Kick", "kick")
+                        .add_string_choice("Reset nickname", "reset-nickname")
                         .required(true)
                 })
                 .create_sub_option(|sub| {
                     sub.kind(ApplicationCommandOptionType::String)
                         .name("message")
-                        .description("The message to send, if applicable")
+                        .description("The message to send or the lockdown or kick reason, if applicable")
                 })
                 .create_sub_option(|sub| {
                     sub.kind(ApplicationCommandOptionType::Channel)
                         .name("channel")
-                        .description("The channel to send the message to, if applicable")
+                        .description("The channel to send the message to or to lock down, if applicable")
+                })
+                .create_sub_option(|sub| {
+                    sub.kind(ApplicationCommandOptionType::Integer)
+                        .name("min_score")
+                        .description("Only run the action for matches scoring at least this much out of 100")
                 })
         })
         .create_sub_option(|sub| {
@@ -193,6 +223,7 @@ fn build_setting_subcommand(opt: &mut CreateApplicationCommandOption) -> &mut Cr
                 .name("get")
                 .description("Displays all settings and their values for the module")
                 .create_sub_option(module_option(true))
+                .create_sub_option(setting_channel_option)
         })
         .create_sub_option(|sub| {
             sub.kind(ApplicationCommandOptionType::SubCommand)
@@ -211,6 +242,7 @@ fn build_setting_subcommand(opt: &mut CreateApplicationCommandOption) -> &mut Cr
                         .description("The value of the setting")
                         .required(true)
                 })
+                .create_sub_option(setting_channel_option)
         })
         .create_sub_option(|sub| {
             sub.kind(ApplicationCommandOptionType::SubCommand)
@@ -223,6 +255,106 @@ fn build_setting_subcommand(opt: &mut CreateApplicationCommandOption) -> &mut Cr
                         .description("The name of the setting")
                         .required(true)
                 })
+                .create_sub_option(setting_channel_option)
+        })
+}
+
+fn setting_channel_option(opt: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    opt.kind(ApplicationCommandOptionType::Channel)
+        .name("channel")
+        .description("Use the setting overridden in this channel instead of the guild-wide setting")
+}
+
+fn build_filter_subcommand(opt: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    opt.kind(ApplicationCommandOptionType::SubCommandGroup)
+        .name("filter")
+        .description("Modify the words filtered by the word filter module")
+        .create_sub_option(|sub| {
+            sub.kind(ApplicationCommandOptionType::SubCommand)
+                .name("list")
+                .description("Shows all filtered words")
+        })
+        .create_sub_option(|sub| {
+            sub.kind(ApplicationCommandOptionType::SubCommand)
+                .name("add")
+                .description("Adds a filtered word")
+                .create_sub_option(|sub| {
+                    sub.kind(ApplicationCommandOptionType::String)
+                        .name("pattern")
+                        .description("A word or phrase, a wildcard such as `spam*`, or a regex prefixed with `re:`")
+                        .required(true)
+                })
+        })
+        .create_sub_option(|sub| {
+            sub.kind(ApplicationCommandOptionType::SubCommand)
+                .name("remove")
+                .description("Removes a filtered word")
+                .create_sub_option(|sub| {
+                    sub.kind(ApplicationCommandOptionType::String)
+                        .name("pattern")
+                        .description("The filtered word to remove, exactly as it's listed")
+                        .required(true)
+                })
+        })
+}
+
+fn build_rule_subcommand(opt: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    opt.kind(ApplicationCommandOptionType::SubCommandGroup)
+        .name("rule")
+        .description("Modify the rules matched by the custom rule module")
+        .create_sub_option(|sub| {
+            sub.kind(ApplicationCommandOptionType::SubCommand)
+                .name("list")
+                .description("Shows all custom rules")
+        })
+        .create_sub_option(|sub| {
+            sub.kind(ApplicationCommandOptionType::SubCommand)
+                .name("add")
+                .description("Adds a custom rule")
+                .create_sub_option(|sub| {
+                    sub.kind(ApplicationCommandOptionType::String)
+                        .name("rule")
+                        .description("A rule such as `content matches /free nitro/ AND author.account_age < 1d`")
+                        .required(true)
+                })
+        })
+        .create_sub_option(|sub| {
+            sub.kind(ApplicationCommandOptionType::SubCommand)
+                .name("remove")
+                .description("Removes a custom rule based on its index")
+                .create_sub_option(|sub| {
+                    sub.kind(ApplicationCommandOptionType::Integer)
+                        .name("index")
+                        .description("The index of the rule to remove")
+                        .required(true)
+                })
+        })
+}
+
+fn build_script_subcommand(opt: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    opt.kind(ApplicationCommandOptionType::SubCommandGroup)
+        .name("script")
+        .description("Modify the script run by the script module")
+        .create_sub_option(|sub| {
+            sub.kind(ApplicationCommandOptionType::SubCommand)
+                .name("show")
+                .description("Shows the script")
+        })
+        .create_sub_option(|sub| {
+            sub.kind(ApplicationCommandOptionType::SubCommand)
+                .name("set")
+                .description("Replaces the script")
+                .create_sub_option(|sub| {
+                    sub.kind(ApplicationCommandOptionType::String)
+                        .name("script")
+                        .description("A Rhai script defining `fn is_match(msg)`. The key-value store is `this`")
+                        .required(true)
+                })
+        })
+        .create_sub_option(|sub| {
+            sub.kind(ApplicationCommandOptionType::SubCommand)
+                .name("clear")
+                .description("Removes the script and clears its key-value store")
         })
 }
 
@@ -237,6 +369,44 @@ fn build_admin_subcommand(opt: &mut CreateApplicationCommand) -> &mut CreateAppl
         })
 }
 
+fn build_raid_subcommand(cmd: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    cmd.name("raid")
+        .description("Manage raid protection")
+        .create_option(|opt| {
+            opt.kind(ApplicationCommandOptionType::SubCommand)
+                .name("end")
+                .description("End the raid, stop kicking new members and undo its lockdown and verification level")
+        })
+}
+
+fn build_lockdown_subcommand(cmd: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    cmd.name("lockdown")
+        .description("Stop everyone from sending messages in channels")
+        .create_option(|opt| {
+            opt.kind(ApplicationCommandOptionType::SubCommand)
+                .name("start")
+                .description("Lock down channels, denying Send Messages from @everyone")
+                .create_sub_option(lockdown_channels_option)
+                .create_sub_option(|sub| {
+                    sub.kind(ApplicationCommandOptionType::String)
+                        .name("reason")
+                        .description("The reason for the lockdown, shown in the locked channels")
+                })
+        })
+        .create_option(|opt| {
+            opt.kind(ApplicationCommandOptionType::SubCommand)
+                .name("end")
+                .description("Lift the lockdown, restoring the channels' permissions from before it")
+                .create_sub_option(lockdown_channels_option)
+        })
+}
+
+fn lockdown_channels_option(opt: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    opt.kind(ApplicationCommandOptionType::String)
+        .name("channels")
+        .description("Mentions or IDs of the channels. Defaults to all text channels")
+}
+
 pub async fn process(ctx: Context, interact: Interaction) {
     debug!("{:?}", interact);
 
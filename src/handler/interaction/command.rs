@@ -1,6 +1,8 @@
+mod lockdown;
 mod module;
+mod raid;
 
-use self::module::ModuleSubcommand;
+use self::{lockdown::LockdownSubcommand, module::ModuleSubcommand, raid::RaidSubcommand};
 use super::{enabled_string, respond, respond_embed, respond_success};
 use crate::{
     error::{ArgumentError, InternalError},
@@ -30,6 +32,8 @@ pub enum Command {
     Success,
     Module,
     SetAdminRole,
+    Raid,
+    Lockdown,
 }
 
 #[async_trait]
@@ -78,6 +82,8 @@ impl Command {
             Command::Status => status_command(ctx, interact).await,
             Command::Module => run_subcommand::<ModuleSubcommand>(ctx, interact, &interact.data.options).await,
             Command::SetAdminRole => set_admin_role(ctx, interact, &interact.data.options).await,
+            Command::Raid => run_subcommand::<RaidSubcommand>(ctx, interact, &interact.data.options).await,
+            Command::Lockdown => run_subcommand::<LockdownSubcommand>(ctx, interact, &interact.data.options).await,
         }
     }
 }
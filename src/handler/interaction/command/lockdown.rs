@@ -0,0 +1,107 @@
+use super::{check_permission, respond, SubcommandTrait};
+use crate::{error::ArgumentError, ext::UserdataExt, lockdown, DbPool};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        id::ChannelId,
+        interactions::application_command::{
+            ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+            ApplicationCommandInteractionDataOptionValue,
+        },
+    },
+    utils::parse_channel,
+};
+use strum::EnumString;
+
+#[derive(Debug, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum LockdownSubcommand {
+    Start,
+    End,
+}
+
+#[async_trait]
+impl SubcommandTrait for LockdownSubcommand {
+    async fn run(
+        self,
+        ctx: &Context,
+        interact: &ApplicationCommandInteraction,
+        options: &[ApplicationCommandInteractionDataOption],
+    ) -> anyhow::Result<()> {
+        check_permission(ctx, interact).await?;
+        let guild_id = interact.guild_id.ok_or(ArgumentError::NotSupportedInDM)?;
+        let db_pool = ctx.data.read().await.get_userdata::<DbPool>()?.clone();
+
+        // both options are optional so either one may be given alone, meaning they can't be read by their index
+        let channels = string_option(options, "channels").map(parse_channels).transpose()?;
+
+        match self {
+            LockdownSubcommand::Start => {
+                let reason = string_option(options, "reason");
+                let result = lockdown::start(&ctx.http, &db_pool, guild_id, channels.as_deref(), reason).await?;
+
+                let mut lines = Vec::new();
+                if !result.locked.is_empty() {
+                    lines.push(format!("Locked down {}", channel_list(&result.locked)));
+                }
+                for (channel, reason) in &result.skipped {
+                    lines.push(format!("Skipped <#{}>: it {}", channel, reason));
+                }
+
+                respond(ctx, interact, |m| {
+                    if lines.is_empty() {
+                        m.content("There were no unlocked text channels to lock down")
+                    } else {
+                        m.content(lines.join("\n"))
+                    }
+                })
+                .await
+            }
+            LockdownSubcommand::End => {
+                let unlocked = lockdown::end(&ctx.http, &db_pool, guild_id, channels.as_deref()).await?;
+
+                respond(ctx, interact, |m| {
+                    if unlocked.is_empty() {
+                        m.content("There were no locked channels to unlock")
+                    } else {
+                        m.content(format!("Lifted the lockdown from {}", channel_list(&unlocked)))
+                    }
+                })
+                .await
+            }
+        }
+    }
+}
+
+fn string_option<'a>(options: &'a [ApplicationCommandInteractionDataOption], name: &str) -> Option<&'a str> {
+    options
+        .iter()
+        .find(|opt| opt.name == name)
+        .and_then(|opt| match &opt.resolved {
+            Some(ApplicationCommandInteractionDataOptionValue::String(value)) => Some(value.as_str()),
+            _ => None,
+        })
+}
+
+// slash commands can't take a list of channels, so they're given as channel mentions or IDs in a single string
+fn parse_channels(value: &str) -> Result<Vec<ChannelId>, ArgumentError> {
+    value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            parse_channel(word)
+                .or_else(|| word.parse().ok())
+                .map(ChannelId)
+                .ok_or_else(|| ArgumentError::InvalidChannel(word.to_string()))
+        })
+        .collect()
+}
+
+fn channel_list(channels: &[ChannelId]) -> String {
+    channels
+        .iter()
+        .map(|channel| format!("<#{}>", channel))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
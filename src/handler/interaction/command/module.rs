@@ -1,10 +1,14 @@
 mod action;
 mod enabled;
 mod exclusion;
+mod filter;
+mod rule;
+mod script;
 mod setting;
 
 use self::{
-    action::ActionSubcommand, enabled::EnabledSubcommand, exclusion::ExclusionSubcommand, setting::SettingSubcommand,
+    action::ActionSubcommand, enabled::EnabledSubcommand, exclusion::ExclusionSubcommand, filter::FilterSubcommand,
+    rule::RuleSubcommand, script::ScriptSubcommand, setting::SettingSubcommand,
 };
 use super::{
     check_permission, enabled_string, respond, respond_embed, respond_success, run_subcommand, SubcommandTrait,
@@ -33,6 +37,9 @@ pub enum ModuleSubcommand {
     Action,
     Setting,
     Exclusion,
+    Filter,
+    Rule,
+    Script,
 }
 
 #[async_trait]
@@ -50,6 +57,9 @@ impl SubcommandTrait for ModuleSubcommand {
             ModuleSubcommand::Action => run_subcommand::<ActionSubcommand>(ctx, interact, options).await,
             ModuleSubcommand::Setting => run_subcommand::<SettingSubcommand>(ctx, interact, options).await,
             ModuleSubcommand::Exclusion => run_subcommand::<ExclusionSubcommand>(ctx, interact, options).await,
+            ModuleSubcommand::Filter => run_subcommand::<FilterSubcommand>(ctx, interact, options).await,
+            ModuleSubcommand::Rule => run_subcommand::<RuleSubcommand>(ctx, interact, options).await,
+            ModuleSubcommand::Script => run_subcommand::<ScriptSubcommand>(ctx, interact, options).await,
         }
     }
 }
@@ -3,6 +3,7 @@ use crate::{
     command_option,
     error::{ArgumentError, InternalError},
     ext::UserdataExt,
+    matcher::MAX_SCORE,
     module::{
         action::{Action, ActionKind},
         Module,
@@ -14,9 +15,14 @@ use serenity::{
     client::Context,
     model::interactions::application_command::{
         ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+        ApplicationCommandInteractionDataOptionValue,
     },
 };
-use std::{borrow::Cow, convert::TryInto, str::FromStr};
+use std::{
+    borrow::Cow,
+    convert::{TryFrom, TryInto},
+    str::FromStr,
+};
 use strum::EnumString;
 
 const NO_ACTIONS: &str =
@@ -87,6 +93,13 @@ async fn add_action(
 
     let message = optional_command_option!(options, 2, String)?.map(|val| val.as_str());
     let in_channel = optional_command_option!(options, 3, Channel)?.map(|ch| ch.id);
+    let min_score = match min_score_option(options) {
+        Some(score) => u8::try_from(score)
+            .ok()
+            .filter(|score| *score <= MAX_SCORE)
+            .ok_or(ArgumentError::InvalidMinScore(score, MAX_SCORE))?,
+        None => 0,
+    };
 
     let data = ctx.data.read().await;
     let db = data.get_userdata::<DbPool>()?.get()?;
@@ -115,13 +128,40 @@ async fn add_action(
                 })?,
             )
         }
+        ActionKind::DirectMessage => Action::direct_message(message.map(Cow::Borrowed).ok_or_else(|| {
+            InternalError::ImpossibleCase(format!("message is {:?} while ActionKind is {}", message, action_kind))
+        })?),
         ActionKind::RemoveMessage => Action::remove_message(),
+        ActionKind::Kick => Action::kick(message.map(Cow::Borrowed)),
+        ActionKind::ResetNickname => Action::reset_nickname(),
+        ActionKind::Lockdown => {
+            if let Some(in_channel) = in_channel {
+                let channels = module.guild().channels(ctx).await?;
+                if !channels.contains_key(&in_channel) {
+                    return Err(ArgumentError::ChannelNotInGuild(in_channel).into());
+                }
+            }
+
+            Action::lockdown(in_channel, message.map(Cow::Borrowed))
+        }
     };
 
-    module.add_action(&action, &db)?;
+    module.add_action(&action.with_min_score(min_score), &db)?;
     respond_success(ctx, interact).await
 }
 
+// the minimum score is looked up by name, since it's the last of the optional options and its position depends on which
+// of the others were given
+fn min_score_option(options: &[ApplicationCommandInteractionDataOption]) -> Option<i64> {
+    options
+        .iter()
+        .find(|opt| opt.name == "min_score")
+        .and_then(|opt| match &opt.resolved {
+            Some(ApplicationCommandInteractionDataOptionValue::Integer(value)) => Some(*value),
+            _ => None,
+        })
+}
+
 async fn remove_action(
     ctx: &Context,
     interact: &ApplicationCommandInteraction,
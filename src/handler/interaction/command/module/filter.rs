@@ -0,0 +1,128 @@
+use super::{respond, respond_embed, respond_success, SubcommandTrait};
+use crate::{
+    command_option,
+    error::ArgumentError,
+    ext::UserdataExt,
+    module::{
+        settings::{ModuleSettings, WordFilterSettings},
+        word_filter::FilterPattern,
+        Module, ModuleKind,
+    },
+    DbPool,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::application_command::{
+        ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+    },
+};
+use std::str::FromStr;
+use strum::EnumString;
+
+const NO_PATTERNS: &str = "There aren't any filtered words defined. Add some with the `/module filter add` command!";
+const MAX_PATTERNS: usize = 50;
+
+#[derive(Debug, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum FilterSubcommand {
+    List,
+    Add,
+    Remove,
+}
+
+#[async_trait]
+impl SubcommandTrait for FilterSubcommand {
+    async fn run(
+        self,
+        ctx: &Context,
+        interact: &ApplicationCommandInteraction,
+        options: &[ApplicationCommandInteractionDataOption],
+    ) -> anyhow::Result<()> {
+        let guild_id = interact.guild_id.ok_or(ArgumentError::NotSupportedInDM)?;
+        let module = {
+            let data = ctx.data.read().await;
+            let db = data.get_userdata::<DbPool>()?.get()?;
+            Module::get_module_for_guild(guild_id, ModuleKind::WordFilter, &db)?
+        };
+
+        match self {
+            FilterSubcommand::List => list_patterns(ctx, interact, module).await,
+            FilterSubcommand::Add => add_pattern(ctx, interact, options, module).await,
+            FilterSubcommand::Remove => remove_pattern(ctx, interact, options, module).await,
+        }
+    }
+}
+
+async fn list_patterns(ctx: &Context, interact: &ApplicationCommandInteraction, module: Module) -> anyhow::Result<()> {
+    let data = ctx.data.read().await;
+    let db = data.get_userdata::<DbPool>()?.get()?;
+    let settings = module.get_settings_as::<WordFilterSettings>(&db)?;
+
+    if settings.patterns.is_empty() {
+        respond(ctx, interact, |m| m.content(NO_PATTERNS)).await
+    } else {
+        respond_embed(ctx, interact, |e| {
+            e.title(format!(
+                "Filtered words ({} out of {})",
+                settings.patterns.len(),
+                MAX_PATTERNS
+            ));
+            e.description(
+                settings
+                    .patterns
+                    .iter()
+                    .map(|p| format!("`{}`", p))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+        })
+        .await
+    }
+}
+
+async fn add_pattern(
+    ctx: &Context,
+    interact: &ApplicationCommandInteraction,
+    options: &[ApplicationCommandInteractionDataOption],
+    module: Module,
+) -> anyhow::Result<()> {
+    let pattern = FilterPattern::from_str(command_option!(options, 0, String)?)?;
+
+    let data = ctx.data.read().await;
+    let db = data.get_userdata::<DbPool>()?.get()?;
+    let mut settings = module.get_settings_as::<WordFilterSettings>(&db)?;
+
+    if settings.patterns.len() >= MAX_PATTERNS {
+        return Err(ArgumentError::FilterPatternLimit(settings.patterns.len(), MAX_PATTERNS).into());
+    } else if settings.patterns.contains(&pattern) {
+        return Err(ArgumentError::FilterPatternAlreadyExists.into());
+    }
+
+    settings.patterns.push(pattern);
+    // compile the patterns just to see they're valid, so a broken regex never ends up in the database
+    settings.patterns.compile(settings.normalize, settings.word_boundary)?;
+
+    module.set_settings(&ModuleSettings::from(settings), &db)?;
+    respond_success(ctx, interact).await
+}
+
+async fn remove_pattern(
+    ctx: &Context,
+    interact: &ApplicationCommandInteraction,
+    options: &[ApplicationCommandInteractionDataOption],
+    module: Module,
+) -> anyhow::Result<()> {
+    let pattern = FilterPattern::from_str(command_option!(options, 0, String)?)?;
+
+    let data = ctx.data.read().await;
+    let db = data.get_userdata::<DbPool>()?.get()?;
+    let mut settings = module.get_settings_as::<WordFilterSettings>(&db)?;
+
+    if settings.patterns.remove(&pattern) {
+        module.set_settings(&ModuleSettings::from(settings), &db)?;
+        respond_success(ctx, interact).await
+    } else {
+        Err(ArgumentError::NoSuchFilterPattern.into())
+    }
+}
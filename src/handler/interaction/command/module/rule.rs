@@ -0,0 +1,128 @@
+use super::{respond, respond_embed, respond_success, SubcommandTrait};
+use crate::{
+    command_option,
+    error::ArgumentError,
+    ext::UserdataExt,
+    module::{
+        rule::Rule,
+        settings::{CustomRuleSettings, ModuleSettings},
+        Module, ModuleKind,
+    },
+    DbPool,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::application_command::{
+        ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+    },
+};
+use std::{convert::TryInto, str::FromStr};
+use strum::EnumString;
+
+const NO_RULES: &str = "There aren't any custom rules defined. Add some with the `/module rule add` command!";
+const MAX_RULES: usize = 20;
+
+#[derive(Debug, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum RuleSubcommand {
+    List,
+    Add,
+    Remove,
+}
+
+#[async_trait]
+impl SubcommandTrait for RuleSubcommand {
+    async fn run(
+        self,
+        ctx: &Context,
+        interact: &ApplicationCommandInteraction,
+        options: &[ApplicationCommandInteractionDataOption],
+    ) -> anyhow::Result<()> {
+        let guild_id = interact.guild_id.ok_or(ArgumentError::NotSupportedInDM)?;
+        let module = {
+            let data = ctx.data.read().await;
+            let db = data.get_userdata::<DbPool>()?.get()?;
+            Module::get_module_for_guild(guild_id, ModuleKind::CustomRule, &db)?
+        };
+
+        match self {
+            RuleSubcommand::List => list_rules(ctx, interact, module).await,
+            RuleSubcommand::Add => add_rule(ctx, interact, options, module).await,
+            RuleSubcommand::Remove => remove_rule(ctx, interact, options, module).await,
+        }
+    }
+}
+
+async fn list_rules(ctx: &Context, interact: &ApplicationCommandInteraction, module: Module) -> anyhow::Result<()> {
+    let data = ctx.data.read().await;
+    let db = data.get_userdata::<DbPool>()?.get()?;
+    let settings = module.get_settings_as::<CustomRuleSettings>(&db)?;
+
+    if settings.rules.is_empty() {
+        respond(ctx, interact, |m| m.content(NO_RULES)).await
+    } else {
+        respond_embed(ctx, interact, |e| {
+            e.title(format!("Custom rules ({} out of {})", settings.rules.len(), MAX_RULES));
+            e.description(
+                settings
+                    .rules
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, rule)| format!("{}: `{}`", idx, rule))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+        })
+        .await
+    }
+}
+
+async fn add_rule(
+    ctx: &Context,
+    interact: &ApplicationCommandInteraction,
+    options: &[ApplicationCommandInteractionDataOption],
+    module: Module,
+) -> anyhow::Result<()> {
+    let rule = Rule::from_str(command_option!(options, 0, String)?)?;
+
+    let data = ctx.data.read().await;
+    let db = data.get_userdata::<DbPool>()?.get()?;
+    let mut settings = module.get_settings_as::<CustomRuleSettings>(&db)?;
+
+    if settings.rules.len() >= MAX_RULES {
+        return Err(ArgumentError::RuleLimit(settings.rules.len(), MAX_RULES).into());
+    } else if settings.rules.contains(&rule) {
+        return Err(ArgumentError::RuleAlreadyExists.into());
+    }
+
+    settings.rules.push(rule);
+    // compile the rules just to see their regexes are valid, so a broken regex never ends up in the database
+    settings.rules.compile()?;
+
+    module.set_settings(&ModuleSettings::from(settings), &db)?;
+    respond_success(ctx, interact).await
+}
+
+async fn remove_rule(
+    ctx: &Context,
+    interact: &ApplicationCommandInteraction,
+    options: &[ApplicationCommandInteractionDataOption],
+    module: Module,
+) -> anyhow::Result<()> {
+    let index = *command_option!(options, 0, Integer)?;
+    let index = index.try_into().map_err(|_| ArgumentError::I64OutOfRange(index))?;
+
+    let data = ctx.data.read().await;
+    let db = data.get_userdata::<DbPool>()?.get()?;
+    let mut settings = module.get_settings_as::<CustomRuleSettings>(&db)?;
+
+    if settings.rules.is_empty() {
+        respond(ctx, interact, |m| m.content(NO_RULES)).await
+    } else if settings.rules.remove(index).is_some() {
+        module.set_settings(&ModuleSettings::from(settings), &db)?;
+        respond_success(ctx, interact).await
+    } else {
+        Err(ArgumentError::UsizeOutOfRange(index).into())
+    }
+}
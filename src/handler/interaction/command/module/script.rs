@@ -0,0 +1,106 @@
+use super::{respond, respond_success, SubcommandTrait};
+use crate::{
+    command_option,
+    error::ArgumentError,
+    ext::UserdataExt,
+    module::{
+        script::{self, ScriptEngine, ScriptSource},
+        settings::{ModuleSettings, ScriptSettings},
+        Module, ModuleKind,
+    },
+    DbPool,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::application_command::{
+        ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+    },
+};
+use std::str::FromStr;
+use strum::EnumString;
+
+const NO_SCRIPT: &str = "There isn't a script defined. Set one with the `/module script set` command!";
+
+#[derive(Debug, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ScriptSubcommand {
+    Show,
+    Set,
+    Clear,
+}
+
+#[async_trait]
+impl SubcommandTrait for ScriptSubcommand {
+    async fn run(
+        self,
+        ctx: &Context,
+        interact: &ApplicationCommandInteraction,
+        options: &[ApplicationCommandInteractionDataOption],
+    ) -> anyhow::Result<()> {
+        let guild_id = interact.guild_id.ok_or(ArgumentError::NotSupportedInDM)?;
+        let module = {
+            let data = ctx.data.read().await;
+            let db = data.get_userdata::<DbPool>()?.get()?;
+            Module::get_module_for_guild(guild_id, ModuleKind::Script, &db)?
+        };
+
+        match self {
+            ScriptSubcommand::Show => show_script(ctx, interact, module).await,
+            ScriptSubcommand::Set => set_script(ctx, interact, options, module).await,
+            ScriptSubcommand::Clear => clear_script(ctx, interact, module).await,
+        }
+    }
+}
+
+async fn show_script(ctx: &Context, interact: &ApplicationCommandInteraction, module: Module) -> anyhow::Result<()> {
+    let settings = {
+        let data = ctx.data.read().await;
+        let db = data.get_userdata::<DbPool>()?.get()?;
+        module.get_settings_as::<ScriptSettings>(&db)?
+    };
+
+    if settings.script.is_empty() {
+        respond(ctx, interact, |m| m.content(NO_SCRIPT)).await
+    } else {
+        respond(ctx, interact, |m| {
+            m.content(format!("```rust\n{}\n```", settings.script))
+        })
+        .await
+    }
+}
+
+async fn set_script(
+    ctx: &Context,
+    interact: &ApplicationCommandInteraction,
+    options: &[ApplicationCommandInteractionDataOption],
+    module: Module,
+) -> anyhow::Result<()> {
+    let script = ScriptSource::from_str(command_option!(options, 0, String)?)?;
+    // compile the script just to see it's valid, so a broken script never ends up in the database
+    ScriptEngine::new().compile(&script)?;
+
+    {
+        let data = ctx.data.read().await;
+        let db = data.get_userdata::<DbPool>()?.get()?;
+        let mut settings = module.get_settings_as::<ScriptSettings>(&db)?;
+        settings.script = script;
+        module.set_settings(&ModuleSettings::from(settings), &db)?;
+    }
+
+    respond_success(ctx, interact).await
+}
+
+// clearing the script clears its store as well, so the next script starts from scratch
+async fn clear_script(ctx: &Context, interact: &ApplicationCommandInteraction, module: Module) -> anyhow::Result<()> {
+    {
+        let data = ctx.data.read().await;
+        let db = data.get_userdata::<DbPool>()?.get()?;
+        let mut settings = module.get_settings_as::<ScriptSettings>(&db)?;
+        settings.script = ScriptSource::default();
+        module.set_settings(&ModuleSettings::from(settings), &db)?;
+        script::delete_store(module.guild(), &db)?;
+    }
+
+    respond_success(ctx, interact).await
+}
@@ -1,16 +1,19 @@
 use super::{resolve_module, respond, respond_embed, SubcommandTrait};
 use crate::{
     command_option,
+    error::ArgumentError,
     ext::UserdataExt,
     handler::interaction::respond_success,
-    module::{settings::Settings, Module},
-    DbPool,
+    module::{cache::ModuleCache, settings::Settings, Module},
+    optional_command_option, DbPool,
 };
 use serenity::{
     async_trait,
     client::Context,
-    model::interactions::application_command::{
-        ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+    model::{
+        id::ChannelId,
+        interactions::application_command::{ApplicationCommandInteraction, ApplicationCommandInteractionDataOption},
+        misc::Mentionable,
     },
 };
 use strum::EnumString;
@@ -33,18 +36,45 @@ impl SubcommandTrait for SettingSubcommand {
     ) -> anyhow::Result<()> {
         let module = resolve_module(ctx, interact, options).await?;
 
+        // the channel is always the last, optional option
+        let channel = match self {
+            SettingSubcommand::Get => optional_command_option!(options, 1, Channel)?,
+            SettingSubcommand::Set => optional_command_option!(options, 3, Channel)?,
+            SettingSubcommand::Reset => optional_command_option!(options, 2, Channel)?,
+        }
+        .map(|ch| ch.id);
+
+        if let Some(channel) = channel {
+            let channels = module.guild().channels(ctx).await?;
+            if !channels.contains_key(&channel) {
+                return Err(ArgumentError::ChannelNotInGuild(channel).into());
+            }
+        }
+
         match self {
-            SettingSubcommand::Get => get_settings(ctx, interact, module).await,
-            SettingSubcommand::Set => set_setting(ctx, interact, options, module).await,
-            SettingSubcommand::Reset => reset_setting(ctx, interact, options, module).await,
+            SettingSubcommand::Get => get_settings(ctx, interact, module, channel).await,
+            SettingSubcommand::Set => set_setting(ctx, interact, options, module, channel).await,
+            SettingSubcommand::Reset => reset_setting(ctx, interact, options, module, channel).await,
         }
     }
 }
 
-async fn get_settings(ctx: &Context, interact: &ApplicationCommandInteraction, module: Module) -> anyhow::Result<()> {
+async fn get_settings(
+    ctx: &Context,
+    interact: &ApplicationCommandInteraction,
+    module: Module,
+    channel: Option<ChannelId>,
+) -> anyhow::Result<()> {
     let data = ctx.data.read().await;
     let db = data.get_userdata::<DbPool>()?.get()?;
-    let settings = module.get_settings(&db)?;
+    let (settings, overridden) = match channel {
+        Some(channel) => {
+            let overrides = module.get_channel_overrides(channel, &db)?;
+            let overridden = overrides.iter().map(|row| row.setting.clone()).collect();
+            (module.get_channel_settings(channel, overrides, &db)?, overridden)
+        }
+        None => (module.get_settings(&db)?, Vec::new()),
+    };
     let values = settings.get_all();
 
     if values.is_empty() {
@@ -55,13 +85,24 @@ async fn get_settings(ctx: &Context, interact: &ApplicationCommandInteraction, m
     } else {
         respond_embed(ctx, interact, |e| {
             e.title(format!("Settings for the `{}` module", module.kind()));
+            // mentions don't render in embed titles
+            if let Some(channel) = channel {
+                e.description(format!("The settings in effect in {}", channel.mention()));
+            }
             e.fields(values.into_iter().map(|(k, v)| {
+                let source = if overridden.iter().any(|setting| setting == k) {
+                    " (overridden in this channel)"
+                } else {
+                    ""
+                };
+
                 (
                     k, // field name
                     format!(
-                        "{}\nValue: `{}` (default: `{}`)",
+                        "{}\nValue: `{}`{} (default: `{}`)",
                         settings.description_for(k).unwrap(),
                         v,
+                        source,
                         settings.default_for(k).unwrap(),
                     ), // field value
                     false, // inline
@@ -77,16 +118,37 @@ async fn set_setting(
     interact: &ApplicationCommandInteraction,
     options: &[ApplicationCommandInteractionDataOption],
     module: Module,
+    channel: Option<ChannelId>,
 ) -> anyhow::Result<()> {
     let name = command_option!(options, 1, String)?;
     let value = command_option!(options, 2, String)?;
 
     let data = ctx.data.read().await;
     let db = data.get_userdata::<DbPool>()?.get()?;
+    let module_cache = data.get_userdata::<ModuleCache>()?;
     let mut settings = module.get_settings(&db)?;
 
     settings.set(name, value)?;
-    module.set_settings(&settings, &db)?;
+    match channel {
+        Some(_) if module.kind().guild_only_settings().contains(&name.as_str()) => {
+            return Err(ArgumentError::SettingNotOverridable(name.clone()).into());
+        }
+        Some(channel) => {
+            // only the one setting is stored as an override so the channel keeps following the guild's other settings.
+            // it's stored in the same form the settings would store it
+            let (_, value) = settings
+                .get_all()
+                .into_iter()
+                .find(|(setting, _)| *setting == name.as_str())
+                .ok_or_else(|| ArgumentError::NoSuchSetting(name.clone()))?;
+            module.set_channel_override(channel, name, &value, &db)?;
+            let overrides = module.get_channel_overrides(channel, &db)?;
+            module_cache
+                .update_channel_overrides(module, channel, overrides)
+                .await?;
+        }
+        None => module.set_settings(&settings, &db)?,
+    }
     respond_success(ctx, interact).await
 }
 
@@ -95,14 +157,28 @@ async fn reset_setting(
     interact: &ApplicationCommandInteraction,
     options: &[ApplicationCommandInteractionDataOption],
     module: Module,
+    channel: Option<ChannelId>,
 ) -> anyhow::Result<()> {
     let name = command_option!(options, 1, String)?;
 
     let data = ctx.data.read().await;
     let db = data.get_userdata::<DbPool>()?.get()?;
+    let module_cache = data.get_userdata::<ModuleCache>()?;
     let mut settings = module.get_settings(&db)?;
 
+    // resetting a channel's setting removes its override so it goes back to following the guild's setting
     settings.reset(name)?;
-    module.set_settings(&settings, &db)?;
+    match channel {
+        Some(channel) => {
+            if !module.remove_channel_override(channel, name, &db)? {
+                return Err(ArgumentError::NoSuchChannelOverride(name.clone(), channel).into());
+            }
+            let overrides = module.get_channel_overrides(channel, &db)?;
+            module_cache
+                .update_channel_overrides(module, channel, overrides)
+                .await?;
+        }
+        None => module.set_settings(&settings, &db)?,
+    }
     respond_success(ctx, interact).await
 }
@@ -0,0 +1,63 @@
+use super::{check_permission, respond, SubcommandTrait};
+use crate::{
+    error::ArgumentError,
+    ext::{DurationExt, UserdataExt},
+    raid::RaidTracker,
+    DbPool,
+};
+use chrono::Utc;
+use humantime::format_duration;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::application_command::{
+        ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+    },
+};
+use strum::EnumString;
+
+#[derive(Debug, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum RaidSubcommand {
+    End,
+}
+
+#[async_trait]
+impl SubcommandTrait for RaidSubcommand {
+    async fn run(
+        self,
+        ctx: &Context,
+        interact: &ApplicationCommandInteraction,
+        _: &[ApplicationCommandInteractionDataOption],
+    ) -> anyhow::Result<()> {
+        check_permission(ctx, interact).await?;
+        let guild_id = interact.guild_id.ok_or(ArgumentError::NotSupportedInDM)?;
+
+        match self {
+            RaidSubcommand::End => {
+                let (tracker, db_pool) = {
+                    let data = ctx.data.read().await;
+                    (
+                        data.get_userdata::<RaidTracker>()?.clone(),
+                        data.get_userdata::<DbPool>()?.clone(),
+                    )
+                };
+                let raid = tracker
+                    .end_raid(ctx, &db_pool, guild_id)
+                    .await?
+                    .ok_or(ArgumentError::NoActiveRaid)?;
+                let duration = (Utc::now() - raid.started_at).round_to_seconds();
+
+                respond(ctx, interact, |m| {
+                    m.content(format!(
+                        "Raid ({}) ended after {}. {} members were kicked",
+                        raid.reason,
+                        format_duration(duration),
+                        raid.kicked
+                    ))
+                })
+                .await
+            }
+        }
+    }
+}
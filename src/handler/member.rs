@@ -0,0 +1,48 @@
+use crate::matcher::member::MemberEvent;
+use log::*;
+use serenity::model::guild::Member;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+pub fn process_join(member: Member, member_tx: &broadcast::Sender<MemberEvent>) {
+    if member.user.bot {
+        return;
+    }
+
+    send_member(member, true, member_tx);
+}
+
+// the update event is sent for role changes and such as well, but only name changes are interesting to the matchers. if
+// the old member isn't cached it's not known what changed, and matching them anyway would match them again on every
+// role change, so they're left alone. the member was matched when they joined anyway
+pub fn process_update(old: Option<Member>, new: Member, member_tx: &broadcast::Sender<MemberEvent>) {
+    if new.user.bot {
+        return;
+    }
+
+    match old {
+        Some(old) if old.nick != new.nick || old.user.name != new.user.name => (),
+        Some(_) => return,
+        None => {
+            debug!(
+                "Member {} in {} updated but their previous state isn't cached, not matching",
+                new.user.id, new.guild_id
+            );
+            return;
+        }
+    }
+
+    debug!("Member {} in {} changed their name", new.user.id, new.guild_id);
+    send_member(new, false, member_tx);
+}
+
+fn send_member(member: Member, joined: bool, member_tx: &broadcast::Sender<MemberEvent>) {
+    let event = MemberEvent {
+        member: Arc::new(member),
+        joined,
+    };
+
+    if member_tx.send(event).is_err() {
+        error!("Sending member to broadcast channel failed (channel closed)");
+    }
+}
@@ -1,19 +1,51 @@
-use crate::{ext::UserdataExt, latency_counter::LatencyCounter};
+use crate::{
+    error::InternalError,
+    ext::UserdataExt,
+    latency_counter::LatencyCounter,
+    matcher::{ghost_ping::GhostPingStore, MessageEvent},
+    module::{cache::ModuleCache, ModuleKind},
+};
 use chrono::Utc;
 use log::*;
+use serde::Deserialize;
+use serde_json::json;
 use serenity::{
     client::Context,
-    model::channel::{Message, MessageType},
+    http::{request::RequestBuilder, routing::RouteInfo},
+    model::{
+        channel::{Message, MessageType, Sticker},
+        event::MessageUpdateEvent,
+        id::{ChannelId, GuildId, MessageId, StickerId},
+    },
 };
 use std::{sync::Arc, time::Duration};
 use tokio::sync::broadcast;
 
-pub async fn process(ctx: &Context, msg: Message, msg_tx: &broadcast::Sender<Arc<Message>>) {
+#[derive(Deserialize)]
+struct RawStickerItems {
+    #[serde(default)]
+    sticker_items: Vec<RawStickerItem>,
+}
+
+#[derive(Deserialize)]
+struct RawStickerItem {
+    id: StickerId,
+    name: String,
+    format_type: u64,
+}
+
+pub async fn process(ctx: &Context, mut msg: Message, msg_tx: &broadcast::Sender<MessageEvent>) {
     // straight-up ignore bot messages and non-regular messages
     if is_from_bot(&msg) || !is_regular(&msg) {
         return;
     }
 
+    if may_only_have_stickers(&msg) {
+        if let Err(e) = read_sticker_items(ctx, &mut msg).await {
+            warn!("Failed to read the stickers of message {}: {}", msg.id, e);
+        }
+    }
+
     let delay = (Utc::now() - msg.timestamp).num_milliseconds();
     // debug!("{:?}", msg);
     debug!(
@@ -21,7 +53,8 @@ pub async fn process(ctx: &Context, msg: Message, msg_tx: &broadcast::Sender<Arc
         delay, msg.timestamp
     );
 
-    if let Err(e) = process_message(msg, msg_tx) {
+    let thread_parent = thread_parent(ctx, msg.guild_id, msg.channel_id).await;
+    if let Err(e) = process_message(msg, false, thread_parent, msg_tx) {
         error!("Message processing failed: {}", e)
     }
 
@@ -32,18 +65,189 @@ pub async fn process(ctx: &Context, msg: Message, msg_tx: &broadcast::Sender<Arc
     }
 }
 
-fn process_message(msg: Message, msg_tx: &broadcast::Sender<Arc<Message>>) -> anyhow::Result<()> {
+// the update event only has the fields that changed, so the full edited message comes from the cache. messages that
+// aren't cached anymore (or weren't ever, e.g. ones sent before the bot started) can't be matched
+pub async fn process_update(
+    ctx: &Context,
+    old: Option<Message>,
+    new: Option<Message>,
+    event: &MessageUpdateEvent,
+    msg_tx: &broadcast::Sender<MessageEvent>,
+) {
+    // embeds being added to a message after the fact trigger an update as well, but those aren't edits by the user
+    if event.content.is_none() || event.edited_timestamp.is_none() {
+        return;
+    }
+
+    let msg = if let Some(msg) = new {
+        msg
+    } else {
+        debug!("Edited message {} isn't cached, not matching it", event.id);
+        return;
+    };
+
+    if is_from_bot(&msg) || !is_regular(&msg) {
+        return;
+    }
+
+    // a message that was already edited keeps its edit timestamp, so e.g. pinning it looks like another edit. the
+    // message was already matched with the same content, so it's only matched again if the content changed
+    if old.is_some_and(|old| old.content == msg.content) {
+        debug!(
+            "Message {} updated without its content changing, not matching it",
+            msg.id
+        );
+        return;
+    }
+
+    debug!("Message {} edited at {:?}", msg.id, msg.edited_timestamp);
+    let thread_parent = thread_parent(ctx, msg.guild_id, msg.channel_id).await;
+    if let Err(e) = process_message(msg, true, thread_parent, msg_tx) {
+        error!("Edited message processing failed: {}", e)
+    }
+}
+
+// the module may have been disabled after the message was stored, so it's checked again before reporting anything
+pub async fn process_delete(ctx: &Context, guild: GuildId, channel: ChannelId, id: MessageId) {
+    let store = {
+        let data = ctx.data.read().await;
+        let module = match data.get_userdata::<ModuleCache>() {
+            Ok(cache) => cache.get(guild, ModuleKind::GhostPing).await,
+            Err(e) => {
+                error!("Failed to process deleted message: {:?}", e);
+                return;
+            }
+        };
+        if !module.is_enabled() {
+            return;
+        }
+
+        match data.get_userdata::<GhostPingStore>() {
+            Ok(store) => store.clone(),
+            Err(e) => {
+                error!("Failed to process deleted message: {:?}", e);
+                return;
+            }
+        }
+    };
+
+    if let Err(e) = store.notify_deleted(&ctx.http, guild, channel, id).await {
+        error!("Failed to notify about a ghost ping in {}: {}", channel, e);
+    }
+}
+
+fn process_message(
+    msg: Message,
+    edited: bool,
+    thread_parent: Option<ChannelId>,
+    msg_tx: &broadcast::Sender<MessageEvent>,
+) -> anyhow::Result<()> {
+    let event = MessageEvent {
+        msg: Arc::new(msg),
+        edited,
+        thread_name: false,
+        thread_parent,
+    };
+
     // dirty short-circuit side-effect
-    if msg.guild_id.is_some() && msg_tx.send(Arc::new(msg)).is_err() {
+    if event.msg.guild_id.is_some() && msg_tx.send(event).is_err() {
         error!("Sending message to broadcast channel failed (channel closed)");
     }
     Ok(())
 }
 
+// the thread's parent channel, if the channel is a thread. threads aren't in the cache's channels but in their guild's
+// active threads, so a channel that isn't found there either isn't a thread or the thread isn't cached
+// (Serenity deserializes a thread's parent channel as its category)
+pub(super) async fn thread_parent(ctx: &Context, guild: Option<GuildId>, channel: ChannelId) -> Option<ChannelId> {
+    let guild = guild?;
+    ctx.cache
+        .guild_field(guild, |g| {
+            g.threads
+                .iter()
+                .find(|thread| thread.id == channel)
+                .and_then(|thread| thread.category_id)
+        })
+        .await
+        .flatten()
+}
+
+// a message with nothing else in it has to have stickers, since it couldn't have been sent otherwise. stickers sent
+// along with text can't be told apart from plain text, so only these messages are checked for stickers
+fn may_only_have_stickers(msg: &Message) -> bool {
+    msg.content.is_empty() && msg.attachments.is_empty() && msg.embeds.is_empty() && msg.stickers.is_empty()
+}
+
+// the gateway sends a message's stickers as sticker_items, which Serenity doesn't deserialize, so they have to be read
+// from the raw message object. they're stored as the message's stickers so the matchers can count them as usual
+async fn read_sticker_items(ctx: &Context, msg: &mut Message) -> anyhow::Result<()> {
+    let raw = ctx
+        .http
+        .fire::<RawStickerItems>(
+            RequestBuilder::new(RouteInfo::GetMessage {
+                channel_id: msg.channel_id.0,
+                message_id: msg.id.0,
+            })
+            .build(),
+        )
+        .await?;
+
+    msg.stickers = stickers_from_items(raw.sticker_items)?;
+    debug!("Message {} has {} stickers", msg.id, msg.stickers.len());
+    Ok(())
+}
+
+// sticker items only have the sticker's ID, name and format, the rest is left empty
+fn stickers_from_items(items: Vec<RawStickerItem>) -> anyhow::Result<Vec<Sticker>> {
+    items
+        .into_iter()
+        .map(|item| {
+            serde_json::from_value(json!({
+                "id": item.id,
+                "pack_id": "0",
+                "name": item.name,
+                "description": "",
+                "tags": null,
+                "asset": "",
+                "preview_asset": null,
+                "format_type": item.format_type,
+            }))
+            .map_err(|_| InternalError::ConversionFailed("failed to build a sticker from a sticker item").into())
+        })
+        .collect()
+}
+
 fn is_from_bot(msg: &Message) -> bool {
     msg.author.bot
 }
 
+// replies are regular messages as far as the matchers are concerned
 fn is_regular(msg: &Message) -> bool {
-    msg.kind == MessageType::Regular
+    matches!(msg.kind, MessageType::Regular | MessageType::InlineReply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sticker_items_become_stickers() {
+        let raw = serde_json::from_value::<RawStickerItems>(json!({
+            "id": "1",
+            "content": "",
+            "sticker_items": [
+                { "id": "10", "name": "wave", "format_type": 1 },
+                { "id": "11", "name": "dance", "format_type": 3 },
+            ],
+        }))
+        .unwrap();
+
+        let stickers = stickers_from_items(raw.sticker_items).unwrap();
+        assert_eq!(stickers.len(), 2);
+        assert_eq!(stickers[0].id, StickerId(10));
+        assert_eq!(stickers[1].name, "dance");
+
+        let raw = serde_json::from_value::<RawStickerItems>(json!({ "id": "1", "content": "" })).unwrap();
+        assert!(stickers_from_items(raw.sticker_items).unwrap().is_empty());
+    }
 }
@@ -0,0 +1,104 @@
+use super::message;
+use crate::{
+    ext::UserdataExt,
+    matcher::{reaction_spam::ReactionTracker, Match, MatcherResponse},
+    module::{action::ActionTarget, settings::ReactionSpamSettings, Module, ModuleKind},
+};
+use log::*;
+use serenity::{client::Context, model::channel::Reaction};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::mpsc;
+
+pub async fn process_add(ctx: &Context, reaction: &Reaction, action_tx: &mpsc::Sender<MatcherResponse>) {
+    if let Err(e) = try_process_add(ctx, reaction, action_tx).await {
+        error!(
+            "Processing reaction to message {} in channel {} failed: {:?}",
+            reaction.message_id, reaction.channel_id, e
+        );
+    }
+}
+
+async fn try_process_add(
+    ctx: &Context,
+    reaction: &Reaction,
+    action_tx: &mpsc::Sender<MatcherResponse>,
+) -> anyhow::Result<()> {
+    let (guild, user, member) = match (reaction.guild_id, reaction.user_id, &reaction.member) {
+        (Some(guild), Some(user), Some(member)) => (guild, user, member),
+        _ => return Ok(()),
+    };
+
+    if ctx.cache.user(user).await.is_some_and(|user| user.bot) {
+        return Ok(());
+    }
+
+    // like with messages, the reactions in a thread fall under its parent channel's setting overrides
+    let channel = message::thread_parent(ctx, Some(guild), reaction.channel_id)
+        .await
+        .or(Some(reaction.channel_id));
+
+    let (tracker, settings) = {
+        let data = ctx.data.read().await;
+        let roles = Some(member.roles.as_slice());
+        let settings: ReactionSpamSettings =
+            match Module::load_for_matching(&data, guild, ModuleKind::ReactionSpam, channel, user, roles).await? {
+                Some(settings) => settings,
+                None => return Ok(()),
+            };
+
+        (data.get_userdata::<ReactionTracker>()?.clone(), settings)
+    };
+
+    if settings.reaction_count == 0 {
+        return Ok(());
+    }
+
+    let window = Duration::from_secs(settings.reaction_window);
+    let reactions = match tracker
+        .track(guild, user, reaction, settings.reaction_count, window)
+        .await
+    {
+        Some(reactions) => reactions,
+        None => return Ok(()),
+    };
+
+    info!(
+        "ReactionSpam in {}: user {} added {} reactions within {:?}",
+        guild,
+        user,
+        reactions.len(),
+        window
+    );
+
+    let reason = format!(
+        "added {} reactions within {} seconds",
+        reactions.len(),
+        settings.reaction_window
+    );
+    let matched = Match::over_limit(reactions.len(), settings.reaction_count, reason);
+
+    if settings.remove_reactions {
+        for tracked in reactions {
+            if let Err(e) = tracked
+                .channel
+                .delete_reaction(&ctx.http, tracked.message, Some(user), tracked.emoji)
+                .await
+            {
+                warn!(
+                    "Failed to remove reaction to message {} by {}: {}",
+                    tracked.message, user, e
+                );
+            }
+        }
+    }
+
+    let member = guild.member(ctx, user).await?;
+    action_tx
+        .send((
+            ModuleKind::ReactionSpam,
+            ActionTarget::Member(Arc::new(member)),
+            matched,
+        ))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use crate::{error::InternalError, matcher::MessageEvent};
+use chrono::{DateTime, Duration, Utc};
+use log::*;
+use serde::Deserialize;
+use serde_json::json;
+use serenity::{
+    client::Context,
+    http::{request::RequestBuilder, routing::RouteInfo},
+    model::{
+        channel::{GuildChannel, Message},
+        guild::Member,
+        id::UserId,
+    },
+};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+// the bot being added to an existing private thread is also reported as the thread being created. a thread older than
+// this when it's reported is assumed to be one of those
+const MAX_NEW_THREAD_AGE_SECONDS: i64 = 60;
+
+// Serenity doesn't deserialize who created a thread or when, so they have to be read from the raw channel object
+#[derive(Deserialize)]
+struct RawThread {
+    owner_id: UserId,
+    thread_metadata: Option<RawThreadMetadata>,
+}
+
+#[derive(Deserialize)]
+struct RawThreadMetadata {
+    create_timestamp: Option<DateTime<Utc>>,
+}
+
+pub async fn process_create(ctx: &Context, thread: GuildChannel, msg_tx: &broadcast::Sender<MessageEvent>) {
+    debug!("Thread {} created in {:?}", thread.id, thread.category_id);
+
+    let msg = match thread_message(ctx, &thread).await {
+        Ok(Some(msg)) => msg,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Processing thread {} in {} failed: {:?}", thread.id, thread.guild_id, e);
+            return;
+        }
+    };
+
+    if msg.author.bot {
+        return;
+    }
+
+    let event = MessageEvent {
+        msg: Arc::new(msg),
+        edited: false,
+        thread_name: true,
+        thread_parent: thread.category_id,
+    };
+
+    if msg_tx.send(event).is_err() {
+        error!("Sending thread to broadcast channel failed (channel closed)");
+    }
+}
+
+// the matchers work on messages, so the thread's name is matched as if it was a message sent in the thread by its
+// creator. None if the thread isn't new
+async fn thread_message(ctx: &Context, thread: &GuildChannel) -> anyhow::Result<Option<Message>> {
+    let raw = ctx
+        .http
+        .fire::<RawThread>(
+            RequestBuilder::new(RouteInfo::GetChannel {
+                channel_id: thread.id.0,
+            })
+            .build(),
+        )
+        .await?;
+
+    // threads created before Discord started recording their creation time don't have one, but they're old anyway.
+    // a thread started from a message shares its ID, so the ID's timestamp is the message's instead
+    let created_at = raw
+        .thread_metadata
+        .and_then(|metadata| metadata.create_timestamp)
+        .unwrap_or_else(|| thread.id.created_at());
+    if Utc::now() - created_at > Duration::seconds(MAX_NEW_THREAD_AGE_SECONDS) {
+        debug!(
+            "Thread {} was created at {}, not matching it as a new thread",
+            thread.id, created_at
+        );
+        return Ok(None);
+    }
+
+    let member = thread.guild_id.member(ctx, raw.owner_id).await?;
+    build_thread_message(thread, &member, created_at).map(Some)
+}
+
+// the message doesn't actually exist so it can't be deleted or replied to, it only has the thread's ID
+fn build_thread_message(thread: &GuildChannel, member: &Member, created_at: DateTime<Utc>) -> anyhow::Result<Message> {
+    let msg = serde_json::from_value(json!({
+        "id": thread.id,
+        "channel_id": thread.id,
+        "guild_id": thread.guild_id,
+        "author": member.user,
+        "member": member,
+        "content": thread.name,
+        "timestamp": created_at,
+        "edited_timestamp": null,
+        "type": 0,
+        "tts": false,
+        "pinned": false,
+        "mention_everyone": false,
+        "mentions": [],
+        "mention_roles": [],
+        "mention_channels": [],
+        "attachments": [],
+        "embeds": [],
+        "reactions": [],
+        "stickers": [],
+        "components": [],
+    }))
+    .map_err(|_| InternalError::ConversionFailed("failed to build a message from a thread"))?;
+
+    Ok(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thread_is_built_into_a_message() {
+        let thread = serde_json::from_value::<GuildChannel>(json!({
+            "id": "200",
+            "guild_id": "100",
+            "parent_id": "300",
+            "type": 11,
+            "name": "free nitro",
+            "position": 0,
+            "permission_overwrites": [],
+            "nsfw": false,
+        }))
+        .unwrap();
+        let member = serde_json::from_value::<Member>(json!({
+            "guild_id": "100",
+            "user": {
+                "id": "400",
+                "username": "someone",
+                "discriminator": "0001",
+                "avatar": null,
+            },
+            "nick": null,
+            "roles": [],
+            "joined_at": "2021-01-01T00:00:00Z",
+            "deaf": false,
+            "mute": false,
+        }))
+        .unwrap();
+        let created_at = Utc::now();
+
+        let msg = build_thread_message(&thread, &member, created_at).unwrap();
+        assert_eq!(msg.id.0, thread.id.0);
+        assert_eq!(msg.channel_id, thread.id);
+        assert_eq!(msg.guild_id, Some(thread.guild_id));
+        assert_eq!(msg.author.id, member.user.id);
+        assert_eq!(msg.member.map(|m| m.roles), Some(Vec::new()));
+        assert_eq!(msg.content, "free nitro");
+        assert_eq!(msg.timestamp, created_at);
+        assert!(msg.attachments.is_empty() && msg.mentions.is_empty());
+    }
+}
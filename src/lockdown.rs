@@ -0,0 +1,325 @@
+use crate::{models, DbConnPool};
+use diesel::prelude::*;
+use log::*;
+use serenity::{
+    http::Http,
+    model::{
+        channel::{ChannelType, GuildChannel, PermissionOverwrite, PermissionOverwriteType},
+        id::{ChannelId, GuildId, RoleId},
+        permissions::Permissions,
+    },
+};
+use std::{collections::HashMap, fmt::Display};
+
+// why one of the channels given to a lockdown wasn't locked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    NotInGuild,
+    NotLockable,
+    AlreadyLocked,
+    Failed,
+}
+
+#[derive(Debug, Default)]
+pub struct LockdownResult {
+    pub locked: Vec<ChannelId>,
+    pub skipped: Vec<(ChannelId, SkipReason)>,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // threads aren't listed among the guild's channels, so they end up here as well
+            SkipReason::NotInGuild => write!(f, "isn't a channel in this server"),
+            SkipReason::NotLockable => write!(f, "isn't a text channel"),
+            SkipReason::AlreadyLocked => write!(f, "is already locked down"),
+            SkipReason::Failed => write!(f, "couldn't be locked down"),
+        }
+    }
+}
+
+// locks the given channels, or all text channels if none are given, by denying Send Messages for @everyone. channels
+// that are already locked are left alone so their original overwrite isn't lost. every given channel that wasn't
+// locked is returned with the reason why, while a lockdown of all channels only reports the ones that failed
+pub async fn start(
+    http: &Http,
+    db_pool: &DbConnPool,
+    guild: GuildId,
+    channels: Option<&[ChannelId]>,
+    reason: Option<&str>,
+) -> anyhow::Result<LockdownResult> {
+    let already_locked = locked_channels(db_pool, guild)?;
+    let guild_channels = guild.channels(http).await?;
+    let mut result = LockdownResult::default();
+
+    let targets = select_targets(&guild_channels, channels, &already_locked, &mut result.skipped);
+    for channel in targets {
+        match lock_channel(http, db_pool, guild, channel, reason).await {
+            Ok(_) => result.locked.push(channel.id),
+            Err(e) => {
+                warn!("Failed to lock down channel {} in {}: {}", channel.id, guild, e);
+                result.skipped.push((channel.id, SkipReason::Failed));
+            }
+        }
+    }
+
+    info!(
+        "Locked down {} channels in {}, skipped {}",
+        result.locked.len(),
+        guild,
+        result.skipped.len()
+    );
+    Ok(result)
+}
+
+// unlocks the given channels, or all locked channels if none are given, restoring their @everyone overwrite to what it
+// was before the lockdown. returns the channels that were unlocked
+pub async fn end(
+    http: &Http,
+    db_pool: &DbConnPool,
+    guild: GuildId,
+    channels: Option<&[ChannelId]>,
+) -> anyhow::Result<Vec<ChannelId>> {
+    use crate::schema::lockdown_channels;
+
+    let snapshots = lockdown_channels::table
+        .filter(lockdown_channels::guild.eq(guild.0 as i64))
+        .load::<models::LockdownChannel>(&db_pool.get()?)?
+        .into_iter()
+        .filter(|snapshot| channels.is_none_or(|channels| channels.contains(&ChannelId(snapshot.channel as u64))))
+        .collect::<Vec<_>>();
+
+    let mut unlocked = Vec::with_capacity(snapshots.len());
+    for snapshot in snapshots {
+        let channel = ChannelId(snapshot.channel as u64);
+        match unlock_channel(http, db_pool, guild, &snapshot).await {
+            Ok(_) => unlocked.push(channel),
+            Err(e) => warn!("Failed to lift lockdown from channel {} in {}: {}", channel, guild, e),
+        }
+    }
+
+    info!("Lifted lockdown from {} channels in {}", unlocked.len(), guild);
+    Ok(unlocked)
+}
+
+pub fn locked_channels(db_pool: &DbConnPool, guild: GuildId) -> anyhow::Result<Vec<ChannelId>> {
+    use crate::schema::lockdown_channels;
+
+    let channels = lockdown_channels::table
+        .filter(lockdown_channels::guild.eq(guild.0 as i64))
+        .select(lockdown_channels::channel)
+        .load::<i64>(&db_pool.get()?)?
+        .into_iter()
+        .map(|channel| ChannelId(channel as u64))
+        .collect();
+
+    Ok(channels)
+}
+
+async fn lock_channel(
+    http: &Http,
+    db_pool: &DbConnPool,
+    guild: GuildId,
+    channel: &GuildChannel,
+    reason: Option<&str>,
+) -> anyhow::Result<()> {
+    use crate::schema::lockdown_channels;
+
+    let everyone = everyone_overwrite(guild);
+    let previous = channel.permission_overwrites.iter().find(|o| o.kind == everyone);
+
+    // the snapshot is stored before touching the channel, so a failure in between never loses the original overwrite
+    let snapshot = models::LockdownChannel {
+        guild: guild.0 as i64,
+        channel: channel.id.0 as i64,
+        allow: previous.map(|o| o.allow.bits() as i64),
+        deny: previous.map(|o| o.deny.bits() as i64),
+    };
+    diesel::insert_into(lockdown_channels::table)
+        .values(&snapshot)
+        .execute(&db_pool.get()?)?;
+    debug!("Stored lockdown snapshot {:?}", snapshot);
+
+    let (allow, deny) = previous.map_or((Permissions::empty(), Permissions::empty()), |o| (o.allow, o.deny));
+    let locked = channel
+        .id
+        .create_permission(
+            http,
+            &PermissionOverwrite {
+                allow: allow - Permissions::SEND_MESSAGES,
+                deny: deny | Permissions::SEND_MESSAGES,
+                kind: everyone,
+            },
+        )
+        .await;
+
+    // the channel wasn't touched, so it mustn't be remembered as locked either
+    if let Err(e) = locked {
+        delete_snapshot(db_pool, guild, channel.id)?;
+        return Err(e.into());
+    }
+
+    let notice = match reason {
+        Some(reason) => format!("This channel has been locked down: {}", reason),
+        None => String::from("This channel has been locked down"),
+    };
+    if let Err(e) = channel.id.say(http, notice).await {
+        warn!("Failed to send lockdown notice to {}: {}", channel.id, e);
+    }
+
+    Ok(())
+}
+
+async fn unlock_channel(
+    http: &Http,
+    db_pool: &DbConnPool,
+    guild: GuildId,
+    snapshot: &models::LockdownChannel,
+) -> anyhow::Result<()> {
+    let channel = ChannelId(snapshot.channel as u64);
+    let everyone = everyone_overwrite(guild);
+
+    match (snapshot.allow, snapshot.deny) {
+        (Some(allow), Some(deny)) => {
+            channel
+                .create_permission(
+                    http,
+                    &PermissionOverwrite {
+                        allow: Permissions::from_bits_truncate(allow as u64),
+                        deny: Permissions::from_bits_truncate(deny as u64),
+                        kind: everyone,
+                    },
+                )
+                .await?;
+        }
+        // there was no overwrite before the lockdown so the one it created is removed entirely
+        _ => channel.delete_permission(http, everyone).await?,
+    }
+
+    delete_snapshot(db_pool, guild, channel)?;
+
+    if let Err(e) = channel.say(http, "This channel is no longer locked down").await {
+        warn!("Failed to send lockdown notice to {}: {}", channel, e);
+    }
+
+    Ok(())
+}
+
+fn delete_snapshot(db_pool: &DbConnPool, guild: GuildId, channel: ChannelId) -> anyhow::Result<()> {
+    use crate::schema::lockdown_channels;
+
+    diesel::delete(
+        lockdown_channels::table.filter(
+            lockdown_channels::guild
+                .eq(guild.0 as i64)
+                .and(lockdown_channels::channel.eq(channel.0 as i64)),
+        ),
+    )
+    .execute(&db_pool.get()?)?;
+    Ok(())
+}
+
+// the channels to lock: the given ones that can be locked, or every lockable channel if none are given. the given
+// channels that can't be locked are skipped with the reason why
+fn select_targets<'a>(
+    guild_channels: &'a HashMap<ChannelId, GuildChannel>,
+    channels: Option<&[ChannelId]>,
+    already_locked: &[ChannelId],
+    skipped: &mut Vec<(ChannelId, SkipReason)>,
+) -> Vec<&'a GuildChannel> {
+    let channels = match channels {
+        Some(channels) => channels,
+        None => {
+            return guild_channels
+                .values()
+                .filter(|channel| is_lockable(channel) && !already_locked.contains(&channel.id))
+                .collect()
+        }
+    };
+
+    let mut targets: Vec<&GuildChannel> = Vec::with_capacity(channels.len());
+    for id in channels {
+        match guild_channels.get(id) {
+            None => skipped.push((*id, SkipReason::NotInGuild)),
+            Some(channel) if !is_lockable(channel) => skipped.push((*id, SkipReason::NotLockable)),
+            Some(_) if already_locked.contains(id) => skipped.push((*id, SkipReason::AlreadyLocked)),
+            Some(channel) => {
+                if !targets.iter().any(|target| target.id == *id) {
+                    targets.push(channel);
+                }
+            }
+        }
+    }
+    targets
+}
+
+// the @everyone role shares its ID with the guild
+fn everyone_overwrite(guild: GuildId) -> PermissionOverwriteType {
+    PermissionOverwriteType::Role(RoleId(guild.0))
+}
+
+fn is_lockable(channel: &GuildChannel) -> bool {
+    matches!(channel.kind, ChannelType::Text | ChannelType::News)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // text, voice and news channels
+    const GUILD_CHANNELS: &[(u64, u8)] = &[(10, 0), (11, 2), (12, 5), (13, 0)];
+
+    fn channels(channels: &[(u64, u8)]) -> HashMap<ChannelId, GuildChannel> {
+        channels
+            .iter()
+            .map(|(id, kind)| {
+                let channel = serde_json::from_value::<GuildChannel>(json!({
+                    "id": id.to_string(),
+                    "guild_id": "1",
+                    "type": kind,
+                    "name": "channel",
+                    "position": 0,
+                    "permission_overwrites": [],
+                    "nsfw": false,
+                }))
+                .unwrap();
+                (channel.id, channel)
+            })
+            .collect()
+    }
+
+    fn ids(targets: &[&GuildChannel]) -> Vec<u64> {
+        let mut ids = targets.iter().map(|channel| channel.id.0).collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn every_unlocked_text_channel_is_locked_by_default() {
+        let guild_channels = channels(GUILD_CHANNELS);
+        let mut skipped = Vec::new();
+
+        let targets = select_targets(&guild_channels, None, &[ChannelId(13)], &mut skipped);
+        assert_eq!(ids(&targets), [10, 12]);
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn given_channels_are_skipped_with_a_reason() {
+        let guild_channels = channels(GUILD_CHANNELS);
+        let mut skipped = Vec::new();
+        let given = [10, 10, 11, 13, 99].iter().copied().map(ChannelId).collect::<Vec<_>>();
+
+        let targets = select_targets(&guild_channels, Some(&given), &[ChannelId(13)], &mut skipped);
+        assert_eq!(ids(&targets), [10]);
+        assert_eq!(
+            skipped,
+            [
+                (ChannelId(11), SkipReason::NotLockable),
+                (ChannelId(13), SkipReason::AlreadyLocked),
+                (ChannelId(99), SkipReason::NotInGuild),
+            ]
+        );
+    }
+}
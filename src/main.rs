@@ -19,12 +19,15 @@ mod ext;
 mod guild_settings;
 mod handler;
 mod latency_counter;
+mod lockdown;
 mod logging;
 mod matcher;
 mod models;
 mod module;
+mod raid;
 mod schema;
 mod tasks;
+mod text;
 // separate the embedded migrations into their own module just so the panic_in_result_fn clippy lint can be allowed in
 // the entire module
 mod migrations {
@@ -42,14 +45,20 @@ use diesel::{
 use handler::Handler;
 use latency_counter::LatencyCounter;
 use log::*;
+use matcher::{
+    blocklist::DomainBlocklist, ghost_ping::GhostPingStore, member::MemberEvent, reaction_spam::ReactionTracker,
+    state::StateSnapshot, MatcherResponse, MessageEvent,
+};
 use module::cache::ModuleCache;
-use serenity::{http::Http, model::prelude::*, prelude::*, Client};
+use raid::RaidTracker;
+use serenity::{http::Http, prelude::*, Client};
 use std::{collections::HashMap, sync::Arc};
 use tokio::sync::{broadcast, mpsc};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 type DbConn = PooledConnection<ConnectionManager<PgConnection>>;
+type DbConnPool = Pool<ConnectionManager<PgConnection>>;
 
 #[derive(Debug)]
 struct ShardMetadata {
@@ -64,7 +73,13 @@ impl TypeMapKey for ShardMetadata {
 
 struct DbPool {}
 impl TypeMapKey for DbPool {
-    type Value = Pool<ConnectionManager<PgConnection>>;
+    type Value = DbConnPool;
+}
+
+// shared by the matchers that fetch things over HTTP, so there's only one connection pool
+struct HttpClient {}
+impl TypeMapKey for HttpClient {
+    type Value = reqwest::Client;
 }
 
 struct BotUptime {}
@@ -87,15 +102,44 @@ async fn main() -> anyhow::Result<()> {
     let module_cache = ModuleCache::populate_from_db(&db_pool.get()?)?;
 
     let (msg_tx, _) = broadcast::channel(64);
+    let (member_tx, _) = broadcast::channel(64);
     let (action_tx, action_rx) = mpsc::channel(8);
-
-    let mut client = create_discord_client(&config.discord_token, msg_tx.clone()).await?;
-    populate_userdata(&client, module_cache, db_pool, start_time).await?;
-
-    matcher::spawn_message_matchers(msg_tx, action_tx, client.data.clone());
+    let (shutdown_tx, _) = broadcast::channel(1);
+
+    // reactions are matched in the event handler itself, so it needs to be able to send matches to the action handler
+    let mut client = create_discord_client(
+        &config.discord_token,
+        msg_tx.clone(),
+        member_tx.clone(),
+        action_tx.clone(),
+    )
+    .await?;
+    populate_userdata(&client, &config, module_cache, db_pool, start_time).await?;
+
+    // edited messages are matched as a whole, which requires the rest of the message from the cache
+    client
+        .cache_and_http
+        .cache
+        .set_max_messages(config.message_cache_size)
+        .await;
+
+    let mut matchers = matcher::spawn_message_matchers(
+        msg_tx,
+        action_tx.clone(),
+        client.data.clone(),
+        Arc::clone(&client.cache_and_http),
+        &shutdown_tx,
+    );
+    matchers.extend(matcher::member::spawn_member_matchers(
+        member_tx,
+        action_tx,
+        client.data.clone(),
+        Arc::clone(&client.cache_and_http),
+        &shutdown_tx,
+    ));
     tasks::spawn_action_handler(&client, action_rx).await?;
     tasks::spawn_shard_latency_ticker(&client, config.latency_update_freq_ms);
-    tasks::spawn_termination_waiter(&client);
+    tasks::spawn_termination_waiter(&client, shutdown_tx, matchers);
 
     info!("Starting client...");
     match client.start_autosharded().await {
@@ -121,7 +165,12 @@ fn build_db_pool(config: &DatabaseConfig) -> anyhow::Result<Pool<ConnectionManag
     Ok(pool)
 }
 
-async fn create_discord_client(token: &str, msg_tx: broadcast::Sender<Arc<Message>>) -> anyhow::Result<Client> {
+async fn create_discord_client(
+    token: &str,
+    msg_tx: broadcast::Sender<MessageEvent>,
+    member_tx: broadcast::Sender<MemberEvent>,
+    action_tx: mpsc::Sender<MatcherResponse>,
+) -> anyhow::Result<Client> {
     info!("Initialising Discord client...");
 
     let http = Http::new_with_token(token);
@@ -137,7 +186,7 @@ async fn create_discord_client(token: &str, msg_tx: broadcast::Sender<Arc<Messag
     );
 
     info!("Initialising handler and client...");
-    let handler = Handler::new(msg_tx);
+    let handler = Handler::new(msg_tx, member_tx, action_tx);
     let client = Client::builder(token)
         .event_handler(handler)
         .application_id(appinfo.id.0) // this ID is technically the bot user ID but it also works as the application ID
@@ -150,6 +199,7 @@ async fn create_discord_client(token: &str, msg_tx: broadcast::Sender<Arc<Messag
 
 async fn populate_userdata(
     client: &Client,
+    config: &Config,
     module_cache: ModuleCache,
     db_pool: Pool<ConnectionManager<PgConnection>>,
     start_time: DateTime<Utc>,
@@ -160,8 +210,34 @@ async fn populate_userdata(
     data.insert::<ModuleCache>(module_cache);
     data.insert::<ShardMetadata>(HashMap::default());
     data.insert::<DbPool>(db_pool);
+    // redirects aren't followed so the shortened links can be expanded without visiting where they lead to.
+    // attachments are downloaded straight from the CDN, which doesn't redirect
+    data.insert::<HttpClient>(
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?,
+    );
     data.insert::<BotUptime>(start_time);
     data.insert::<LatencyCounter>(LatencyCounter::new());
+    data.insert::<GhostPingStore>(GhostPingStore::default());
+    data.insert::<RaidTracker>(RaidTracker::default());
+
+    // the reaction tracker isn't a matcher so it's restored here instead of when the matchers are built
+    if let Some(dir) = &config.matcher_state_dir {
+        info!("Matcher state snapshots enabled in {}", dir);
+        let snapshot = StateSnapshot::new(
+            dir.into(),
+            chrono::Duration::seconds(config.matcher_state_max_age as i64),
+        );
+        data.insert::<ReactionTracker>(ReactionTracker::restore(&snapshot));
+        data.insert::<StateSnapshot>(snapshot);
+    } else {
+        data.insert::<ReactionTracker>(ReactionTracker::default());
+    }
+
+    if let Some(path) = &config.scam_blocklist_path {
+        data.insert::<DomainBlocklist>(DomainBlocklist::new(path.into()));
+    }
 
     Ok(())
 }
@@ -1,37 +1,211 @@
+mod attachments;
+pub mod blocklist;
+mod caps;
 mod crosspost;
+mod custom_rule;
+mod emoji_spam;
+pub mod ghost_ping;
+mod impersonation;
 mod invite_link;
+mod link_policy;
+mod links;
 mod mass_ping;
+pub mod member;
+mod new_account;
+pub mod reaction_spam;
+mod scam_link;
+mod script;
+mod secret_leak;
 mod selfbot;
+pub mod state;
+mod thread_spam;
+mod unicode_abuse;
+mod word_filter;
 
 use crate::{
     error::InternalError,
-    ext::UserdataExt,
     module::{
-        cache::ModuleCache,
+        action::{ActionTarget, ThreadRemoval},
         settings::{ModuleSettings, Settings},
-        ModuleKind,
+        Module, ModuleKind,
     },
-    DbPool,
 };
+use attachments::Attachments;
+use caps::Caps;
 use crosspost::Crosspost;
+use custom_rule::CustomRule;
+use emoji_spam::EmojiSpam;
+use ghost_ping::GhostPing;
+use impersonation::Impersonation;
 use invite_link::InviteLink;
+use link_policy::LinkPolicy;
 use log::*;
 use mass_ping::MassPing;
+use new_account::NewAccount;
+use scam_link::ScamLink;
+use script::Script;
+use secret_leak::SecretLeak;
 use selfbot::Selfbot;
-use serenity::{async_trait, model::channel::Message, prelude::TypeMap};
-use std::{convert::TryInto, sync::Arc, time::Instant};
-use tokio::sync::{
-    broadcast::{self, error::RecvError},
-    mpsc, RwLock,
+use serenity::{
+    async_trait,
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId, RoleId, UserId},
+    },
+    prelude::TypeMap,
+    CacheAndHttp,
+};
+use state::StateSnapshot;
+use std::{any::type_name, convert::TryInto, sync::Arc, time::Instant};
+use thread_spam::ThreadSpam;
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc, RwLock,
+    },
+    task::JoinHandle,
 };
+use unicode_abuse::UnicodeAbuse;
+use word_filter::WordFilter;
+
+// match scores are on a scale from 0 to MAX_SCORE. something right at a module's threshold scores BORDERLINE_SCORE
+// and something well past it scores MAX_SCORE
+pub const MAX_SCORE: u8 = 100;
+const BORDERLINE_SCORE: u8 = 50;
+
+pub type MatcherResponse = (ModuleKind, ActionTarget, Match);
+
+// how confident a matcher is that what it matched is actually what the module is looking for, and why it matched.
+// actions can require a minimum score so e.g. a borderline match only notifies while a strong one is removed
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub score: u8,
+    pub reason: String,
+}
+
+// a message sent to the matchers. edited messages are the full message with its new content, not just the edit. a new
+// thread is sent as a message in the thread with the thread's name as its content, with thread_name set
+#[derive(Debug, Clone)]
+pub struct MessageEvent {
+    pub msg: Arc<Message>,
+    pub edited: bool,
+    pub thread_name: bool,
+    // the channel the thread the message was sent in belongs to, if it was sent in a thread the cache knows of
+    pub thread_parent: Option<ChannelId>,
+}
+
+// what a runner needs from the events it passes on to its matcher: who and where the event is from to load the module's
+// settings, and what the module's actions should target if the event matches
+trait MatcherEvent: Clone + Send + Sync + 'static {
+    fn guild_id(&self) -> anyhow::Result<GuildId>;
+    fn settings_channel(&self) -> Option<ChannelId>;
+    fn user_id(&self) -> UserId;
+    fn roles(&self) -> Option<&[RoleId]>;
+    // how the event is shown in the log when it's matched
+    fn describe(&self) -> String;
+    fn target(&self, kind: ModuleKind) -> ActionTarget;
+}
+
+impl MessageEvent {
+    // threads are considered a part of their parent channel, so e.g. a channel's setting overrides apply in its threads
+    // as well
+    pub fn channel(&self) -> ChannelId {
+        self.thread_parent.unwrap_or(self.msg.channel_id)
+    }
+}
+
+impl MatcherEvent for MessageEvent {
+    fn guild_id(&self) -> anyhow::Result<GuildId> {
+        Ok(self.msg.guild_id.ok_or(InternalError::MissingGuildID)?)
+    }
+
+    fn settings_channel(&self) -> Option<ChannelId> {
+        Some(self.channel())
+    }
+
+    fn user_id(&self) -> UserId {
+        self.msg.author.id
+    }
+
+    fn roles(&self) -> Option<&[RoleId]> {
+        self.msg.member.as_ref().map(|member| member.roles.as_slice())
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} {} in channel {} by {}",
+            if self.thread_name {
+                "new thread"
+            } else if self.edited {
+                "edited message"
+            } else {
+                "message"
+            },
+            self.msg.id,
+            self.msg.channel_id,
+            self.msg.author.id,
+        )
+    }
+
+    fn target(&self, kind: ModuleKind) -> ActionTarget {
+        if self.thread_name {
+            ActionTarget::Thread(
+                Arc::clone(&self.msg),
+                if kind == ModuleKind::ThreadSpam {
+                    ThreadRemoval::Delete
+                } else {
+                    ThreadRemoval::Rename
+                },
+            )
+        } else {
+            ActionTarget::Message(Arc::clone(&self.msg), self.channel())
+        }
+    }
+}
+
+impl Match {
+    pub fn new(score: u8, reason: String) -> Self {
+        Self {
+            score: score.min(MAX_SCORE),
+            reason,
+        }
+    }
+
+    // for matchers that either match or don't, such as a blocked domain or a filtered word
+    pub fn certain(reason: String) -> Self {
+        Self::new(MAX_SCORE, reason)
+    }
+
+    // scales the value linearly from a borderline score at the low end to the maximum score at the high end and above
+    pub fn scaled(value: usize, low: usize, high: usize, reason: String) -> Self {
+        let score = if value >= high || high <= low {
+            MAX_SCORE
+        } else {
+            let span = usize::from(MAX_SCORE - BORDERLINE_SCORE);
+            BORDERLINE_SCORE + (value.saturating_sub(low) * span / (high - low)) as u8
+        };
 
-pub type MatcherResponse = (ModuleKind, Arc<Message>);
+        Self::new(score, reason)
+    }
 
+    // for matchers with a single limit: right at the limit is borderline and twice the limit is certain
+    pub fn over_limit(value: usize, limit: usize, reason: String) -> Self {
+        Self::scaled(value, limit, limit.saturating_mul(2), reason)
+    }
+}
+
+// matchers match messages unless they're given another kind of event, such as the member matchers
 #[async_trait]
-trait Matcher {
+trait Matcher<E: MatcherEvent = MessageEvent>: Sized {
     type SettingsType: Settings;
-    async fn build(userdata: Arc<RwLock<TypeMap>>) -> (ModuleKind, Self);
-    async fn is_match(&mut self, settings: Self::SettingsType, msg: &Message) -> anyhow::Result<bool>;
+    async fn build(userdata: Arc<RwLock<TypeMap>>, cache_http: Arc<CacheAndHttp>)
+        -> anyhow::Result<(ModuleKind, Self)>;
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &E) -> anyhow::Result<Option<Match>>;
+
+    // matchers that keep state across messages should override this and load the saved state back in build()
+    fn save_state(&self, _: &StateSnapshot) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 // because the macro `matchers` always copies the action_tx, the original given action_tx isn't consumed, just cloned a
@@ -39,40 +213,76 @@ trait Matcher {
 // instances of the action_tx are in the matcher tasks and there won't be any dangling ones
 #[allow(clippy::needless_pass_by_value)]
 pub fn spawn_message_matchers(
-    msg_tx: broadcast::Sender<Arc<Message>>,
+    msg_tx: broadcast::Sender<MessageEvent>,
     action_tx: mpsc::Sender<MatcherResponse>,
     userdata: Arc<RwLock<TypeMap>>,
-) {
+    cache_http: Arc<CacheAndHttp>,
+    shutdown_tx: &broadcast::Sender<()>,
+) -> Vec<JoinHandle<()>> {
+    let mut handles = Vec::new();
+
     macro_rules! matchers {
         ($($matcher:ty),+) => {
             $(let rx = msg_tx.subscribe();
             let tx = action_tx.clone();
             let data = userdata.clone();
-            tokio::spawn(async move {
-                run_matcher::<$matcher>(rx, tx, data).await;
-            });)+
+            let cache = Arc::clone(&cache_http);
+            let shutdown = shutdown_tx.subscribe();
+            handles.push(tokio::spawn(async move {
+                run_matcher::<MessageEvent, $matcher>(rx, tx, data, cache, shutdown).await;
+            }));)+
         };
     }
 
-    matchers!(Crosspost, MassPing, Selfbot, InviteLink);
+    matchers!(
+        Crosspost,
+        MassPing,
+        Selfbot,
+        InviteLink,
+        WordFilter,
+        ScamLink,
+        LinkPolicy,
+        Caps,
+        UnicodeAbuse,
+        Attachments,
+        SecretLeak,
+        NewAccount,
+        GhostPing,
+        Impersonation,
+        EmojiSpam,
+        ThreadSpam,
+        CustomRule,
+        Script
+    );
+    handles
 }
 
-async fn run_matcher<M>(
-    rx: broadcast::Receiver<Arc<Message>>,
+async fn run_matcher<E, M>(
+    rx: broadcast::Receiver<E>,
     tx: mpsc::Sender<MatcherResponse>,
     userdata: Arc<RwLock<TypeMap>>,
+    cache_http: Arc<CacheAndHttp>,
+    shutdown: broadcast::Receiver<()>,
 ) where
-    M: Matcher,
-    ModuleSettings: TryInto<<M as Matcher>::SettingsType>,
-    <ModuleSettings as TryInto<<M as Matcher>::SettingsType>>::Error: 'static + Send + Sync,
+    E: MatcherEvent,
+    M: Matcher<E>,
+    ModuleSettings: TryInto<<M as Matcher<E>>::SettingsType>,
+    <ModuleSettings as TryInto<<M as Matcher<E>>::SettingsType>>::Error: 'static + Send + Sync,
 {
-    let (kind, matcher) = M::build(Arc::clone(&userdata)).await;
+    let (kind, matcher) = match M::build(Arc::clone(&userdata), cache_http).await {
+        Ok(built) => built,
+        Err(e) => {
+            error!("{}: building the matcher failed: {:?}", type_name::<M>(), e);
+            return;
+        }
+    };
     let runner = MatcherRunner {
         matcher,
         kind,
         rx,
         tx,
         userdata,
+        shutdown,
     };
 
     match runner.run().await {
@@ -81,80 +291,94 @@ async fn run_matcher<M>(
     }
 }
 
-struct MatcherRunner<M: Matcher> {
+struct MatcherRunner<E: MatcherEvent, M: Matcher<E>> {
     matcher: M,
     kind: ModuleKind,
-    rx: broadcast::Receiver<Arc<Message>>,
+    rx: broadcast::Receiver<E>,
     tx: mpsc::Sender<MatcherResponse>,
     userdata: Arc<RwLock<TypeMap>>,
+    shutdown: broadcast::Receiver<()>,
 }
 
-impl<M> MatcherRunner<M>
+impl<E, M> MatcherRunner<E, M>
 where
-    M: Matcher,
-    ModuleSettings: TryInto<<M as Matcher>::SettingsType>,
-    <ModuleSettings as TryInto<<M as Matcher>::SettingsType>>::Error: 'static + Send + Sync,
+    E: MatcherEvent,
+    M: Matcher<E>,
+    ModuleSettings: TryInto<<M as Matcher<E>>::SettingsType>,
+    <ModuleSettings as TryInto<<M as Matcher<E>>::SettingsType>>::Error: 'static + Send + Sync,
 {
     async fn run(mut self) -> anyhow::Result<()> {
         loop {
-            let msg = match self.rx.recv().await {
-                Ok(m) => m,
+            let received = tokio::select! {
+                _ = self.shutdown.recv() => {
+                    info!("{}: shutting down", self.kind);
+                    return self.save_state().await;
+                }
+                received = self.rx.recv() => received,
+            };
+
+            let event = match received {
+                Ok(e) => e,
                 Err(RecvError::Lagged(skipped)) => {
-                    warn!("{}: message rx lagged (skipped {} messages)", self.kind, skipped);
+                    warn!("{}: event rx lagged (skipped {} events)", self.kind, skipped);
                     continue;
                 }
                 Err(e) => return Err(e.into()),
             };
 
-            match self.run_matcher(&msg).await {
-                Ok(true) => {
+            match self.run_matcher(&event).await {
+                Ok(Some(matched)) => {
                     info!(
-                        "{} in {:?}: matched message {} in channel {} by {}",
-                        self.kind, msg.guild_id, msg.id, msg.channel_id, msg.author.id,
+                        "{} in {:?}: matched {} with score {}: {}",
+                        self.kind,
+                        event.guild_id().ok(),
+                        event.describe(),
+                        matched.score,
+                        matched.reason,
                     );
 
-                    self.tx.send((self.kind, msg)).await?;
+                    self.tx.send((self.kind, event.target(self.kind), matched)).await?;
                 }
                 Err(e) => {
-                    error!("{} in {:?}: matching failed: {:?}", self.kind, msg.guild_id, e);
+                    error!("{} in {:?}: matching failed: {:?}", self.kind, event.guild_id().ok(), e);
                     continue;
                 }
-                _ => (),
+                Ok(None) => (),
             };
         }
     }
 
-    async fn run_matcher(&mut self, msg: &Message) -> anyhow::Result<bool> {
+    async fn save_state(&self) -> anyhow::Result<()> {
         let data = self.userdata.read().await;
-        let guild_id = msg.guild_id.ok_or(InternalError::MissingGuildID)?;
-        let module = data.get_userdata::<ModuleCache>()?.get(guild_id, self.kind).await;
-
-        if !module.is_enabled() {
-            debug!("{} in {}: module disabled, not matching", self.kind, guild_id);
-            return Ok(false);
+        if let Some(snapshot) = data.get::<StateSnapshot>() {
+            self.matcher.save_state(snapshot)
+        } else {
+            debug!("{}: state snapshots not configured, not saving state", self.kind);
+            Ok(())
         }
+    }
 
-        let (settings, exclusions) = {
-            let db = data.get_userdata::<DbPool>()?.get()?;
-            // the author of enum_dispatch is an idiot so their TryInto impl returns a 'static &str as an error, which
-            // is everything but (it doesn't impl Error)
-            let settings = module.get_settings(&db)?.try_into().map_err(|_| {
-                InternalError::ConversionFailed("tried to convert ModuleSettings variant to invalid type")
-            })?;
-            let exclusions = module.get_exclusions(&db)?;
-
-            (settings, exclusions)
-        };
-
-        if let Some(member) = &msg.member {
-            if exclusions.should_exclude(&msg.author, member) {
-                debug!("{} in {}: user {} is excluded", self.kind, guild_id, msg.author.id);
-                return Ok(false);
+    async fn run_matcher(&mut self, event: &E) -> anyhow::Result<Option<Match>> {
+        let guild_id = event.guild_id()?;
+        let settings = {
+            let data = self.userdata.read().await;
+            match Module::load_for_matching(
+                &data,
+                guild_id,
+                self.kind,
+                event.settings_channel(),
+                event.user_id(),
+                event.roles(),
+            )
+            .await?
+            {
+                Some(settings) => settings,
+                None => return Ok(None),
             }
-        }
+        };
 
         let start = Instant::now();
-        let result = self.matcher.is_match(settings, msg).await;
+        let result = self.matcher.is_match(settings, event).await;
         debug!(
             "{} in {}: returned match result {:?} in {:?}",
             self.kind,
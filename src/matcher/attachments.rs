@@ -0,0 +1,176 @@
+use super::{Match, Matcher, MessageEvent};
+use crate::module::{settings::AttachmentsSettings, ModuleKind};
+use log::*;
+use serenity::{async_trait, model::channel::Attachment, prelude::TypeMap, CacheAndHttp};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// executables and scripts Windows runs more or less directly when opened
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "apk", "app", "bat", "cmd", "com", "cpl", "dll", "exe", "hta", "jar", "js", "jse", "lnk", "msi", "pif", "ps1",
+    "reg", "scr", "vbe", "vbs", "wsf",
+];
+// extensions a file pretends to be in a double extension, e.g. the pdf in invoice.pdf.exe
+const DECOY_EXTENSIONS: &[&str] = &[
+    "avi", "bmp", "csv", "doc", "docx", "gif", "jpeg", "jpg", "mkv", "mov", "mp3", "mp4", "odt", "pdf", "png", "ppt",
+    "pptx", "rtf", "txt", "wav", "webm", "webp", "xls", "xlsx",
+];
+const KILOBYTE: u64 = 1024;
+
+pub struct Attachments {}
+
+#[async_trait]
+impl Matcher for Attachments {
+    type SettingsType = AttachmentsSettings;
+    async fn build(_: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        Ok((ModuleKind::Attachments, Self {}))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+        if msg.attachments.is_empty() {
+            return Ok(None);
+        }
+
+        if settings.max_attachments > 0 && msg.attachments.len() > settings.max_attachments {
+            debug!("Message {} has {} attachments", msg.id, msg.attachments.len());
+            let reason = format!("has {} attachments", msg.attachments.len());
+            return Ok(Some(Match::over_limit(
+                msg.attachments.len(),
+                settings.max_attachments,
+                reason,
+            )));
+        }
+
+        Ok(msg
+            .attachments
+            .iter()
+            .filter_map(|attachment| disallowed(attachment, &settings))
+            .max_by_key(|matched| matched.score))
+    }
+}
+
+fn disallowed(attachment: &Attachment, settings: &AttachmentsSettings) -> Option<Match> {
+    // the limit is set by the guild, so a huge one must not overflow
+    let max_size = settings.max_size.saturating_mul(KILOBYTE);
+    if settings.max_size > 0 && attachment.size > max_size {
+        debug!("Attachment {} is {} bytes", attachment.filename, attachment.size);
+        let reason = format!(
+            "has the {} KB attachment {}",
+            attachment.size / KILOBYTE,
+            attachment.filename
+        );
+        return Some(Match::over_limit(attachment.size as usize, max_size as usize, reason));
+    }
+
+    let extensions = extensions(&attachment.filename);
+    if let Some(extension) = extensions.last() {
+        let blocked = (settings.block_executables && EXECUTABLE_EXTENSIONS.contains(&extension.as_str()))
+            || settings
+                .blocked_extensions
+                .iter()
+                .any(|blocked| blocked.trim_start_matches('.').eq_ignore_ascii_case(extension));
+
+        if blocked {
+            debug!("Attachment {} has a blocked extension", attachment.filename);
+            let reason = format!("has the attachment {} with a blocked extension", attachment.filename);
+            return Some(Match::certain(reason));
+        }
+    }
+
+    if settings.double_extensions && has_double_extension(&extensions) {
+        debug!("Attachment {} has a double extension", attachment.filename);
+        let reason = format!("has the attachment {} with a double extension", attachment.filename);
+        return Some(Match::certain(reason));
+    }
+
+    None
+}
+
+// every extension in the filename, lowercased and in order. the first part is the name itself and never an
+// extension, even if the file is named e.g. ".exe"
+fn extensions(filename: &str) -> Vec<String> {
+    filename
+        .split('.')
+        .skip(1)
+        .map(|extension| extension.trim().to_lowercase())
+        .filter(|extension| !extension.is_empty())
+        .collect()
+}
+
+// a decoy extension followed by something that isn't one, e.g. invoice.pdf.exe. archive.tar.gz and photo.jpg.png
+// aren't pretending to be anything
+fn has_double_extension(extensions: &[String]) -> bool {
+    match extensions {
+        [.., decoy, actual] => {
+            DECOY_EXTENSIONS.contains(&decoy.as_str()) && !DECOY_EXTENSIONS.contains(&actual.as_str())
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::settings::SettingList;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn attachment(filename: &str, size: u64) -> Attachment {
+        serde_json::from_value(json!({
+            "id": "100",
+            "filename": filename,
+            "size": size,
+            "url": "",
+            "proxy_url": "",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn extensions_skip_the_name() {
+        assert_eq!(extensions("Invoice.PDF.exe"), ["pdf", "exe"]);
+        assert_eq!(extensions(".exe"), ["exe"]);
+        assert!(extensions("README").is_empty());
+        assert!(extensions("trailing.").is_empty());
+    }
+
+    #[test]
+    fn only_decoys_followed_by_something_else_are_double_extensions() {
+        let double = |filename| has_double_extension(&extensions(filename));
+        assert!(double("invoice.pdf.exe"));
+        assert!(double("song.mp3.zip"));
+        assert!(!double("archive.tar.gz"));
+        assert!(!double("photo.jpg.png"));
+        assert!(!double("setup.exe"));
+    }
+
+    #[test]
+    fn executables_and_blocked_extensions_are_matched() {
+        let mut settings = AttachmentsSettings {
+            blocked_extensions: SettingList::from_str(".zip, rar").unwrap(),
+            ..AttachmentsSettings::default()
+        };
+
+        assert!(disallowed(&attachment("setup.EXE", 10), &settings).is_some());
+        assert!(disallowed(&attachment("files.zip", 10), &settings).is_some());
+        assert!(disallowed(&attachment("files.RAR", 10), &settings).is_some());
+        assert!(disallowed(&attachment("photo.png", 10), &settings).is_none());
+
+        settings.block_executables = false;
+        assert!(disallowed(&attachment("setup.exe", 10), &settings).is_none());
+    }
+
+    #[test]
+    fn size_limit_is_in_kilobytes() {
+        let mut settings = AttachmentsSettings {
+            max_size: 1,
+            ..AttachmentsSettings::default()
+        };
+        assert!(disallowed(&attachment("photo.png", KILOBYTE), &settings).is_none());
+        assert!(disallowed(&attachment("photo.png", KILOBYTE + 1), &settings).is_some());
+
+        settings.max_size = u64::MAX;
+        assert!(disallowed(&attachment("photo.png", u64::MAX), &settings).is_none());
+    }
+}
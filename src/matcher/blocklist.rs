@@ -0,0 +1,169 @@
+use log::*;
+use serenity::prelude::TypeMapKey;
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+// how often the file's modification time is checked. the check is cheap but there's no need to hit the filesystem for
+// every single message
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const COMMENT_PREFIX: char = '#';
+
+// a list of blocked domains read from a local file with one domain per line. hosts file-style lines such as
+// "0.0.0.0 example.com" work as well, so most public blocklists can be used as-is. the file is reloaded whenever it
+// changes on disk
+#[derive(Debug, Clone)]
+pub struct DomainBlocklist {
+    path: PathBuf,
+    domains: HashSet<String>,
+    modified: Option<SystemTime>,
+    last_checked: Option<Instant>,
+}
+
+impl TypeMapKey for DomainBlocklist {
+    type Value = DomainBlocklist;
+}
+
+impl DomainBlocklist {
+    pub fn new(path: PathBuf) -> Self {
+        let mut blocklist = Self {
+            path,
+            domains: HashSet::new(),
+            modified: None,
+            last_checked: None,
+        };
+        blocklist.reload_if_changed();
+        blocklist
+    }
+
+    // whether the host or any of its parent domains is blocked, so blocking example.com blocks sub.example.com as well
+    pub fn contains(&self, host: &str) -> bool {
+        let mut domain = host;
+        loop {
+            if self.domains.contains(domain) {
+                return true;
+            }
+
+            match domain.split_once('.') {
+                Some((_, parent)) if parent.contains('.') => domain = parent,
+                _ => return false,
+            }
+        }
+    }
+
+    // a blocklist that fails to load keeps the domains it had before, so a half-written file doesn't suddenly let
+    // everything through
+    pub fn reload_if_changed(&mut self) {
+        if self
+            .last_checked
+            .is_some_and(|checked| checked.elapsed() < RELOAD_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_checked = Some(Instant::now());
+
+        let modified = match fs::metadata(&self.path).and_then(|meta| meta.modified()) {
+            Ok(modified) => modified,
+            Err(e) => {
+                warn!("Failed to read domain blocklist {}: {}", self.path.display(), e);
+                return;
+            }
+        };
+
+        if self.modified == Some(modified) {
+            return;
+        }
+
+        match fs::read_to_string(&self.path) {
+            Ok(contents) => {
+                self.domains = parse_domains(&contents);
+                self.modified = Some(modified);
+                info!(
+                    "Loaded {} blocked domains from {}",
+                    self.domains.len(),
+                    self.path.display()
+                );
+            }
+            Err(e) => warn!("Failed to read domain blocklist {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+fn parse_domains(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.split(COMMENT_PREFIX).next()?.trim();
+            // in a hosts file the domain is the last field, otherwise it's the only one
+            let domain = line.split_whitespace().last()?;
+            let domain = domain.trim_end_matches('.').to_lowercase();
+            let domain = domain.strip_prefix("www.").map(String::from).unwrap_or(domain);
+
+            if domain.contains('.') {
+                Some(domain)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_blocklist(path: &PathBuf, contents: &str, modified: SystemTime) {
+        fs::write(path, contents).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn domains_are_parsed_from_lists_and_hosts_files() {
+        let domains = parse_domains("# a comment\nexample.com\n0.0.0.0 www.Example.org # blocked\nlocalhost\n\n");
+        assert_eq!(domains.len(), 2);
+        assert!(domains.contains("example.com") && domains.contains("example.org"));
+    }
+
+    #[test]
+    fn parent_domains_block_subdomains() {
+        let path = std::env::temp_dir().join(format!("blocklist-parents-{}", std::process::id()));
+        write_blocklist(&path, "example.com", SystemTime::now());
+        let blocklist = DomainBlocklist::new(path.clone());
+        fs::remove_file(&path).unwrap();
+
+        assert!(blocklist.contains("example.com"));
+        assert!(blocklist.contains("a.b.example.com"));
+        assert!(!blocklist.contains("notexample.com"));
+        assert!(!blocklist.contains("com"));
+    }
+
+    #[test]
+    fn changed_file_is_reloaded() {
+        let path = std::env::temp_dir().join(format!("blocklist-reload-{}", std::process::id()));
+        let loaded_at = SystemTime::now();
+        write_blocklist(&path, "example.com", loaded_at);
+        let mut blocklist = DomainBlocklist::new(path.clone());
+
+        write_blocklist(&path, "example.org", loaded_at + Duration::from_secs(1));
+        blocklist.reload_if_changed();
+        assert!(blocklist.contains("example.com"), "reloaded before the check interval");
+
+        blocklist.last_checked = None;
+        blocklist.reload_if_changed();
+        assert!(!blocklist.contains("example.com") && blocklist.contains("example.org"));
+
+        // a missing file keeps the domains loaded before
+        fs::remove_file(&path).unwrap();
+        blocklist.last_checked = None;
+        blocklist.reload_if_changed();
+        assert!(blocklist.contains("example.org"));
+    }
+}
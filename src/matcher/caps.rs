@@ -0,0 +1,162 @@
+use super::{Match, Matcher, MessageEvent};
+use crate::module::{settings::CapsSettings, ModuleKind};
+use log::*;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serenity::{async_trait, prelude::TypeMap, CacheAndHttp};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// user, role and channel mentions, custom emoji, emoji shortcodes and links. none of them are something the user typed
+// in caps or repeated by hand, e.g. a custom emoji's name or a link's path may well be in all caps
+static IGNORED_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"<(?:@[!&]?|#)\d+>|<a?:\w+:\d+>|:\w+:|(?i)\bhttps?://\S+")
+        .expect("failed to compile ignored text regex")
+});
+// a character has to be repeated at least this many times in a row to count as repetition, so that regular words with
+// double letters don't count
+const MINIMUM_CHARACTER_RUN: usize = 3;
+// fewer words than this can't really be repeated
+const MINIMUM_WORDS: usize = 3;
+
+pub struct Caps {}
+
+#[async_trait]
+impl Matcher for Caps {
+    type SettingsType = CapsSettings;
+    async fn build(_: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        Ok((ModuleKind::Caps, Self {}))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+        let content = IGNORED_REGEX.replace_all(&msg.content, " ");
+
+        if settings.caps_ratio > 0 {
+            if let Some(ratio) = caps_ratio(&content, settings.minimum_length) {
+                debug!("Message {} caps ratio: {}%", msg.id, ratio);
+                if ratio >= settings.caps_ratio as usize {
+                    let reason = format!("is {}% caps", ratio);
+                    return Ok(Some(Match::scaled(ratio, settings.caps_ratio as usize, 100, reason)));
+                }
+            }
+        }
+
+        if settings.repetition_ratio > 0 {
+            if let Some(ratio) = repetition_ratio(&content, settings.minimum_length) {
+                debug!("Message {} repetition ratio: {}%", msg.id, ratio);
+                if ratio >= settings.repetition_ratio as usize {
+                    let reason = format!("is {}% repetition", ratio);
+                    return Ok(Some(Match::scaled(
+                        ratio,
+                        settings.repetition_ratio as usize,
+                        100,
+                        reason,
+                    )));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+// the percentage of uppercase letters out of all the letters that have a case. letters without case, such as most
+// CJK characters, aren't counted at all. None if there are fewer cased letters than the minimum length
+fn caps_ratio(content: &str, minimum_length: usize) -> Option<usize> {
+    let (upper, cased) = content.chars().fold((0, 0), |(upper, cased), c| {
+        if c.is_uppercase() {
+            (upper + 1, cased + 1)
+        } else if c.is_lowercase() {
+            (upper, cased + 1)
+        } else {
+            (upper, cased)
+        }
+    });
+
+    if cased < minimum_length.max(1) {
+        None
+    } else {
+        Some(upper * 100 / cased)
+    }
+}
+
+// the larger of the character and word repetition ratios. None if there are fewer letters than the minimum length, so
+// short replies such as "no no no" aren't matched
+fn repetition_ratio(content: &str, minimum_length: usize) -> Option<usize> {
+    let letters = content.chars().filter(|c| c.is_alphabetic()).count();
+    if letters < minimum_length.max(1) {
+        None
+    } else {
+        Some(character_repetition_ratio(content).max(word_repetition_ratio(content)))
+    }
+}
+
+// the percentage of the non-whitespace characters that only repeat the character before them, e.g. "aaaaaaaa" is
+// 7/8 repetition
+fn character_repetition_ratio(content: &str) -> usize {
+    let chars = content
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+
+    if chars.is_empty() {
+        return 0;
+    }
+
+    let mut repeated = 0;
+    let mut run = 1;
+    for (idx, c) in chars.iter().enumerate().skip(1) {
+        if *c == chars[idx - 1] {
+            run += 1;
+        } else {
+            repeated += repetitions_in_run(run);
+            run = 1;
+        }
+    }
+    repeated += repetitions_in_run(run);
+
+    repeated * 100 / chars.len()
+}
+
+fn repetitions_in_run(run: usize) -> usize {
+    if run >= MINIMUM_CHARACTER_RUN {
+        run - 1
+    } else {
+        0
+    }
+}
+
+// the percentage of words that are the same as the word before them, e.g. "lol lol lol lol" is 3/4 repetition
+fn word_repetition_ratio(content: &str) -> usize {
+    let words = content
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+
+    if words.len() < MINIMUM_WORDS {
+        return 0;
+    }
+
+    let repeated = words.windows(2).filter(|pair| pair[0] == pair[1]).count();
+    repeated * 100 / words.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_messages_are_ignored() {
+        let minimum_length = CapsSettings::default().minimum_length;
+        for content in &["no no no", "ha ha ha", "go go go", "NOOOOO", "!!!!!!!!!!!!"] {
+            assert_eq!(repetition_ratio(content, minimum_length), None, "{}", content);
+            assert_eq!(caps_ratio(content, minimum_length), None, "{}", content);
+        }
+
+        assert!(repetition_ratio("lol lol lol lol", minimum_length).unwrap() >= 60);
+        assert!(repetition_ratio("nooooooooooooo", minimum_length).unwrap() >= 60);
+    }
+}
@@ -1,112 +1,281 @@
-use super::Matcher;
-use crate::module::{settings::CrosspostSettings, ModuleKind};
+use super::{
+    state::{self, StateSnapshot, UserState},
+    Match, Matcher, MessageEvent,
+};
+use crate::{
+    ext::UserdataExt,
+    module::{settings::CrosspostSettings, ModuleKind},
+    HttpClient,
+};
 use chrono::{DateTime, Duration, Utc};
 use circular_queue::CircularQueue;
+use futures::{stream, StreamExt};
+use image::{imageops::FilterType, io::Reader};
 use log::*;
 use nilsimsa::Nilsimsa;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use serenity::{
     async_trait,
     model::{
-        channel::Message,
+        channel::Attachment,
         id::{ChannelId, GuildId, UserId},
     },
-    prelude::TypeMap,
+    prelude::{Mentionable, TypeMap},
+    CacheAndHttp,
 };
 use std::{
     collections::{hash_map::Entry, HashMap},
+    io::Cursor,
     sync::Arc,
+    time::Duration as StdDuration,
 };
-use tokio::sync::RwLock;
+use tokio::{sync::RwLock, task};
 
 const HISTORY_SIZE: usize = 3;
+// don't bother downloading and decoding images larger than this (8 MiB, Discord's default upload limit)
+const MAX_IMAGE_SIZE: u64 = 8 * 1024 * 1024;
+const IMAGE_CONTENT_TYPE: &str = "image/";
+// the download size only limits the compressed image, which may well decode into gigabytes of pixels. images larger
+// than this in either dimension aren't decoded
+const MAX_IMAGE_DIMENSION: u32 = 4096;
+// the perceptual hash is computed from a grayscale image scaled to 9x8 pixels, which yields exactly 64 bits
+const DHASH_WIDTH: u32 = 9;
+const DHASH_HEIGHT: u32 = 8;
+// the matcher waits on the download, so a slow CDN must not hold up every other message for long
+const DOWNLOAD_TIMEOUT: StdDuration = StdDuration::from_secs(5);
+// a message can have up to 10 attachments, so all of them are downloaded in at most three rounds
+const MAX_CONCURRENT_DOWNLOADS: usize = 4;
 
 pub struct Crosspost {
+    downloader: Box<dyn AttachmentDownloader>,
     msg_history: HashMap<(GuildId, UserId), History>,
 }
 
+// fetches an attachment's contents so its image can be hashed
+#[async_trait]
+pub trait AttachmentDownloader: Send + Sync {
+    async fn download(&self, attachment: &Attachment) -> anyhow::Result<Vec<u8>>;
+}
+
+pub struct HttpAttachmentDownloader {
+    client: Client,
+}
+
+#[async_trait]
+impl AttachmentDownloader for HttpAttachmentDownloader {
+    async fn download(&self, attachment: &Attachment) -> anyhow::Result<Vec<u8>> {
+        let response = self
+            .client
+            .get(&attachment.url)
+            .timeout(DOWNLOAD_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
 #[derive(Debug)]
 struct History {
     history: CircularQueue<MessageInformation>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct MessageInformation {
-    hash: String,
+    hash: Option<String>,
+    attachments: Vec<AttachmentHash>,
     channel: ChannelId,
     timestamp: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AttachmentHash {
+    metadata: AttachmentMetadata,
+    image: Option<u64>,
+}
+
+// the attachment's ID and URL are unique per upload so they can't be used here, but re-uploading the same file keeps
+// its name, size and type. they're kept as they are instead of hashed so they compare the same after a restart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct AttachmentMetadata {
+    filename: String,
+    size: u64,
+    content_type: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistorySnapshot {
+    messages: Vec<MessageInformation>,
+}
+
 #[async_trait]
 impl Matcher for Crosspost {
     type SettingsType = CrosspostSettings;
 
-    async fn build(_: Arc<RwLock<TypeMap>>) -> (ModuleKind, Self) {
-        (
-            ModuleKind::Crosspost,
-            Self {
-                msg_history: HashMap::new(),
-            },
-        )
+    async fn build(userdata: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        let data = userdata.read().await;
+        let msg_history = data
+            .get::<StateSnapshot>()
+            .and_then(|snapshot| {
+                let histories = snapshot.load::<Vec<UserState<HistorySnapshot>>>(ModuleKind::Crosspost)?;
+                Some(restore_histories(histories, snapshot.max_age()))
+            })
+            .unwrap_or_default();
+
+        let client = data.get_userdata::<HttpClient>()?.clone();
+
+        let mut crosspost = Self::new(Box::new(HttpAttachmentDownloader { client }));
+        crosspost.msg_history = msg_history;
+        Ok((ModuleKind::Crosspost, crosspost))
     }
 
-    async fn is_match(&mut self, settings: Self::SettingsType, msg: &Message) -> anyhow::Result<bool> {
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        // a thread's name is usually the same as its first message, which would make every new thread a crosspost
+        if event.thread_name {
+            return Ok(None);
+        }
+
+        let msg = &event.msg;
         let content = &msg.content;
 
         // .len() on a string returns its length in bytes, not in graphemes, so messages such as 'äää' would be
         // considered since its length is six bytes, but only three characters
-        if content.len() < settings.minimum_length {
-            debug!("Not matching a message of length {}", content.len());
-            return Ok(false);
+        let hash = if content.len() < settings.minimum_length {
+            debug!("Not hashing message content of length {}", content.len());
+            None
+        } else {
+            Some(hash(content))
+        };
+
+        // attachments can't be added in an edit, so an edited message's attachments have already been hashed
+        let attachments = if settings.attachments && !event.edited {
+            hash_attachments(self.downloader.as_ref(), &msg.attachments, settings.image_hash).await
+        } else {
+            Vec::new()
+        };
+
+        if hash.is_none() && attachments.is_empty() {
+            debug!("Not matching a message with no hashable content or attachments");
+            return Ok(None);
         }
 
+        let info = MessageInformation {
+            hash,
+            attachments,
+            // the same message in a channel and one of its threads isn't posted across channels, it's just a reply
+            channel: event.channel(),
+            timestamp: msg.timestamp,
+        };
+
         match self.msg_history.entry((msg.guild_id.unwrap(), msg.author.id)) {
             Entry::Occupied(mut entry) => {
                 let history = entry.get_mut();
 
-                if history.compare(msg, settings.threshold, Duration::seconds(settings.timeout as i64)) {
-                    return Ok(true);
-                } else {
-                    history.push(msg);
+                let matched = history.compare(
+                    &info,
+                    settings.threshold,
+                    settings.image_distance.get(),
+                    Duration::seconds(settings.timeout as i64),
+                );
+
+                if matched.is_some() {
+                    return Ok(matched);
+                } else if !event.edited {
+                    // the edited message is still the same message, it's only compared against the others
+                    history.push(info);
                 }
             }
+            Entry::Vacant(_) if event.edited => (),
             Entry::Vacant(entry) => {
                 let mut new_history = History::default();
-                new_history.push(msg);
+                new_history.push(info);
                 entry.insert(new_history);
             }
         }
 
-        Ok(false)
+        Ok(None)
+    }
+
+    fn save_state(&self, snapshot: &StateSnapshot) -> anyhow::Result<()> {
+        let histories = state::flatten_users(&self.msg_history, |history| HistorySnapshot {
+            messages: history.history.asc_iter().cloned().collect(),
+        });
+
+        snapshot.save(ModuleKind::Crosspost, &histories)
+    }
+}
+
+impl Crosspost {
+    pub fn new(downloader: Box<dyn AttachmentDownloader>) -> Self {
+        Self {
+            downloader,
+            msg_history: HashMap::new(),
+        }
     }
 }
 
 impl History {
-    fn push(&mut self, msg: &Message) {
-        let info = MessageInformation {
-            hash: hash(&msg.content),
-            channel: msg.channel_id,
-            timestamp: msg.timestamp,
-        };
+    fn push(&mut self, info: MessageInformation) {
         self.history.push(info);
     }
 
-    fn compare(&self, msg: &Message, threshold: i16, timeout: Duration) -> bool {
-        let hash = hash(&msg.content);
+    // the strongest match against the messages in other channels. text is scored by how similar it is past the
+    // threshold, where equal text is certain. attachments are scored by how close their images are, where the same
+    // file is certain
+    fn compare(
+        &self,
+        info: &MessageInformation,
+        threshold: i16,
+        image_distance: u32,
+        timeout: Duration,
+    ) -> Option<Match> {
+        let mut best: Option<Match> = None;
+        let mut consider = |matched: Match| {
+            if best.as_ref().is_none_or(|best| matched.score > best.score) {
+                best = Some(matched);
+            }
+        };
 
         for hist in self
             .history
             .iter()
-            .filter(|info| info.channel != msg.channel_id && (Utc::now() - info.timestamp) < timeout)
+            .filter(|hist| hist.channel != info.channel && (Utc::now() - hist.timestamp) < timeout)
         {
-            let comparison = nilsimsa::compare(&hash, &hist.hash);
-            debug!("{} : {} -> {}", hash, hist.hash, comparison);
+            if let (Some(hash), Some(hist_hash)) = (&info.hash, &hist.hash) {
+                let comparison = nilsimsa::compare(hash, hist_hash);
+                debug!("{} : {} -> {}", hash, hist_hash, comparison);
 
-            if comparison >= threshold {
-                return true;
+                if comparison >= threshold {
+                    // the comparison is between -128 and 128, shift it to be non-negative for scaling
+                    consider(Match::scaled(
+                        (i32::from(comparison) + 128) as usize,
+                        (i32::from(threshold) + 128) as usize,
+                        256,
+                        format!("was posted in {} as well", hist.channel.mention()),
+                    ));
+                }
+            }
+
+            for attachment in &info.attachments {
+                if let Some(distance) = hist
+                    .attachments
+                    .iter()
+                    .filter_map(|a| a.distance(attachment, image_distance))
+                    .min()
+                {
+                    debug!("Attachment {:?} matches an attachment in history", attachment);
+                    consider(Match::scaled(
+                        (image_distance - distance) as usize,
+                        0,
+                        image_distance as usize,
+                        format!("has an attachment posted in {} as well", hist.channel.mention()),
+                    ));
+                }
             }
         }
 
-        false
+        best
     }
 }
 
@@ -118,6 +287,45 @@ impl Default for History {
     }
 }
 
+impl AttachmentHash {
+    // how many bits the attachments' image hashes differ by, if they're similar. the same file doesn't differ at all
+    fn distance(&self, other: &AttachmentHash, image_distance: u32) -> Option<u32> {
+        if self.metadata == other.metadata {
+            return Some(0);
+        }
+
+        match (self.image, other.image) {
+            (Some(a), Some(b)) => {
+                let distance = (a ^ b).count_ones();
+                debug!("{:016x} : {:016x} -> {}", a, b, distance);
+                Some(distance).filter(|distance| *distance <= image_distance)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn restore_histories(
+    histories: Vec<UserState<HistorySnapshot>>,
+    max_age: Duration,
+) -> HashMap<(GuildId, UserId), History> {
+    let msg_history = state::restore_users(histories, |snapshot| {
+        let mut history = History::default();
+        for info in snapshot
+            .messages
+            .into_iter()
+            .filter(|info| (Utc::now() - info.timestamp) < max_age)
+        {
+            history.push(info);
+        }
+
+        Some(history).filter(|history| !history.history.is_empty())
+    });
+
+    debug!("Restored {} message histories from snapshot", msg_history.len());
+    msg_history
+}
+
 fn hash(message: &str) -> String {
     let mut hasher = Nilsimsa::new();
     for word in message.split_whitespace() {
@@ -125,3 +333,213 @@ fn hash(message: &str) -> String {
     }
     hasher.digest()
 }
+
+// the images are downloaded a few at a time, so a message with many images doesn't hold up the matcher for the sum of
+// all their download timeouts
+async fn hash_attachments(
+    downloader: &dyn AttachmentDownloader,
+    attachments: &[Attachment],
+    image_hash: bool,
+) -> Vec<AttachmentHash> {
+    let hashes = attachments
+        .iter()
+        .map(|attachment| hash_attachment(downloader, attachment, image_hash))
+        .collect::<Vec<_>>();
+    stream::iter(hashes).buffered(MAX_CONCURRENT_DOWNLOADS).collect().await
+}
+
+async fn hash_attachment(
+    downloader: &dyn AttachmentDownloader,
+    attachment: &Attachment,
+    image_hash: bool,
+) -> AttachmentHash {
+    let image = if image_hash && is_image(attachment) {
+        match image_hash_attachment(downloader, attachment).await {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                warn!("Failed to compute image hash for attachment {}: {}", attachment.id, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    AttachmentHash {
+        metadata: AttachmentMetadata {
+            filename: attachment.filename.clone(),
+            size: attachment.size,
+            content_type: attachment.content_type.clone(),
+        },
+        image,
+    }
+}
+
+// the dimensions Discord reports for the attachment are checked before downloading it, and the ones in the image's
+// header before decoding it, since the attachment's might be missing
+fn is_image(attachment: &Attachment) -> bool {
+    attachment.size <= MAX_IMAGE_SIZE
+        && attachment
+            .width
+            .is_none_or(|width| width <= u64::from(MAX_IMAGE_DIMENSION))
+        && attachment
+            .height
+            .is_none_or(|height| height <= u64::from(MAX_IMAGE_DIMENSION))
+        && attachment
+            .content_type
+            .as_deref()
+            .is_some_and(|t| t.starts_with(IMAGE_CONTENT_TYPE))
+}
+
+async fn image_hash_attachment(downloader: &dyn AttachmentDownloader, attachment: &Attachment) -> anyhow::Result<u64> {
+    let bytes = downloader.download(attachment).await?;
+    // decoding and resizing the image is CPU-bound work, don't block the runtime with it
+    task::spawn_blocking(move || dhash(&bytes)).await?
+}
+
+// a difference hash: scale the image down to 9x8 grayscale pixels and compare each pixel to its right neighbour. the
+// result is resistant to scaling and recompression, so re-encoded copies of the same image end up with a close hash
+fn dhash(bytes: &[u8]) -> anyhow::Result<u64> {
+    let (width, height) = Reader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_dimensions()?;
+    if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+        return Err(anyhow::anyhow!(
+            "the image is {}x{}, larger than {}x{}",
+            width,
+            height,
+            MAX_IMAGE_DIMENSION,
+            MAX_IMAGE_DIMENSION
+        ));
+    }
+
+    let image = image::load_from_memory(bytes)?
+        .resize_exact(DHASH_WIDTH, DHASH_HEIGHT, FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..DHASH_HEIGHT {
+        for x in 0..DHASH_WIDTH - 1 {
+            let left = image.get_pixel(x, y).0[0];
+            let right = image.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, ImageOutputFormat, Luma};
+
+    const IMAGE_DISTANCE: u32 = 10;
+
+    // serves the attachment's URL as the image's contents, so each test attachment carries its own image
+    struct StubDownloader(HashMap<String, Vec<u8>>);
+
+    #[async_trait]
+    impl AttachmentDownloader for StubDownloader {
+        async fn download(&self, attachment: &Attachment) -> anyhow::Result<Vec<u8>> {
+            self.0
+                .get(&attachment.url)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("no such attachment"))
+        }
+    }
+
+    fn png(size: u32, pixel: impl Fn(u32, u32) -> u8) -> Vec<u8> {
+        let image = ImageBuffer::from_fn(size, size, |x, y| Luma([pixel(x * 64 / size, y * 64 / size)]));
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageLuma8(image)
+            .write_to(&mut bytes, ImageOutputFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    fn attachment(url: &str, bytes: &[u8]) -> Attachment {
+        sized_attachment(url, bytes, None)
+    }
+
+    fn sized_attachment(url: &str, bytes: &[u8], dimensions: Option<(u32, u32)>) -> Attachment {
+        serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "filename": format!("{}.png", url),
+            "proxy_url": url,
+            "size": bytes.len(),
+            "url": url,
+            "content_type": "image/png",
+            "width": dimensions.map(|(width, _)| width),
+            "height": dimensions.map(|(_, height)| height),
+        }))
+        .unwrap()
+    }
+
+    async fn hash_images(images: &[(&str, Vec<u8>)]) -> Vec<AttachmentHash> {
+        let attachments = images
+            .iter()
+            .map(|(url, bytes)| attachment(url, bytes))
+            .collect::<Vec<_>>();
+        let downloader = StubDownloader(
+            images
+                .iter()
+                .map(|(url, bytes)| (url.to_string(), bytes.clone()))
+                .collect(),
+        );
+
+        hash_attachments(&downloader, &attachments, true).await
+    }
+
+    #[tokio::test]
+    async fn rescaled_image_is_close() {
+        let hashes = hash_images(&[
+            ("original", png(64, |x, y| (x * 3 + y) as u8)),
+            ("rescaled", png(200, |x, y| (x * 3 + y) as u8)),
+        ])
+        .await;
+
+        assert!(hashes[0].image.is_some());
+        assert_ne!(hashes[0].metadata, hashes[1].metadata);
+        assert!(hashes[0].distance(&hashes[1], IMAGE_DISTANCE).is_some());
+    }
+
+    #[tokio::test]
+    async fn different_image_is_not_close() {
+        let hashes = hash_images(&[
+            ("original", png(64, |x, y| (x * 3 + y) as u8)),
+            ("mirrored", png(64, |x, y| ((63 - x) * 3 + y) as u8)),
+        ])
+        .await;
+
+        assert_eq!(hashes[0].distance(&hashes[1], IMAGE_DISTANCE), None);
+    }
+
+    #[tokio::test]
+    async fn failed_download_keeps_metadata() {
+        let bytes = png(64, |x, y| (x + y) as u8);
+        let attachments = [attachment("missing", &bytes)];
+        let hashes = hash_attachments(&StubDownloader(HashMap::new()), &attachments, true).await;
+
+        assert_eq!(hashes.len(), 1);
+        assert_eq!(hashes[0].image, None);
+        assert_eq!(hashes[0].distance(&hashes[0], IMAGE_DISTANCE), Some(0));
+    }
+
+    #[tokio::test]
+    async fn oversized_image_is_not_decoded() {
+        let bytes = png(64, |x, y| (x + y) as u8);
+        let attachments = [sized_attachment("huge", &bytes, Some((30000, 30000)))];
+        let downloader = StubDownloader(vec![(String::from("huge"), bytes)].into_iter().collect());
+        let hashes = hash_attachments(&downloader, &attachments, true).await;
+        assert_eq!(hashes[0].image, None);
+
+        // an image whose attachment doesn't tell its dimensions is checked by its header
+        let image = ImageBuffer::from_pixel(MAX_IMAGE_DIMENSION + 1, 1, Luma([0u8]));
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageLuma8(image)
+            .write_to(&mut bytes, ImageOutputFormat::Png)
+            .unwrap();
+        assert!(dhash(&bytes).is_err());
+    }
+}
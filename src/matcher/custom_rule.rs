@@ -0,0 +1,58 @@
+use super::{Match, Matcher, MessageEvent};
+use crate::module::{rule::CompiledRules, settings::CustomRuleSettings, ModuleKind};
+use log::*;
+use serenity::{
+    async_trait,
+    model::id::{ChannelId, GuildId},
+    prelude::TypeMap,
+    CacheAndHttp,
+};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::RwLock;
+
+pub struct CustomRule {
+    rules: HashMap<(GuildId, ChannelId), CachedRules>,
+}
+
+// the rules' regexes are compiled once and kept around as long as the rules stay the same, like the word filter. the
+// rules can be overridden per channel, so they're kept per channel so messages in different channels don't keep
+// replacing each other's rules
+struct CachedRules {
+    source: String,
+    rules: CompiledRules,
+}
+
+#[async_trait]
+impl Matcher for CustomRule {
+    type SettingsType = CustomRuleSettings;
+
+    async fn build(_: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        Ok((ModuleKind::CustomRule, Self { rules: HashMap::new() }))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        if settings.rules.is_empty() {
+            return Ok(None);
+        }
+
+        let msg = &event.msg;
+        let key = (msg.guild_id.unwrap(), event.channel());
+        let source = settings.rules.to_string();
+
+        let up_to_date = self.rules.get(&key).map_or(false, |cached| cached.source == source);
+
+        if !up_to_date {
+            debug!("Compiling custom rules for {} in {}", key.0, key.1);
+            let rules = settings.rules.compile()?;
+            self.rules.insert(key, CachedRules { source, rules });
+        }
+
+        match self.rules[&key].rules.find_match(msg) {
+            Some(idx) => {
+                debug!("Message {} matched custom rule {}", msg.id, idx);
+                Ok(Some(Match::certain(format!("matched custom rule {}", idx))))
+            }
+            None => Ok(None),
+        }
+    }
+}
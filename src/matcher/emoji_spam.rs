@@ -0,0 +1,187 @@
+use super::{
+    state::{self, StateSnapshot, UserState},
+    Match, Matcher, MessageEvent,
+};
+use crate::{
+    module::{settings::EmojiSpamSettings, ModuleKind},
+    text,
+};
+use chrono::{DateTime, Duration, Utc};
+use log::*;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    async_trait,
+    model::id::{GuildId, UserId},
+    prelude::TypeMap,
+    CacheAndHttp,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
+use tokio::sync::RwLock;
+
+static CUSTOM_EMOJI_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<a?:\w+:\d+>").expect("failed to compile custom emoji regex"));
+// how often the users who stopped sending stickers are forgotten
+const PRUNE_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+pub struct EmojiSpam {
+    stickers: HashMap<(GuildId, UserId), SentStickers>,
+    last_pruned: Instant,
+}
+
+// when the user last sent stickers, one entry per sticker
+struct SentStickers {
+    window: Duration,
+    sent: VecDeque<DateTime<Utc>>,
+}
+
+// the window is in seconds, since chrono's durations can't be serialized
+#[derive(Serialize, Deserialize)]
+struct StickersSnapshot {
+    window: i64,
+    sent: Vec<DateTime<Utc>>,
+}
+
+#[async_trait]
+impl Matcher for EmojiSpam {
+    type SettingsType = EmojiSpamSettings;
+
+    async fn build(userdata: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        let stickers = userdata
+            .read()
+            .await
+            .get::<StateSnapshot>()
+            .and_then(|snapshot| snapshot.load::<Vec<UserState<StickersSnapshot>>>(ModuleKind::EmojiSpam))
+            .map(restore_stickers)
+            .unwrap_or_default();
+
+        Ok((
+            ModuleKind::EmojiSpam,
+            Self {
+                stickers,
+                last_pruned: Instant::now(),
+            },
+        ))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+
+        // stickers can't be added in an edit, so an edited message's stickers have already been counted. the stickers
+        // are read from the message's sticker items by the message handler
+        if settings.sticker_count > 0 && !event.edited && !msg.stickers.is_empty() {
+            self.prune();
+
+            let window = Duration::seconds(settings.sticker_window as i64);
+            let stickers = self
+                .stickers
+                .entry((msg.guild_id.unwrap(), msg.author.id))
+                .or_insert_with(|| SentStickers {
+                    window,
+                    sent: VecDeque::new(),
+                });
+            stickers.window = window;
+
+            let sent = &mut stickers.sent;
+            while sent
+                .front()
+                .is_some_and(|timestamp| msg.timestamp - *timestamp > window)
+            {
+                sent.pop_front();
+            }
+            sent.resize(sent.len() + msg.stickers.len(), msg.timestamp);
+
+            debug!("User {} sent {} stickers within the window", msg.author.id, sent.len());
+            if sent.len() > settings.sticker_count {
+                let reason = format!(
+                    "sent {} stickers within {} seconds",
+                    sent.len(),
+                    settings.sticker_window
+                );
+                let matched = Match::over_limit(sent.len(), settings.sticker_count, reason);
+                sent.clear();
+                return Ok(Some(matched));
+            }
+        }
+
+        let (emoji, only_emoji) = count_emoji(&msg.content);
+        debug!("Message {} has {} emoji (only emoji: {})", msg.id, emoji, only_emoji);
+
+        if settings.max_emoji > 0 && emoji > settings.max_emoji {
+            let reason = format!("has {} emoji", emoji);
+            return Ok(Some(Match::over_limit(emoji, settings.max_emoji, reason)));
+        }
+
+        if settings.emoji_only > 0 && only_emoji && emoji >= settings.emoji_only {
+            let reason = format!("has only {} emoji", emoji);
+            return Ok(Some(Match::over_limit(emoji, settings.emoji_only, reason)));
+        }
+
+        Ok(None)
+    }
+
+    fn save_state(&self, snapshot: &StateSnapshot) -> anyhow::Result<()> {
+        let stickers = state::flatten_users(&self.stickers, |stickers| StickersSnapshot {
+            window: stickers.window.num_seconds(),
+            sent: stickers.sent.iter().copied().collect(),
+        });
+
+        snapshot.save(ModuleKind::EmojiSpam, &stickers)
+    }
+}
+
+impl EmojiSpam {
+    fn prune(&mut self) {
+        if self.last_pruned.elapsed() < PRUNE_INTERVAL {
+            return;
+        }
+        self.last_pruned = Instant::now();
+
+        let now = Utc::now();
+        self.stickers.retain(|_, stickers| !stickers.is_stale(now));
+    }
+}
+
+impl SentStickers {
+    fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.sent.back().is_none_or(|latest| now - *latest > self.window)
+    }
+}
+
+fn restore_stickers(snapshots: Vec<UserState<StickersSnapshot>>) -> HashMap<(GuildId, UserId), SentStickers> {
+    let now = Utc::now();
+    let restored = state::restore_users(snapshots, |snapshot| {
+        let stickers = SentStickers {
+            window: Duration::seconds(snapshot.window),
+            sent: snapshot.sent.into(),
+        };
+        Some(stickers).filter(|stickers| !stickers.is_stale(now))
+    });
+
+    debug!("Restored {} users' sent stickers from snapshot", restored.len());
+    restored
+}
+
+// the amount of custom and Unicode emoji in the content, and whether there's nothing else in it. emoji sequences, e.g.
+// family emoji made of several people joined together, count as each emoji in them
+fn count_emoji(content: &str) -> (usize, bool) {
+    let custom = CUSTOM_EMOJI_REGEX.find_iter(content).count();
+    let rest = CUSTOM_EMOJI_REGEX.replace_all(content, "");
+
+    let mut unicode = 0;
+    let mut only_emoji = true;
+    for c in rest.chars() {
+        if text::is_emoji(c) {
+            unicode += 1;
+        } else if !c.is_whitespace() && !text::is_emoji_modifier(c) {
+            only_emoji = false;
+        }
+    }
+
+    (custom + unicode, only_emoji)
+}
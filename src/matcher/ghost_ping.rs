@@ -0,0 +1,220 @@
+use super::{Match, Matcher, MessageEvent};
+use crate::{
+    ext::UserdataExt,
+    module::{settings::GhostPingSettings, ModuleKind},
+};
+use chrono::{DateTime, Duration, Utc};
+use log::*;
+use serenity::{
+    async_trait,
+    http::Http,
+    model::{
+        guild::ActionMessage,
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        misc::Mentionable,
+    },
+    prelude::{TypeMap, TypeMapKey},
+    CacheAndHttp,
+};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::RwLock;
+
+// Discord's message length limit, leaving some room for the ellipsis
+const MAX_NOTIFICATION_LENGTH: usize = 1990;
+// the bot removes a message shortly after matching it, so by then its removal has long since gone through
+const REMOVED_TTL_SECONDS: i64 = 600;
+const AUDIT_LOG_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+const AUDIT_LOG_ENTRIES: u8 = 25;
+const AUDIT_LOG_WINDOW_SECONDS: i64 = 300;
+
+pub struct GhostPing {
+    store: GhostPingStore,
+}
+
+// recent messages with mentions in them, shared between the matcher that stores them and the message deletion handler
+// that looks them up
+#[derive(Debug, Clone, Default)]
+pub struct GhostPingStore {
+    messages: Arc<RwLock<HashMap<MessageId, PingedMessage>>>,
+    // the messages the bot is about to remove itself and when it decided to. the matcher may store a message only
+    // after the bot has already decided to remove it, so these are checked when the message is deleted instead of
+    // being removed from the stored messages
+    removed: Arc<RwLock<HashMap<MessageId, DateTime<Utc>>>>,
+}
+
+impl TypeMapKey for GhostPingStore {
+    type Value = GhostPingStore;
+}
+
+#[derive(Debug, Clone)]
+struct PingedMessage {
+    author: UserId,
+    users: Vec<UserId>,
+    roles: Vec<RoleId>,
+    expires_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl Matcher for GhostPing {
+    type SettingsType = GhostPingSettings;
+    async fn build(userdata: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        let store = userdata.read().await.get_userdata::<GhostPingStore>()?.clone();
+
+        Ok((ModuleKind::GhostPing, Self { store }))
+    }
+
+    // this never matches anything by itself, it only remembers the messages that ping someone so they can be looked up
+    // once they're deleted
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        // Discord doesn't notify anyone about mentions added in an edit, so only the original message's mentions were
+        // pinged. the ones an edit removes were still pinged, so the stored message is left as it is
+        if event.edited {
+            return Ok(None);
+        }
+
+        let msg = &event.msg;
+        let users = msg
+            .mentions
+            .iter()
+            .filter(|user| user.id != msg.author.id && !user.bot)
+            .map(|user| user.id)
+            .collect::<Vec<_>>();
+        let roles = if settings.role_mentions {
+            msg.mention_roles.clone()
+        } else {
+            Vec::new()
+        };
+
+        if users.is_empty() && roles.is_empty() {
+            return Ok(None);
+        }
+
+        let mut messages = self.store.messages.write().await;
+        let now = Utc::now();
+        messages.retain(|_, pinged| pinged.expires_at > now);
+
+        debug!(
+            "Storing message {} with {} user and {} role mentions",
+            msg.id,
+            users.len(),
+            roles.len()
+        );
+        messages.insert(
+            msg.id,
+            PingedMessage {
+                author: msg.author.id,
+                users,
+                roles,
+                expires_at: msg.timestamp + Duration::seconds(settings.max_age as i64),
+            },
+        );
+
+        Ok(None)
+    }
+}
+
+impl GhostPingStore {
+    // messages the bot removes itself aren't ghost pings
+    pub async fn mark_removed(&self, id: MessageId) {
+        let mut removed = self.removed.write().await;
+        let now = Utc::now();
+        removed.retain(|_, marked_at| now - *marked_at < Duration::seconds(REMOVED_TTL_SECONDS));
+        removed.insert(id, now);
+    }
+
+    // notifies the channel if the deleted message pinged someone and was deleted by its author. a message removed by a
+    // moderator isn't a ghost ping, even though whoever it pinged won't find it either
+    pub async fn notify_deleted(
+        &self,
+        http: &Http,
+        guild: GuildId,
+        channel: ChannelId,
+        id: MessageId,
+    ) -> anyhow::Result<()> {
+        let pinged = {
+            let mut removed = self.removed.write().await;
+            let mut messages = self.messages.write().await;
+            match messages.remove(&id).filter(|_| removed.remove(&id).is_none()) {
+                Some(pinged) if pinged.expires_at > Utc::now() => pinged,
+                _ => return Ok(()),
+            }
+        };
+
+        match deleted_by_someone_else(http, guild, channel, pinged.author).await {
+            Ok(true) => {
+                debug!("Message {} by {} was deleted by someone else", id, pinged.author);
+                return Ok(());
+            }
+            Ok(false) => (),
+            // without the audit log there's no telling who deleted the message, so it's reported just in case
+            Err(e) => warn!("Failed to read the audit log in {} for a deleted message: {}", guild, e),
+        }
+
+        info!(
+            "Ghost ping in {} by {}: {:?} {:?}",
+            channel, pinged.author, pinged.users, pinged.roles
+        );
+        let mut notification = pinged.describe();
+        if notification.len() > MAX_NOTIFICATION_LENGTH {
+            let mut end = MAX_NOTIFICATION_LENGTH;
+            while !notification.is_char_boundary(end) {
+                end -= 1;
+            }
+            notification.truncate(end);
+            notification.push_str("...");
+        }
+
+        // the mentions are there to show who was pinged, not to ping them all over again
+        channel
+            .send_message(http, |m| {
+                m.content(notification)
+                    .allowed_mentions(|mentions| mentions.empty_parse())
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+// deleting someone else's message leaves an entry in the audit log, while deleting your own doesn't. Discord merges
+// the entries for the same moderator deleting several messages of the same author in the same channel, so any recent
+// enough entry for the author in the channel is taken to cover this deletion. the entry may be written a moment after
+// the deletion is sent to the bot, so it's waited for for a bit
+async fn deleted_by_someone_else(
+    http: &Http,
+    guild: GuildId,
+    channel: ChannelId,
+    author: UserId,
+) -> anyhow::Result<bool> {
+    tokio::time::sleep(AUDIT_LOG_DELAY).await;
+
+    let logs = guild
+        .audit_logs(
+            http,
+            Some(ActionMessage::Delete.num()),
+            None,
+            None,
+            Some(AUDIT_LOG_ENTRIES),
+        )
+        .await?;
+    let now = Utc::now();
+
+    Ok(logs.entries.values().any(|entry| {
+        entry.target_id == Some(author.0)
+            && entry.options.as_ref().and_then(|options| options.channel_id) == Some(channel)
+            && now - entry.id.created_at() < Duration::seconds(AUDIT_LOG_WINDOW_SECONDS)
+    }))
+}
+
+impl PingedMessage {
+    fn describe(&self) -> String {
+        let targets = self
+            .users
+            .iter()
+            .map(|user| user.mention().to_string())
+            .chain(self.roles.iter().map(|role| role.mention().to_string()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("{} pinged {} in a message they deleted", self.author.mention(), targets)
+    }
+}
@@ -0,0 +1,298 @@
+use super::{Match, Matcher, MessageEvent};
+use crate::{
+    ext::UserdataExt,
+    guild_settings::GuildSettings,
+    module::{settings::ImpersonationSettings, ModuleKind},
+    text, DbConnPool, DbPool,
+};
+use futures::StreamExt;
+use log::*;
+use serenity::{
+    async_trait,
+    model::{
+        guild::Member,
+        id::{GuildId, RoleId, UserId},
+        user::User,
+    },
+    prelude::TypeMap,
+    CacheAndHttp,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+// the staff members are looked up from the cache and the admin role from the database, so the result is kept around
+// for a while instead of doing that for every single message
+const STAFF_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+// the flagged users are forgotten all at once when there's this many of them, so they don't pile up forever
+const MAX_FLAGGED: usize = 10_000;
+
+pub struct Impersonation {
+    staff: StaffCache,
+    // the users already matched with the name they were matched with, so they aren't matched again for every message
+    // they send. changing their name lets them be matched again
+    flagged: HashSet<(GuildId, UserId, String)>,
+}
+
+pub struct StaffCache {
+    // the pool is kept instead of the userdata since the matcher runner holds the userdata's read lock while matching,
+    // and locking it again would deadlock as soon as a writer is queued
+    db_pool: DbConnPool,
+    cache_http: Arc<CacheAndHttp>,
+    guilds: HashMap<GuildId, (Instant, Arc<Vec<Staff>>)>,
+}
+
+#[derive(Debug)]
+pub struct Staff {
+    id: UserId,
+    // the staff member's username as-is, for the match reason
+    name: String,
+    names: Vec<String>,
+    avatar: Option<String>,
+}
+
+#[async_trait]
+impl Matcher for Impersonation {
+    type SettingsType = ImpersonationSettings;
+
+    async fn build(
+        userdata: Arc<RwLock<TypeMap>>,
+        cache_http: Arc<CacheAndHttp>,
+    ) -> anyhow::Result<(ModuleKind, Self)> {
+        let db_pool = userdata.read().await.get_userdata::<DbPool>()?.clone();
+
+        Ok((
+            ModuleKind::Impersonation,
+            Self {
+                staff: StaffCache::new(db_pool, cache_http),
+                flagged: HashSet::new(),
+            },
+        ))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        // an edit or a new thread doesn't change who the author is, so they've already been matched by their messages
+        if event.edited || event.thread_name {
+            return Ok(None);
+        }
+
+        let msg = &event.msg;
+        let guild_id = msg.guild_id.unwrap();
+        let nick = msg.member.as_ref().and_then(|member| member.nick.as_deref());
+        let flagged = (guild_id, msg.author.id, String::from(nick.unwrap_or(&msg.author.name)));
+        if self.flagged.contains(&flagged) {
+            return Ok(None);
+        }
+
+        let staff = self.staff.get(guild_id).await?;
+        let matched = find_impersonated(&staff, &msg.author, nick, &settings);
+        if matched.is_some() {
+            if self.flagged.len() >= MAX_FLAGGED {
+                self.flagged.clear();
+            }
+            self.flagged.insert(flagged);
+        }
+        Ok(matched)
+    }
+}
+
+impl StaffCache {
+    pub fn new(db_pool: DbConnPool, cache_http: Arc<CacheAndHttp>) -> Self {
+        Self {
+            db_pool,
+            cache_http,
+            guilds: HashMap::new(),
+        }
+    }
+
+    // the members holding the guild's admin role. they're looked up from the cache first, but if none of them are
+    // cached the guild's members are fetched instead
+    pub async fn get(&mut self, guild: GuildId) -> anyhow::Result<Arc<Vec<Staff>>> {
+        if let Some((fetched, staff)) = self.guilds.get(&guild) {
+            if fetched.elapsed() < STAFF_CACHE_TTL {
+                return Ok(Arc::clone(staff));
+            }
+        }
+
+        let admin_role = {
+            let db = self.db_pool.get()?;
+            GuildSettings::get_for_guild(guild, &db)?.get_admin_role()
+        };
+
+        let staff = match admin_role {
+            Some(role) => {
+                let cached = self
+                    .cache_http
+                    .cache
+                    .guild_field(guild, |g| {
+                        g.members
+                            .values()
+                            .filter(|member| member.roles.contains(&role))
+                            .map(Staff::from_member)
+                            .collect::<Vec<_>>()
+                    })
+                    .await
+                    .unwrap_or_default();
+
+                if cached.is_empty() {
+                    self.fetch_staff(guild, role).await?
+                } else {
+                    cached
+                }
+            }
+            None => Vec::new(),
+        };
+
+        debug!("Found {} staff members in {}", staff.len(), guild);
+        let staff = Arc::new(staff);
+        self.guilds.insert(guild, (Instant::now(), Arc::clone(&staff)));
+        Ok(staff)
+    }
+
+    // there's no way to ask for only the members with a given role, so the whole member list is paged through
+    async fn fetch_staff(&self, guild: GuildId, role: RoleId) -> anyhow::Result<Vec<Staff>> {
+        debug!("No staff members cached in {}, fetching them", guild);
+        let mut members = guild.members_iter(&self.cache_http.http).boxed();
+        let mut staff = Vec::new();
+
+        while let Some(member) = members.next().await {
+            let member = member?;
+            if member.roles.contains(&role) {
+                staff.push(Staff::from_member(&member));
+            }
+        }
+
+        Ok(staff)
+    }
+}
+
+impl Staff {
+    fn from_member(member: &Member) -> Self {
+        Self {
+            id: member.user.id,
+            name: member.user.name.clone(),
+            names: std::iter::once(&member.user.name)
+                .chain(member.nick.as_ref())
+                .map(|name| text::fold_name(name))
+                .collect(),
+            avatar: member.user.avatar.clone(),
+        }
+    }
+}
+
+// how much the user looks like the staff member they look the most like, if any. the staff members themselves
+// obviously look like themselves and are skipped. an identical avatar is certain, while a name scores lower the more
+// it differs from the staff member's names
+pub fn find_impersonated(
+    staff: &[Staff],
+    user: &User,
+    nick: Option<&str>,
+    settings: &ImpersonationSettings,
+) -> Option<Match> {
+    let names = std::iter::once(user.name.as_str())
+        .chain(nick)
+        .map(text::fold_name)
+        .filter(|name| name.chars().count() >= settings.minimum_length)
+        .collect::<Vec<_>>();
+
+    let (impersonated, matched) = staff
+        .iter()
+        .filter(|staff| staff.id != user.id)
+        .filter_map(|staff| {
+            if settings.avatars && user.avatar.is_some() && user.avatar == staff.avatar {
+                let reason = format!("has the same avatar as staff member {}", staff.name);
+                return Some((staff, Match::certain(reason)));
+            }
+
+            let distance = names
+                .iter()
+                .flat_map(|name| {
+                    staff
+                        .names
+                        .iter()
+                        .map(move |staff_name| text::edit_distance(name, staff_name))
+                })
+                .min()
+                .filter(|distance| *distance <= settings.name_distance)?;
+
+            let reason = format!("has a name similar to staff member {}", staff.name);
+            let closeness = settings.name_distance - distance;
+            Some((staff, Match::scaled(closeness, 0, settings.name_distance, reason)))
+        })
+        .max_by_key(|(_, matched)| matched.score)?;
+
+    debug!(
+        "User {} looks like staff member {} with score {}",
+        user.id, impersonated.id, matched.score
+    );
+    Some(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::MAX_SCORE;
+    use serde_json::json;
+
+    fn staff(id: u64, name: &str, avatar: Option<&str>) -> Staff {
+        Staff {
+            id: UserId(id),
+            name: String::from(name),
+            names: vec![text::fold_name(name)],
+            avatar: avatar.map(String::from),
+        }
+    }
+
+    fn user(id: u64, name: &str, avatar: Option<&str>) -> User {
+        serde_json::from_value(json!({
+            "id": id.to_string(),
+            "username": name,
+            "discriminator": "0001",
+            "avatar": avatar,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn close_names_are_matched() {
+        let staff = [staff(1, "moderator", None)];
+        let settings = ImpersonationSettings::default();
+
+        assert!(find_impersonated(&staff, &user(2, "moderat0r", None), None, &settings).is_some());
+        assert!(find_impersonated(&staff, &user(2, "moderators", None), None, &settings).is_some());
+        assert!(find_impersonated(&staff, &user(2, "someone", None), None, &settings).is_none());
+        assert!(find_impersonated(&staff, &user(2, "someone", None), Some("moderator"), &settings).is_some());
+    }
+
+    #[test]
+    fn distant_and_short_names_are_not_matched() {
+        let staff = [staff(1, "moderator", None), staff(3, "bob", None)];
+        let settings = ImpersonationSettings::default();
+
+        assert!(find_impersonated(&staff, &user(2, "modern art", None), None, &settings).is_none());
+        assert!(find_impersonated(&staff, &user(2, "bob", None), None, &settings).is_none());
+    }
+
+    #[test]
+    fn same_avatar_is_certain() {
+        let staff = [staff(1, "moderator", Some("abc"))];
+        let mut settings = ImpersonationSettings::default();
+
+        let matched = find_impersonated(&staff, &user(2, "someone", Some("abc")), None, &settings).unwrap();
+        assert_eq!(matched.score, MAX_SCORE);
+
+        settings.avatars = false;
+        assert!(find_impersonated(&staff, &user(2, "someone", Some("abc")), None, &settings).is_none());
+    }
+
+    #[test]
+    fn staff_do_not_impersonate_themselves() {
+        let staff = [staff(1, "moderator", Some("abc"))];
+        let settings = ImpersonationSettings::default();
+
+        assert!(find_impersonated(&staff, &user(1, "moderator", Some("abc")), None, &settings).is_none());
+    }
+}
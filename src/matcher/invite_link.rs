@@ -1,42 +1,289 @@
-use super::Matcher;
-use crate::module::{settings::InviteLinkSettings, ModuleKind};
+use super::{links, Match, Matcher, MessageEvent};
+use crate::module::{
+    settings::{InviteLinkSettings, SettingList},
+    ModuleKind,
+};
 use log::*;
-use serenity::{async_trait, model::channel::Message, prelude::TypeMap};
-use std::sync::Arc;
+use serenity::{
+    async_trait,
+    http::Http,
+    model::{channel::Message, id::GuildId},
+    prelude::TypeMap,
+    CacheAndHttp,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::sync::RwLock;
 use url::Url;
 
-const DISCORD_URLS: &[&str] = &["discord.com", "discord.gg"];
+// domains where the invite code is the first path segment, e.g. discord.gg/<code>
+const INVITE_DOMAINS: &[&str] = &[
+    "discord.gg",
+    "discord.me",
+    "discord.io",
+    "dsc.gg",
+    "invite.gg",
+    "discord.link",
+];
+// domains where the invite code follows the invite path, e.g. discord.com/invite/<code>. these match their
+// subdomains as well (ptb.discord.com, canary.discord.com)
+const INVITE_PATH_DOMAINS: &[&str] = &["discord.com", "discordapp.com"];
 const INVITE_PATH: &str = "invite";
+// the only invite domains whose codes Discord itself knows about and can resolve
+const DISCORD_INVITE_DOMAINS: &[&str] = &["discord.gg", "discord.com", "discordapp.com"];
+// invites can be deleted or expire, so don't trust a resolved guild forever
+const RESOLVE_CACHE_TTL: Duration = Duration::from_secs(3600);
 
-pub struct InviteLink {}
+pub struct InviteLink {
+    resolver: Box<dyn InviteResolver>,
+    resolved: HashMap<String, (Option<GuildId>, Instant)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FoundInvite {
+    pub host: String,
+    pub code: String,
+}
+
+// resolves an invite code to the guild it points to. Ok(None) means the invite exists but isn't for a guild, e.g. a
+// group DM invite
+#[async_trait]
+pub trait InviteResolver: Send + Sync {
+    async fn resolve(&self, code: &str) -> anyhow::Result<Option<GuildId>>;
+}
+
+pub struct HttpInviteResolver {
+    http: Arc<Http>,
+}
+
+#[async_trait]
+impl InviteResolver for HttpInviteResolver {
+    async fn resolve(&self, code: &str) -> anyhow::Result<Option<GuildId>> {
+        let invite = self.http.get_invite(code, false).await?;
+        Ok(invite.guild.map(|guild| guild.id))
+    }
+}
 
 #[async_trait]
 impl Matcher for InviteLink {
     type SettingsType = InviteLinkSettings;
-    async fn build(_: Arc<RwLock<TypeMap>>) -> (ModuleKind, Self) {
-        (ModuleKind::InviteLink, Self {})
-    }
-
-    async fn is_match(&mut self, _: Self::SettingsType, msg: &Message) -> anyhow::Result<bool> {
-        for url in msg.content.split_whitespace().filter_map(|word| Url::parse(word).ok()) {
-            match url.host_str() {
-                Some(host) if DISCORD_URLS.contains(&host) => {
-                    debug!("{} is a Discord URL", url);
-
-                    if let Some(last_segment) = url.path_segments().and_then(|s| s.last()) {
-                        // if the last segment is something, but not "invite" (as in discord.com/invite), it's
-                        // probably an invite
-                        if !last_segment.is_empty() && last_segment != INVITE_PATH {
-                            info!("{} looks like an invite", url);
-                            return Ok(true);
-                        }
-                    }
+    async fn build(_: Arc<RwLock<TypeMap>>, cache_http: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        let resolver = HttpInviteResolver {
+            http: Arc::clone(&cache_http.http),
+        };
+
+        Ok((ModuleKind::InviteLink, Self::new(Box::new(resolver))))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+        for invite in find_invites(msg, &settings.extra_domains) {
+            if self.is_allowed(&invite, &settings, msg.guild_id).await {
+                debug!("{}/{} is an allowed invite", invite.host, invite.code);
+                continue;
+            }
+
+            info!("{}/{} looks like an invite", invite.host, invite.code);
+            let reason = format!("has the invite {}/{}", invite.host, invite.code);
+            return Ok(Some(Match::certain(reason)));
+        }
+
+        Ok(None)
+    }
+}
+
+impl InviteLink {
+    pub fn new(resolver: Box<dyn InviteResolver>) -> Self {
+        Self {
+            resolver,
+            resolved: HashMap::new(),
+        }
+    }
+
+    async fn is_allowed(&mut self, invite: &FoundInvite, settings: &InviteLinkSettings, own: Option<GuildId>) -> bool {
+        if settings.allowed_codes.contains(&invite.code) {
+            return true;
+        }
+
+        // resolving is a request to Discord per invite, so skip it altogether if there aren't any guilds it could be
+        // allowed by
+        if settings.allowed_guilds.is_empty() && !settings.allow_own_guild {
+            return false;
+        }
+
+        if !invite.is_resolvable() {
+            return false;
+        }
+
+        match self.resolve(&invite.code).await {
+            Some(guild) => {
+                (settings.allow_own_guild && Some(guild) == own) || settings.allowed_guilds.contains(&guild.0)
+            }
+            None => false,
+        }
+    }
+
+    async fn resolve(&mut self, code: &str) -> Option<GuildId> {
+        if let Some((guild, resolved_at)) = self.resolved.get(code) {
+            if resolved_at.elapsed() < RESOLVE_CACHE_TTL {
+                return *guild;
+            }
+        }
+
+        // invalid and expired invites are cached as well so the same bogus invite posted over and over again doesn't
+        // cause a request every time
+        let guild = match self.resolver.resolve(code).await {
+            Ok(guild) => guild,
+            Err(e) => {
+                warn!("Failed to resolve invite {}: {}", code, e);
+                None
+            }
+        };
+        debug!("Resolved invite {} to guild {:?}", code, guild);
+
+        self.resolved
+            .retain(|_, (_, resolved_at)| resolved_at.elapsed() < RESOLVE_CACHE_TTL);
+        self.resolved.insert(String::from(code), (guild, Instant::now()));
+        guild
+    }
+}
+
+impl FoundInvite {
+    fn is_resolvable(&self) -> bool {
+        DISCORD_INVITE_DOMAINS
+            .iter()
+            .any(|domain| links::host_matches(&self.host, domain))
+    }
+}
+
+pub fn find_invites(msg: &Message, extra_domains: &SettingList<String>) -> Vec<FoundInvite> {
+    let mut invites = Vec::new();
+    for text in links::message_texts(msg) {
+        // links in their usual form are caught as-is, but the deobfuscated text catches the ones broken up with spaces
+        // and such. schemeless links are considered since people don't need a scheme to know where to go
+        let deobfuscated = links::deobfuscate(text);
+        for url in links::extract_urls(&deobfuscated, true) {
+            if let Some(invite) = parse_invite(&url, extra_domains) {
+                debug!("{} is an invite: {:?}", url, invite);
+
+                if !invites.contains(&invite) {
+                    invites.push(invite);
                 }
-                _ => continue,
             }
         }
+    }
+    invites
+}
+
+fn parse_invite(url: &Url, extra_domains: &SettingList<String>) -> Option<FoundInvite> {
+    let host = links::normalized_host(url)?;
+    let mut segments = url.path_segments()?.filter(|s| !s.is_empty());
+
+    let code = if INVITE_DOMAINS.contains(&host.as_str())
+        || extra_domains.iter().any(|domain| domain.eq_ignore_ascii_case(&host))
+    {
+        segments.next()?
+    } else if INVITE_PATH_DOMAINS
+        .iter()
+        .any(|domain| links::host_matches(&host, domain))
+    {
+        // discord.com/channels/... and the like are regular links, only discord.com/invite/<code> is an invite
+        if segments.next()? != INVITE_PATH {
+            return None;
+        }
+        segments.next()?
+    } else {
+        return None;
+    };
+
+    Some(FoundInvite {
+        host,
+        code: String::from(code),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const OWN_GUILD: GuildId = GuildId(1);
+    const PARTNER_GUILD: GuildId = GuildId(2);
+    const OTHER_GUILD: GuildId = GuildId(3);
+
+    struct StubResolver {
+        invites: HashMap<&'static str, GuildId>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl InviteResolver for StubResolver {
+        async fn resolve(&self, code: &str) -> anyhow::Result<Option<GuildId>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(self.invites.get(code).copied())
+        }
+    }
+
+    fn invite_link() -> (InviteLink, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let resolver = StubResolver {
+            invites: vec![("own", OWN_GUILD), ("partner", PARTNER_GUILD), ("other", OTHER_GUILD)]
+                .into_iter()
+                .collect(),
+            calls: Arc::clone(&calls),
+        };
+        (InviteLink::new(Box::new(resolver)), calls)
+    }
+
+    async fn is_allowed(invite_link: &mut InviteLink, code: &str, settings: &InviteLinkSettings) -> bool {
+        let invite = FoundInvite {
+            host: String::from("discord.gg"),
+            code: String::from(code),
+        };
+        invite_link.is_allowed(&invite, settings, Some(OWN_GUILD)).await
+    }
+
+    #[tokio::test]
+    async fn allowed_guild() {
+        let (mut invite_link, calls) = invite_link();
+        let settings = InviteLinkSettings {
+            allowed_guilds: "2".parse().unwrap(),
+            allow_own_guild: true,
+            ..InviteLinkSettings::default()
+        };
+
+        assert!(is_allowed(&mut invite_link, "partner", &settings).await);
+        assert!(is_allowed(&mut invite_link, "own", &settings).await);
+        assert!(!is_allowed(&mut invite_link, "other", &settings).await);
+        assert!(!is_allowed(&mut invite_link, "unknown", &settings).await);
+
+        // resolved invites are cached
+        assert!(is_allowed(&mut invite_link, "partner", &settings).await);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn allowed_code() {
+        let (mut invite_link, calls) = invite_link();
+        let settings = InviteLinkSettings {
+            allowed_codes: "partner".parse().unwrap(),
+            allowed_guilds: "2".parse().unwrap(),
+            ..InviteLinkSettings::default()
+        };
+
+        assert!(is_allowed(&mut invite_link, "partner", &settings).await);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn nothing_to_allow_by_skips_resolving() {
+        let (mut invite_link, calls) = invite_link();
+        let settings = InviteLinkSettings::default();
 
-        Ok(false)
+        assert!(!is_allowed(&mut invite_link, "own", &settings).await);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
     }
 }
@@ -0,0 +1,166 @@
+use super::{links, Match, Matcher, MessageEvent};
+use crate::{
+    ext::UserdataExt,
+    module::{settings::LinkPolicySettings, ModuleKind},
+    HttpClient,
+};
+use log::*;
+use reqwest::{header::LOCATION, Client};
+use serenity::{async_trait, prelude::TypeMap, CacheAndHttp};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+use url::Url;
+
+const SHORTENER_DOMAINS: &[&str] = &[
+    "bit.ly",
+    "buff.ly",
+    "cutt.ly",
+    "goo.gl",
+    "is.gd",
+    "ow.ly",
+    "rb.gy",
+    "rebrand.ly",
+    "shorturl.at",
+    "t.co",
+    "t.ly",
+    "tiny.cc",
+    "tinyurl.com",
+];
+// shortened links may point to other shortened links, but not forever
+const MAX_REDIRECTS: usize = 5;
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+// shortened links can usually be edited afterwards, so don't trust an expanded link forever
+const RESOLVE_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+pub struct LinkPolicy {
+    resolver: Box<dyn ShortenerResolver>,
+    resolved: HashMap<Url, (Option<Url>, Instant)>,
+}
+
+// expands a shortened link to the link it redirects to. Ok(None) means the link doesn't redirect anywhere
+#[async_trait]
+pub trait ShortenerResolver: Send + Sync {
+    async fn resolve(&self, url: &Url) -> anyhow::Result<Option<Url>>;
+}
+
+pub struct HttpShortenerResolver {
+    client: Client,
+}
+
+#[async_trait]
+impl ShortenerResolver for HttpShortenerResolver {
+    async fn resolve(&self, url: &Url) -> anyhow::Result<Option<Url>> {
+        // the redirect is read from the response instead of followed, so the bot never actually visits the
+        // destination. the shared client doesn't follow redirects
+        let response = self.client.head(url.clone()).timeout(RESOLVE_TIMEOUT).send().await?;
+        if !response.status().is_redirection() {
+            return Ok(None);
+        }
+
+        match response.headers().get(LOCATION) {
+            Some(location) => Ok(Some(url.join(location.to_str()?)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl Matcher for LinkPolicy {
+    type SettingsType = LinkPolicySettings;
+    async fn build(userdata: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        let client = userdata.read().await.get_userdata::<HttpClient>()?.clone();
+
+        Ok((
+            ModuleKind::LinkPolicy,
+            Self {
+                resolver: Box::new(HttpShortenerResolver { client }),
+                resolved: HashMap::new(),
+            },
+        ))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+        if settings.allowed_domains.is_empty() && settings.denied_domains.is_empty() {
+            return Ok(None);
+        }
+
+        for text in links::message_texts(msg) {
+            for url in links::extract_urls(text, settings.bare_domains) {
+                let url = if settings.expand_shorteners {
+                    self.expand(url).await
+                } else {
+                    url
+                };
+
+                if let Some(host) = links::normalized_host(&url) {
+                    if !is_allowed(&host, &settings) {
+                        info!("{} isn't allowed by the link policy", url);
+                        let reason = format!("links to {}, which isn't allowed", host);
+                        return Ok(Some(Match::certain(reason)));
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl LinkPolicy {
+    // follows the shortener redirects as far as it can. a link that can't be expanded any further is returned as it is
+    async fn expand(&mut self, mut url: Url) -> Url {
+        for _ in 0..MAX_REDIRECTS {
+            if !is_shortened(&url) {
+                break;
+            }
+
+            match self.resolve(&url).await {
+                Some(target) => {
+                    debug!("Expanded {} to {}", url, target);
+                    url = target;
+                }
+                None => break,
+            }
+        }
+        url
+    }
+
+    async fn resolve(&mut self, url: &Url) -> Option<Url> {
+        if let Some((target, resolved_at)) = self.resolved.get(url) {
+            if resolved_at.elapsed() < RESOLVE_CACHE_TTL {
+                return target.clone();
+            }
+        }
+
+        let target = match self.resolver.resolve(url).await {
+            Ok(target) => target,
+            Err(e) => {
+                warn!("Failed to expand shortened link {}: {}", url, e);
+                None
+            }
+        };
+
+        self.resolved
+            .retain(|_, (_, resolved_at)| resolved_at.elapsed() < RESOLVE_CACHE_TTL);
+        self.resolved.insert(url.clone(), (target.clone(), Instant::now()));
+        target
+    }
+}
+
+fn is_shortened(url: &Url) -> bool {
+    links::normalized_host(url).is_some_and(|host| SHORTENER_DOMAINS.contains(&host.as_str()))
+}
+
+// denied domains always win. if there are allowed domains, everything else is denied
+fn is_allowed(host: &str, settings: &LinkPolicySettings) -> bool {
+    if settings.denied_domains.iter().any(|pattern| pattern.matches(host)) {
+        return false;
+    }
+
+    settings.allowed_domains.is_empty() || settings.allowed_domains.iter().any(|pattern| pattern.matches(host))
+}
@@ -0,0 +1,174 @@
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serenity::model::channel::Message;
+use std::borrow::Cow;
+use url::Url;
+
+// matches both full URLs and bare domains with an optional path. whether the scheme is required is decided after
+// matching by checking the first capture group
+static URL_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\b(https?://)?((?:[a-z0-9](?:[a-z0-9-]*[a-z0-9])?\.)+[a-z]{2,63})(:\d{1,5})?(/[^\s<>"'`]*)?"#)
+        .expect("failed to compile URL regex")
+});
+// [text](url) and [text](<url> "title")
+static MARKDOWN_LINK_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\[([^\]]*)\]\(\s*<?([^)\s>]+)>?[^)]*\)").expect("failed to compile markdown link regex"));
+// discord(.)gg, discord[dot]gg, discord dot gg
+static OBFUSCATED_DOT_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(\w)\s*(?:\(\.\)|\[\.\]|\(dot\)|\[dot\]|\sdot\s)\s*(\w)")
+        .expect("failed to compile obfuscated dot regex")
+});
+// discord . gg, discord. gg. spaces around a plain dot are also how sentences end, so these are only collapsed when
+// what follows is a domain suffix, see SPACED_DOT_SUFFIXES
+static SPACED_DOT_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)(\w)(?:\s+\.\s*|\.\s+)([a-z0-9]+)\b").expect("failed to compile spaced dot regex"));
+static OBFUSCATED_SLASH_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(\w)\s*/\s*(\w)").expect("failed to compile obfuscated slash regex"));
+
+// zero-width spaces, joiners and the BOM are invisible but break up words
+const INVISIBLE_CHARACTERS: &[char] = &['\u{200b}', '\u{200c}', '\u{200d}', '\u{2060}', '\u{feff}'];
+// the suffixes of the invite domains and the TLDs most commonly used for scam and invite links
+const SPACED_DOT_SUFFIXES: &[&str] = &[
+    "com", "gg", "io", "link", "me", "net", "org", "ru", "xyz", "ly", "co", "app", "site", "shop", "tk",
+];
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '*', '_', '~', '|'];
+
+// all the user-visible text in a message that may contain links: the content and every text field in its embeds
+pub fn message_texts(msg: &Message) -> Vec<&str> {
+    let mut texts = vec![msg.content.as_str()];
+
+    for embed in &msg.embeds {
+        texts.extend(embed.title.as_deref());
+        texts.extend(embed.description.as_deref());
+        texts.extend(embed.url.as_deref());
+
+        if let Some(author) = &embed.author {
+            texts.push(&author.name);
+            texts.extend(author.url.as_deref());
+        }
+
+        if let Some(footer) = &embed.footer {
+            texts.push(&footer.text);
+        }
+
+        for field in &embed.fields {
+            texts.push(&field.name);
+            texts.push(&field.value);
+        }
+    }
+
+    texts
+}
+
+// undo the common ways people break up links to get past filters. the result isn't meant to be shown to anyone, only
+// fed to extract_urls(), which takes care of markdown links
+pub fn deobfuscate(text: &str) -> String {
+    let text = text.replace(INVISIBLE_CHARACTERS, "").replace('\\', "");
+    let text = replace_all_overlapping(&OBFUSCATED_DOT_REGEX, Cow::Owned(text), "$1.$2");
+    let text = collapse_spaced_dots(text);
+    let text = replace_all_overlapping(&OBFUSCATED_SLASH_REGEX, text, "$1/$2");
+    text.into_owned()
+}
+
+// extracts every http(s) URL in the text. if schemeless is true, bare domains such as example.com/path are considered
+// URLs as well
+pub fn extract_urls(text: &str, schemeless: bool) -> Vec<Url> {
+    let text = MARKDOWN_LINK_REGEX.replace_all(text, " $1 $2 ");
+
+    URL_REGEX
+        .captures_iter(&text)
+        .filter(|captures| schemeless || captures.get(1).is_some())
+        .filter_map(|captures| {
+            let matched = captures.get(0)?.as_str().trim_end_matches(TRAILING_PUNCTUATION);
+            if captures.get(1).is_some() {
+                Url::parse(matched).ok()
+            } else {
+                Url::parse(&format!("https://{}", matched)).ok()
+            }
+        })
+        .collect()
+}
+
+// the URL's host, lowercased and without a leading www.
+pub fn normalized_host(url: &Url) -> Option<String> {
+    url.host_str().map(|host| {
+        let host = host.to_lowercase();
+        match host.strip_prefix("www.") {
+            Some(stripped) => String::from(stripped),
+            None => host,
+        }
+    })
+}
+
+// whether the host is the domain itself or any of its subdomains
+pub fn host_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+fn collapse_spaced_dots(text: Cow<str>) -> Cow<str> {
+    let mut text = text;
+    loop {
+        let replaced = SPACED_DOT_REGEX.replace_all(&text, |captures: &Captures| {
+            if SPACED_DOT_SUFFIXES.contains(&captures[2].to_lowercase().as_str()) {
+                format!("{}.{}", &captures[1], &captures[2])
+            } else {
+                String::from(&captures[0])
+            }
+        });
+
+        if replaced == text {
+            return text;
+        }
+        text = Cow::Owned(replaced.into_owned());
+    }
+}
+
+// the obfuscation regexes consume the word characters on both sides of the separator, so chains such as
+// "discord . gg . com" need more than one pass to fully collapse
+fn replace_all_overlapping<'a>(regex: &Regex, text: Cow<'a, str>, replacement: &str) -> Cow<'a, str> {
+    let mut text = text;
+    loop {
+        let replaced = regex.replace_all(&text, replacement).into_owned();
+        if replaced == text {
+            return text;
+        }
+        text = Cow::Owned(replaced);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(text: &str) -> Vec<String> {
+        extract_urls(&deobfuscate(text), true)
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn spaced_dots_before_a_domain_suffix_are_collapsed() {
+        assert_eq!(urls("join discord . gg/abc"), ["https://discord.gg/abc"]);
+        assert_eq!(urls("join discord. gg . com/abc"), ["https://discord.gg.com/abc"]);
+        assert_eq!(urls("join discord(dot)gg/abc"), ["https://discord.gg/abc"]);
+    }
+
+    #[test]
+    fn sentence_breaks_are_not_collapsed() {
+        assert!(urls("that was fine. then it broke").is_empty());
+        assert!(urls("I said no . Why would I").is_empty());
+    }
+
+    #[test]
+    fn markdown_links_are_extracted() {
+        assert_eq!(
+            urls("[free nitro](https://example.com/gift)"),
+            ["https://example.com/gift"]
+        );
+        assert_eq!(
+            urls("[discord . gg/abc](<https://example.com>)"),
+            ["https://discord.gg/abc", "https://example.com/"]
+        );
+    }
+}
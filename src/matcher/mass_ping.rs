@@ -1,20 +1,90 @@
-use super::Matcher;
-use crate::module::{settings::MassPingSettings, ModuleKind};
-use serenity::{async_trait, model::channel::Message, prelude::TypeMap};
+use super::{Match, Matcher, MessageEvent};
+use crate::{
+    error::InternalError,
+    module::{settings::MassPingSettings, ModuleKind},
+};
+use log::*;
+use serenity::{
+    async_trait,
+    model::{
+        channel::Message,
+        id::{GuildId, RoleId},
+        Permissions,
+    },
+    prelude::TypeMap,
+    CacheAndHttp,
+};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-pub struct MassPing {}
+const EVERYONE_TEXT: &str = "@everyone";
+const HERE_TEXT: &str = "@here";
+
+pub struct MassPing {
+    cache_http: Arc<CacheAndHttp>,
+}
 
 #[async_trait]
 impl Matcher for MassPing {
     type SettingsType = MassPingSettings;
-    async fn build(_: Arc<RwLock<TypeMap>>) -> (ModuleKind, Self) {
-        (ModuleKind::MassPing, Self {})
+    async fn build(_: Arc<RwLock<TypeMap>>, cache_http: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        Ok((ModuleKind::MassPing, Self { cache_http }))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+        // this catches both @everyone and @here, but only if the user actually has the permission to use them
+        let attempted = if msg.mention_everyone
+            || (settings.match_text && (msg.content.contains(EVERYONE_TEXT) || msg.content.contains(HERE_TEXT)))
+        {
+            Match::certain(String::from("mentioned everyone"))
+        } else if settings.role_mentions > 0 && msg.mention_roles.len() >= settings.role_mentions {
+            let reason = format!("mentioned {} roles", msg.mention_roles.len());
+            Match::over_limit(msg.mention_roles.len(), settings.role_mentions, reason)
+        } else {
+            return Ok(None);
+        };
+
+        if settings.only_without_permission {
+            let guild_id = msg.guild_id.ok_or(InternalError::MissingGuildID)?;
+            let permitted = self.can_mention_everyone(guild_id, msg).await?;
+            debug!(
+                "User {} has Mention Everyone permission in {}: {}",
+                msg.author.id, guild_id, permitted
+            );
+
+            return Ok(if permitted { None } else { Some(attempted) });
+        }
+
+        Ok(Some(attempted))
     }
+}
+
+impl MassPing {
+    // this only considers the user's roles, not the channel's permission overwrites. the permission is rarely
+    // overwritten per-channel and this way the check doesn't need the entire guild from the cache
+    async fn can_mention_everyone(&self, guild_id: GuildId, msg: &Message) -> anyhow::Result<bool> {
+        let cache = &self.cache_http.cache;
+
+        if cache.guild_field(guild_id, |g| g.owner_id).await == Some(msg.author.id) {
+            return Ok(true);
+        }
+
+        // edited messages don't come with the member, so it's looked up from the cache or over HTTP instead
+        let roles = if let Some(member) = &msg.member {
+            member.roles.clone()
+        } else {
+            guild_id.member(&*self.cache_http, msg.author.id).await?.roles
+        };
+
+        // the @everyone role shares its ID with the guild
+        let mut permissions = Permissions::empty();
+        for role_id in roles.into_iter().chain(Some(RoleId(guild_id.0))) {
+            if let Some(role) = cache.role(guild_id, role_id).await {
+                permissions |= role.permissions;
+            }
+        }
 
-    async fn is_match(&mut self, _: Self::SettingsType, msg: &Message) -> anyhow::Result<bool> {
-        // this catches both @everyone and @here
-        Ok(msg.mention_everyone)
+        Ok(permissions.administrator() || permissions.mention_everyone())
     }
 }
@@ -0,0 +1,86 @@
+mod impersonation;
+mod name_filter;
+
+use super::{run_matcher, MatcherEvent, MatcherResponse};
+use crate::module::{action::ActionTarget, ModuleKind};
+use impersonation::MemberImpersonation;
+use name_filter::NameFilter;
+use serenity::{
+    model::{
+        guild::Member,
+        id::{ChannelId, GuildId, RoleId, UserId},
+    },
+    prelude::TypeMap,
+    CacheAndHttp,
+};
+use std::sync::Arc;
+use tokio::{
+    sync::{broadcast, mpsc, RwLock},
+    task::JoinHandle,
+};
+
+// a member sent to the member matchers, either when they join or when their username or nickname changes
+#[derive(Debug, Clone)]
+pub struct MemberEvent {
+    pub member: Arc<Member>,
+    pub joined: bool,
+}
+
+// matches on members are sent to the same action handler as message matches, only with the member as the action target
+impl MatcherEvent for MemberEvent {
+    fn guild_id(&self) -> anyhow::Result<GuildId> {
+        Ok(self.member.guild_id)
+    }
+
+    fn settings_channel(&self) -> Option<ChannelId> {
+        None
+    }
+
+    fn user_id(&self) -> UserId {
+        self.member.user.id
+    }
+
+    fn roles(&self) -> Option<&[RoleId]> {
+        Some(self.member.roles.as_slice())
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} member {}",
+            if self.joined { "joining" } else { "updated" },
+            self.member.user.id
+        )
+    }
+
+    fn target(&self, _: ModuleKind) -> ActionTarget {
+        ActionTarget::Member(Arc::clone(&self.member))
+    }
+}
+
+// see spawn_message_matchers on why the action_tx is taken by value
+#[allow(clippy::needless_pass_by_value)]
+pub fn spawn_member_matchers(
+    member_tx: broadcast::Sender<MemberEvent>,
+    action_tx: mpsc::Sender<MatcherResponse>,
+    userdata: Arc<RwLock<TypeMap>>,
+    cache_http: Arc<CacheAndHttp>,
+    shutdown_tx: &broadcast::Sender<()>,
+) -> Vec<JoinHandle<()>> {
+    let mut handles = Vec::new();
+
+    macro_rules! matchers {
+        ($($matcher:ty),+) => {
+            $(let rx = member_tx.subscribe();
+            let tx = action_tx.clone();
+            let data = userdata.clone();
+            let cache = Arc::clone(&cache_http);
+            let shutdown = shutdown_tx.subscribe();
+            handles.push(tokio::spawn(async move {
+                run_matcher::<MemberEvent, $matcher>(rx, tx, data, cache, shutdown).await;
+            }));)+
+        };
+    }
+
+    matchers!(NameFilter, MemberImpersonation);
+    handles
+}
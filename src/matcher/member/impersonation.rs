@@ -0,0 +1,50 @@
+use super::MemberEvent;
+use crate::{
+    ext::UserdataExt,
+    matcher::{
+        impersonation::{find_impersonated, StaffCache},
+        Match, Matcher,
+    },
+    module::{settings::ImpersonationSettings, ModuleKind},
+    DbPool,
+};
+use serenity::{async_trait, prelude::TypeMap, CacheAndHttp};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// the same checks as the message matcher, but for members as they join or change their name so they can be caught
+// before they message anyone
+pub struct MemberImpersonation {
+    staff: StaffCache,
+}
+
+#[async_trait]
+impl Matcher<MemberEvent> for MemberImpersonation {
+    type SettingsType = ImpersonationSettings;
+
+    async fn build(
+        userdata: Arc<RwLock<TypeMap>>,
+        cache_http: Arc<CacheAndHttp>,
+    ) -> anyhow::Result<(ModuleKind, Self)> {
+        let db_pool = userdata.read().await.get_userdata::<DbPool>()?.clone();
+
+        Ok((
+            ModuleKind::Impersonation,
+            Self {
+                staff: StaffCache::new(db_pool, cache_http),
+            },
+        ))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MemberEvent) -> anyhow::Result<Option<Match>> {
+        let member = &event.member;
+        let staff = self.staff.get(member.guild_id).await?;
+
+        Ok(find_impersonated(
+            &staff,
+            &member.user,
+            member.nick.as_deref(),
+            &settings,
+        ))
+    }
+}
@@ -0,0 +1,78 @@
+use super::MemberEvent;
+use crate::{
+    matcher::{Match, Matcher},
+    module::{settings::NameFilterSettings, ModuleKind},
+    text,
+};
+use log::*;
+use serenity::{async_trait, prelude::TypeMap, CacheAndHttp};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// these sort before letters and numbers, so names starting with them show up at the top of the member list
+const HOISTING_CHARACTERS: &[char] = &[
+    '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+];
+
+pub struct NameFilter;
+
+#[async_trait]
+impl Matcher<MemberEvent> for NameFilter {
+    type SettingsType = NameFilterSettings;
+
+    async fn build(_: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        Ok((ModuleKind::NameFilter, Self))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MemberEvent) -> anyhow::Result<Option<Match>> {
+        let member = &event.member;
+
+        // hoisting only matters for the name shown in the member list, but a username with a nickname over it can still
+        // contain anything else
+        if settings.hoisting && is_hoisted(&member.display_name()) {
+            debug!("Member {} has a hoisted name", member.user.id);
+            return Ok(Some(Match::certain(String::from("has a hoisted name"))));
+        }
+
+        let names = std::iter::once(&member.user.name).chain(member.nick.as_ref());
+        for name in names {
+            if settings.invisible_characters && has_invisible_characters(name) {
+                debug!("Member {} has invisible characters in their name", member.user.id);
+                return Ok(Some(Match::certain(String::from(
+                    "has invisible characters in their name",
+                ))));
+            }
+
+            let folded = text::fold_name(name);
+            if let Some(word) = settings.blocked_words.iter().find(|word| {
+                let word = text::fold_name(word);
+                !word.is_empty() && folded.contains(&word)
+            }) {
+                debug!("Member {} has a blocked word in their name", member.user.id);
+                let reason = format!("has the blocked word {} in their name", word);
+                return Ok(Some(Match::certain(reason)));
+            }
+
+            if let Some(protected) = settings
+                .protected_names
+                .iter()
+                .find(|protected| !folded.is_empty() && text::fold_name(protected) == folded)
+            {
+                debug!("Member {} has a name that looks like a protected name", member.user.id);
+                let reason = format!("has a name that looks like {}", protected);
+                return Ok(Some(Match::certain(reason)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn is_hoisted(name: &str) -> bool {
+    name.trim_start().starts_with(HOISTING_CHARACTERS)
+}
+
+// names consisting of nothing visible show up as blank in the member list and in mentions
+fn has_invisible_characters(name: &str) -> bool {
+    name.chars().any(text::is_invisible) || name.chars().all(|c| c.is_whitespace() || text::is_invisible(c))
+}
@@ -0,0 +1,317 @@
+use super::{
+    links,
+    state::{self, StateSnapshot, UserState},
+    Match, Matcher, MessageEvent,
+};
+use crate::module::{settings::NewAccountSettings, ModuleKind};
+use chrono::{DateTime, Duration, Utc};
+use log::*;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    async_trait,
+    model::{
+        channel::Message,
+        id::{GuildId, UserId},
+    },
+    prelude::TypeMap,
+    CacheAndHttp,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
+use tokio::sync::RwLock;
+
+// members' message counts are kept for at most this long after they've joined. nobody sensible sets the first
+// messages gate longer than this
+const TRACKING_DAYS: i64 = 7;
+// how often the members who joined too long ago to be tracked are forgotten
+const PRUNE_INTERVAL: StdDuration = StdDuration::from_secs(60 * 60);
+
+pub struct NewAccount {
+    // messages can only be counted from members who joined after the counting started, otherwise every existing member
+    // would look like they're posting their first messages
+    tracking_since: DateTime<Utc>,
+    members: HashMap<(GuildId, UserId), NewMember>,
+    last_pruned: Instant,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct NewMember {
+    joined_at: DateTime<Utc>,
+    messages: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NewAccountSnapshot {
+    tracking_since: DateTime<Utc>,
+    members: Vec<UserState<NewMember>>,
+}
+
+#[async_trait]
+impl Matcher for NewAccount {
+    type SettingsType = NewAccountSettings;
+    async fn build(userdata: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        let data = userdata.read().await;
+        let matcher = data
+            .get::<StateSnapshot>()
+            .and_then(|snapshot| snapshot.load::<NewAccountSnapshot>(ModuleKind::NewAccount))
+            .map_or_else(
+                || Self {
+                    tracking_since: Utc::now(),
+                    members: HashMap::new(),
+                    last_pruned: Instant::now(),
+                },
+                |snapshot| Self {
+                    tracking_since: snapshot.tracking_since,
+                    // the snapshot may hold members who have since gone past the cutoff
+                    members: state::restore_users(snapshot.members, |member| {
+                        Some(member).filter(|member| member.joined_at >= Utc::now() - Duration::days(TRACKING_DAYS))
+                    }),
+                    last_pruned: Instant::now(),
+                },
+            );
+
+        Ok((ModuleKind::NewAccount, matcher))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+        let joined_at = msg.member.as_ref().and_then(|member| member.joined_at);
+        let messages = match joined_at {
+            Some(joined_at) if settings.first_messages > 0 => {
+                // creating a thread isn't sending a message, so it's only looked at like an edit is
+                self.count_message(
+                    msg.guild_id.unwrap(),
+                    msg.author.id,
+                    joined_at,
+                    event.edited || event.thread_name,
+                )
+            }
+            _ => None,
+        };
+
+        let new = match newness(msg, joined_at, messages, &settings) {
+            Some(new) => new,
+            None => return Ok(None),
+        };
+
+        // how new the user is decides the score, what they posted only decides whether they're matched at all
+        let posted = if settings.block_links
+            && links::message_texts(msg)
+                .into_iter()
+                .any(|text| !links::extract_urls(text, false).is_empty())
+        {
+            debug!("New user {} posted a link", msg.author.id);
+            "posted a link"
+        } else if settings.block_attachments && !msg.attachments.is_empty() {
+            debug!("New user {} posted an attachment", msg.author.id);
+            "posted an attachment"
+        } else if settings.block_mentions
+            && (msg.mention_everyone || !msg.mentions.is_empty() || !msg.mention_roles.is_empty())
+        {
+            debug!("New user {} mentioned someone", msg.author.id);
+            "mentioned someone"
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(Match::new(new.score, format!("{} and {}", new.reason, posted))))
+    }
+
+    fn save_state(&self, snapshot: &StateSnapshot) -> anyhow::Result<()> {
+        snapshot.save(
+            ModuleKind::NewAccount,
+            &NewAccountSnapshot {
+                tracking_since: self.tracking_since,
+                members: state::flatten_users(&self.members, |member| *member),
+            },
+        )
+    }
+}
+
+impl NewAccount {
+    // counts the message towards the member's first messages and returns how many messages they've now sent in total,
+    // if they joined recently enough that it's known. edits aren't new messages so they aren't counted, but they're
+    // still considered one of the first messages
+    fn count_message(&mut self, guild: GuildId, user: UserId, joined_at: DateTime<Utc>, edited: bool) -> Option<usize> {
+        let cutoff = Utc::now() - Duration::days(TRACKING_DAYS);
+        if joined_at < self.tracking_since || joined_at < cutoff {
+            return None;
+        }
+
+        if self.last_pruned.elapsed() >= PRUNE_INTERVAL {
+            self.last_pruned = Instant::now();
+            self.members.retain(|_, member| member.joined_at >= cutoff);
+        }
+        let member = self
+            .members
+            .entry((guild, user))
+            .or_insert(NewMember { joined_at, messages: 0 });
+
+        // someone who left and joined back starts from a clean slate
+        if member.joined_at != joined_at {
+            *member = NewMember { joined_at, messages: 0 };
+        }
+
+        if !edited {
+            member.messages += 1;
+        }
+        Some(member.messages.max(1))
+    }
+}
+
+// how new the user is, if they're new at all. the newer they are the higher the score, so e.g. an account created a
+// minute ago scores higher than one that's almost old enough to no longer be new. the newest of the checks wins
+fn newness(
+    msg: &Message,
+    joined_at: Option<DateTime<Utc>>,
+    messages: Option<usize>,
+    settings: &NewAccountSettings,
+) -> Option<Match> {
+    let now = Utc::now();
+    let mut checks = Vec::new();
+
+    let account_age = (now - msg.author.id.created_at()).num_minutes().max(0) as u64;
+    if settings.account_age > 0 && account_age < settings.account_age {
+        debug!("User {} account is {} minutes old", msg.author.id, account_age);
+        checks.push(Match::scaled(
+            (settings.account_age - account_age) as usize,
+            0,
+            settings.account_age as usize,
+            format!("has an account created {} minutes ago", account_age),
+        ));
+    }
+
+    if let Some(joined_at) = joined_at {
+        let member_age = (now - joined_at).num_minutes().max(0) as u64;
+        if settings.join_age > 0 && member_age < settings.join_age {
+            debug!("User {} joined {} minutes ago", msg.author.id, member_age);
+            checks.push(Match::scaled(
+                (settings.join_age - member_age) as usize,
+                0,
+                settings.join_age as usize,
+                format!("joined {} minutes ago", member_age),
+            ));
+        }
+    }
+
+    if let Some(messages) = messages {
+        if messages <= settings.first_messages {
+            debug!("Message {} is user {}'s message #{}", msg.id, msg.author.id, messages);
+            checks.push(Match::scaled(
+                settings.first_messages + 1 - messages,
+                0,
+                settings.first_messages,
+                format!("sent their message #{} after joining", messages),
+            ));
+        }
+    }
+
+    checks.into_iter().max_by_key(|matched| matched.score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::MAX_SCORE;
+    use serde_json::json;
+
+    // Discord's epoch, which user IDs count their creation time from
+    const DISCORD_EPOCH_MILLIS: i64 = 1_420_070_400_000;
+
+    fn message_by_account_created(created_at: DateTime<Utc>) -> Message {
+        let id = ((created_at.timestamp_millis() - DISCORD_EPOCH_MILLIS) as u64) << 22;
+        serde_json::from_value(json!({
+            "id": "200",
+            "channel_id": "300",
+            "guild_id": "100",
+            "author": {
+                "id": id.to_string(),
+                "username": "someone",
+                "discriminator": "0001",
+                "avatar": null,
+            },
+            "content": "",
+            "timestamp": Utc::now(),
+            "edited_timestamp": null,
+            "type": 0,
+            "tts": false,
+            "pinned": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+        }))
+        .unwrap()
+    }
+
+    fn tracker(tracking_since: DateTime<Utc>) -> NewAccount {
+        NewAccount {
+            tracking_since,
+            members: HashMap::new(),
+            last_pruned: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn newer_accounts_score_higher() {
+        let settings = NewAccountSettings::default();
+        let now = Utc::now();
+
+        let brand_new = message_by_account_created(now);
+        let almost_old = message_by_account_created(now - Duration::minutes(settings.account_age as i64 - 60));
+        let old = message_by_account_created(now - Duration::days(30));
+
+        let brand_new = newness(&brand_new, None, None, &settings).unwrap();
+        let almost_old = newness(&almost_old, None, None, &settings).unwrap();
+        assert_eq!(brand_new.score, MAX_SCORE);
+        assert!(almost_old.score < brand_new.score);
+        assert!(newness(&old, None, None, &settings).is_none());
+    }
+
+    #[test]
+    fn newest_check_wins() {
+        let settings = NewAccountSettings {
+            first_messages: 3,
+            ..NewAccountSettings::default()
+        };
+        let now = Utc::now();
+        let old = message_by_account_created(now - Duration::days(30));
+
+        let joined = newness(&old, Some(now - Duration::minutes(1)), Some(3), &settings).unwrap();
+        assert!(joined.reason.starts_with("joined"));
+        let first = newness(&old, Some(now - Duration::days(1)), Some(1), &settings).unwrap();
+        assert!(first.reason.starts_with("sent their message #1"));
+        assert!(newness(&old, Some(now - Duration::days(1)), Some(4), &settings).is_none());
+    }
+
+    #[test]
+    fn messages_are_counted_from_joining() {
+        let now = Utc::now();
+        let mut matcher = tracker(now - Duration::hours(1));
+        let (guild, user) = (GuildId(1), UserId(2));
+        let joined_at = now - Duration::minutes(5);
+
+        assert_eq!(matcher.count_message(guild, user, joined_at, false), Some(1));
+        assert_eq!(matcher.count_message(guild, user, joined_at, true), Some(1));
+        assert_eq!(matcher.count_message(guild, user, joined_at, false), Some(2));
+
+        // joining back starts over
+        assert_eq!(matcher.count_message(guild, user, now, false), Some(1));
+    }
+
+    #[test]
+    fn members_from_before_tracking_are_not_counted() {
+        let now = Utc::now();
+        let mut matcher = tracker(now);
+
+        assert_eq!(
+            matcher.count_message(GuildId(1), UserId(2), now - Duration::minutes(5), false),
+            None
+        );
+        assert!(matcher.members.is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+use super::state::{self, StateSnapshot, UserState};
+use crate::module::ModuleKind;
+use chrono::{DateTime, Utc};
+use log::*;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::{
+        channel::{Reaction, ReactionType},
+        id::{ChannelId, GuildId, MessageId, UserId},
+    },
+    prelude::TypeMapKey,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+// how often the users who stopped reacting are forgotten
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+// reactions aren't messages so they don't go through the matchers, instead the reaction handler tracks them here
+#[derive(Debug, Clone, Default)]
+pub struct ReactionTracker {
+    users: Arc<RwLock<TrackedUsers>>,
+}
+
+impl TypeMapKey for ReactionTracker {
+    type Value = ReactionTracker;
+}
+
+#[derive(Debug, Default)]
+struct TrackedUsers {
+    reactions: HashMap<(GuildId, UserId), UserReactions>,
+    last_pruned: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct UserReactions {
+    window: Duration,
+    reactions: VecDeque<TrackedReaction>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackedReaction {
+    pub channel: ChannelId,
+    pub message: MessageId,
+    pub emoji: ReactionType,
+    added: Instant,
+}
+
+// instants can't be serialized, so the snapshot has the wall-clock time each reaction was added at instead
+#[derive(Serialize, Deserialize)]
+struct UserReactionsSnapshot {
+    window: Duration,
+    reactions: Vec<TrackedReactionSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TrackedReactionSnapshot {
+    channel: ChannelId,
+    message: MessageId,
+    emoji: ReactionType,
+    added: DateTime<Utc>,
+}
+
+impl ReactionTracker {
+    // the reactions that have gone past their window since the snapshot was saved are left out
+    pub fn restore(snapshot: &StateSnapshot) -> Self {
+        let snapshots =
+            if let Some(snapshots) = snapshot.load::<Vec<UserState<UserReactionsSnapshot>>>(ModuleKind::ReactionSpam) {
+                snapshots
+            } else {
+                return Self::default();
+            };
+
+        let now = Utc::now();
+        let reactions = state::restore_users(snapshots, |snapshot| {
+            let window = snapshot.window;
+            let reactions = snapshot
+                .reactions
+                .into_iter()
+                .filter_map(|reaction| {
+                    let age = (now - reaction.added).to_std().ok()?;
+                    if age >= window {
+                        return None;
+                    }
+
+                    Some(TrackedReaction {
+                        channel: reaction.channel,
+                        message: reaction.message,
+                        emoji: reaction.emoji,
+                        added: Instant::now().checked_sub(age)?,
+                    })
+                })
+                .collect::<VecDeque<_>>();
+
+            if reactions.is_empty() {
+                None
+            } else {
+                Some(UserReactions { window, reactions })
+            }
+        });
+
+        debug!("Restored {} users' reactions from snapshot", reactions.len());
+        Self {
+            users: Arc::new(RwLock::new(TrackedUsers {
+                reactions,
+                last_pruned: None,
+            })),
+        }
+    }
+
+    pub async fn save_state(&self, snapshot: &StateSnapshot) -> anyhow::Result<()> {
+        let now = Utc::now();
+        let users = self.users.read().await;
+        let snapshots = state::flatten_users(&users.reactions, |tracked| UserReactionsSnapshot {
+            window: tracked.window,
+            reactions: tracked
+                .reactions
+                .iter()
+                .map(|reaction| TrackedReactionSnapshot {
+                    channel: reaction.channel,
+                    message: reaction.message,
+                    emoji: reaction.emoji.clone(),
+                    added: now
+                        - chrono::Duration::from_std(reaction.added.elapsed())
+                            .unwrap_or_else(|_| chrono::Duration::zero()),
+                })
+                .collect(),
+        });
+
+        snapshot.save(ModuleKind::ReactionSpam, &snapshots)
+    }
+
+    // tracks the reaction and returns all of the user's reactions within the window if there are more of them than the
+    // limit. the returned reactions are forgotten so the same reactions can't match twice
+    pub async fn track(
+        &self,
+        guild: GuildId,
+        user: UserId,
+        reaction: &Reaction,
+        limit: usize,
+        window: Duration,
+    ) -> Option<Vec<TrackedReaction>> {
+        let mut users = self.users.write().await;
+        users.prune();
+
+        let tracked = users.reactions.entry((guild, user)).or_insert_with(|| UserReactions {
+            window,
+            reactions: VecDeque::new(),
+        });
+        tracked.window = window;
+
+        let reactions = &mut tracked.reactions;
+        while reactions.front().is_some_and(|oldest| oldest.added.elapsed() >= window) {
+            reactions.pop_front();
+        }
+
+        reactions.push_back(TrackedReaction {
+            channel: reaction.channel_id,
+            message: reaction.message_id,
+            emoji: reaction.emoji.clone(),
+            added: Instant::now(),
+        });
+
+        debug!(
+            "User {} added {} reactions within the window in {}",
+            user,
+            reactions.len(),
+            guild
+        );
+        if reactions.len() > limit {
+            Some(reactions.drain(..).collect())
+        } else {
+            None
+        }
+    }
+}
+
+impl TrackedUsers {
+    // users who stopped reacting are dropped every once in a while instead of in a separate task
+    fn prune(&mut self) {
+        if self.last_pruned.is_some_and(|pruned| pruned.elapsed() < PRUNE_INTERVAL) {
+            return;
+        }
+        self.last_pruned = Some(Instant::now());
+
+        self.reactions.retain(|_, tracked| {
+            tracked
+                .reactions
+                .back()
+                .is_some_and(|newest| newest.added.elapsed() < tracked.window)
+        });
+    }
+}
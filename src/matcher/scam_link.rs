@@ -0,0 +1,205 @@
+use super::{blocklist::DomainBlocklist, links, Match, Matcher, MessageEvent};
+use crate::{
+    module::{settings::ScamLinkSettings, ModuleKind},
+    text,
+};
+use log::*;
+use serenity::{async_trait, prelude::TypeMap, CacheAndHttp};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// the domains scammers most often imitate. links to these or their subdomains are never typosquats
+const BRAND_DOMAINS: &[&str] = &[
+    "discord.com",
+    "discord.gg",
+    "discord.gift",
+    "discordapp.com",
+    "discordapp.net",
+    "steamcommunity.com",
+    "steampowered.com",
+];
+// other legitimate domains that happen to be within a typo or two of a brand domain
+const KNOWN_DOMAINS: &[&str] = &[
+    "discord.co",
+    "discord.dev",
+    "discord.new",
+    "discord.media",
+    "discordstatus.com",
+    "steamstatic.com",
+    // a server and bot listing site, one letter away from discord.com
+    "discords.com",
+    // third-party invite services, left to the invite link module
+    "discord.io",
+    "discord.me",
+];
+// two typos away from a short brand such as discord.com is already a different, legitimate word (discogs.com), so
+// brands whose name is shorter than this only match a single typo whatever the setting is
+const SHORT_BRAND_LENGTH: usize = 10;
+
+pub struct ScamLink {
+    blocklist: Option<DomainBlocklist>,
+}
+
+#[async_trait]
+impl Matcher for ScamLink {
+    type SettingsType = ScamLinkSettings;
+    async fn build(userdata: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        let blocklist = userdata.read().await.get::<DomainBlocklist>().cloned();
+        if blocklist.is_none() {
+            info!("No domain blocklist configured, scam links are only matched by typosquats and guild settings");
+        }
+
+        Ok((ModuleKind::ScamLink, Self { blocklist }))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+        if let Some(blocklist) = &mut self.blocklist {
+            blocklist.reload_if_changed();
+        }
+
+        for text in links::message_texts(msg) {
+            for url in links::extract_urls(text, false) {
+                let matched = links::normalized_host(&url).and_then(|host| self.match_host(&host, &settings));
+                if matched.is_some() {
+                    return Ok(matched);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl ScamLink {
+    fn match_host(&self, host: &str, settings: &ScamLinkSettings) -> Option<Match> {
+        if settings
+            .allowed_domains
+            .iter()
+            .any(|domain| links::host_matches(host, domain.as_str()))
+        {
+            debug!("{} is an allowed domain", host);
+            return None;
+        }
+
+        if self.is_blocked(host, settings) {
+            info!("{} is a blocked domain", host);
+            return Some(Match::certain(format!("links to the blocked domain {}", host)));
+        }
+
+        let brand = typosquatted_brand(host, settings.typosquat_distance)?;
+        info!("{} looks like a typosquat of {}", host, brand);
+        Some(Match::certain(format!("links to {}, which looks like {}", host, brand)))
+    }
+
+    fn is_blocked(&self, host: &str, settings: &ScamLinkSettings) -> bool {
+        settings
+            .blocked_domains
+            .iter()
+            .any(|domain| links::host_matches(host, domain.as_str()))
+            || self
+                .blocklist
+                .as_ref()
+                .is_some_and(|blocklist| blocklist.contains(host))
+    }
+}
+
+// the brand domain the host is within the given edit distance of, if any. both the full host and its last two labels
+// are compared so typosquats behind a subdomain, such as nitro.discrod.com, are caught as well
+fn typosquatted_brand(host: &str, max_distance: usize) -> Option<&'static str> {
+    if max_distance == 0 {
+        return None;
+    }
+
+    let is_legitimate = |domain: &str| {
+        BRAND_DOMAINS
+            .iter()
+            .chain(KNOWN_DOMAINS)
+            .any(|legitimate| links::host_matches(domain, legitimate))
+    };
+
+    if is_legitimate(host) {
+        return None;
+    }
+
+    // internationalized domains have already been converted to punycode by the URL parser, so homoglyph domains are
+    // far away from the brands and only caught by the blocklists
+    let registrable = registrable_part(host);
+    BRAND_DOMAINS.iter().copied().find(|brand| {
+        let max_distance = brand_distance(brand, max_distance);
+        [host, registrable]
+            .iter()
+            .any(|candidate| !is_legitimate(candidate) && text::edit_distance(candidate, brand) <= max_distance)
+    })
+}
+
+fn brand_distance(brand: &str, max_distance: usize) -> usize {
+    let name = brand.split('.').next().unwrap_or(brand);
+    if name.len() < SHORT_BRAND_LENGTH {
+        max_distance.min(1)
+    } else {
+        max_distance
+    }
+}
+
+// the last two labels of the host, e.g. example.com out of sub.example.com. this ignores multi-label public suffixes
+// such as co.uk, but none of the brands use one
+fn registrable_part(host: &str) -> &str {
+    match host.rmatch_indices('.').nth(1) {
+        Some((idx, _)) => &host[idx + 1..],
+        None => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::settings::SettingList;
+    use std::str::FromStr;
+
+    #[test]
+    fn typos_of_brands_are_matched() {
+        assert_eq!(typosquatted_brand("dlscord.com", 1), Some("discord.com"));
+        assert_eq!(typosquatted_brand("discord.gif", 1), Some("discord.gift"));
+        assert_eq!(typosquatted_brand("nitro.dlscord.com", 1), Some("discord.com"));
+        assert_eq!(typosquatted_brand("steamcommunlty.com", 1), Some("steamcommunity.com"));
+        assert_eq!(typosquatted_brand("dlscord.com", 0), None);
+    }
+
+    #[test]
+    fn short_brands_only_match_one_typo() {
+        // two typos away from discord.com
+        assert_eq!(typosquatted_brand("discogs.com", 2), None);
+        assert_eq!(typosquatted_brand("stearncommunity.com", 1), None);
+        assert_eq!(typosquatted_brand("stearncommunity.com", 2), Some("steamcommunity.com"));
+    }
+
+    #[test]
+    fn legitimate_domains_are_not_typosquats() {
+        for host in [
+            "discord.com",
+            "cdn.discordapp.com",
+            "discord.dev",
+            "discords.com",
+            "discord.io",
+        ] {
+            assert_eq!(typosquatted_brand(host, 2), None, "{}", host);
+        }
+    }
+
+    #[test]
+    fn allowed_domains_are_never_matched() {
+        let matcher = ScamLink { blocklist: None };
+        let mut settings = ScamLinkSettings {
+            blocked_domains: SettingList::from_str("example.com").unwrap(),
+            ..ScamLinkSettings::default()
+        };
+
+        assert!(matcher.match_host("sub.example.com", &settings).is_some());
+        assert!(matcher.match_host("dlscord.com", &settings).is_some());
+
+        settings.allowed_domains = SettingList::from_str("example.com, dlscord.com").unwrap();
+        assert!(matcher.match_host("sub.example.com", &settings).is_none());
+        assert!(matcher.match_host("dlscord.com", &settings).is_none());
+    }
+}
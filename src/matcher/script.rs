@@ -0,0 +1,189 @@
+use super::{Match, Matcher, MessageEvent};
+use crate::{
+    ext::UserdataExt,
+    module::{
+        script::{self, ScriptEngine},
+        settings::ScriptSettings,
+        ModuleKind,
+    },
+    DbConnPool, DbPool,
+};
+use chrono::Utc;
+use log::*;
+use rhai::{Array, Dynamic, Map, AST};
+use serenity::{async_trait, model::id::GuildId, prelude::TypeMap, CacheAndHttp};
+use std::{collections::HashMap, sync::Arc};
+use tokio::{sync::RwLock, task};
+
+pub struct Script {
+    engine: Arc<ScriptEngine>,
+    scripts: HashMap<GuildId, CachedScript>,
+    db_pool: DbConnPool,
+}
+
+// the script is compiled once and kept around as long as its source stays the same. the store is loaded from the
+// database along with it and saved back whenever the script changes it
+struct CachedScript {
+    source: String,
+    ast: Arc<AST>,
+    store: Map,
+    saved_store: String,
+}
+
+#[async_trait]
+impl Matcher for Script {
+    type SettingsType = ScriptSettings;
+
+    async fn build(userdata: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        let db_pool = userdata.read().await.get_userdata::<DbPool>()?.clone();
+
+        Ok((
+            ModuleKind::Script,
+            Self {
+                engine: Arc::new(ScriptEngine::new()),
+                scripts: HashMap::new(),
+                db_pool,
+            },
+        ))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+        let guild_id = msg.guild_id.unwrap();
+
+        if settings.script.is_empty() {
+            // the store might've been cleared along with the script, so it has to be loaded again for a new script
+            self.scripts.remove(&guild_id);
+            return Ok(None);
+        }
+
+        let source = settings.script.to_string();
+        let up_to_date = self
+            .scripts
+            .get(&guild_id)
+            .map_or(false, |cached| cached.source == source);
+
+        if !up_to_date {
+            debug!("Compiling script for {}", guild_id);
+            let ast = Arc::new(self.engine.compile(&settings.script)?);
+
+            // a recompiled script keeps using the store of the previous version
+            let cached = if let Some(cached) = self.scripts.remove(&guild_id) {
+                CachedScript { source, ast, ..cached }
+            } else {
+                let store = script::load_store(guild_id, &self.db_pool.get()?)?;
+                let saved_store = script::store_to_json(&store)?;
+                CachedScript {
+                    source,
+                    ast,
+                    store,
+                    saved_store,
+                }
+            };
+            self.scripts.insert(guild_id, cached);
+        }
+
+        let cached = self.scripts.get_mut(&guild_id).unwrap();
+        let engine = Arc::clone(&self.engine);
+        let ast = Arc::clone(&cached.ast);
+        let mut store = cached.store.clone();
+        let view = message_view(event);
+
+        // the script is limited in how long it runs, but it's still CPU-bound work that shouldn't block the runtime
+        let (result, store) = task::spawn_blocking(move || {
+            let result = engine.run(&ast, view, &mut store);
+            (result, store)
+        })
+        .await?;
+
+        // a store that can't be saved is thrown away, so the script never sees values it couldn't get back later
+        match script::store_to_json(&store) {
+            Ok(json) if json != cached.saved_store => {
+                // a script may change its store on every message, so the write is kept off the runtime as well
+                let db_pool = self.db_pool.clone();
+                let saved = json.clone();
+                task::spawn_blocking(move || script::save_store(guild_id, saved, &db_pool.get()?)).await??;
+                cached.store = store;
+                cached.saved_store = json;
+            }
+            Ok(_) => (),
+            Err(e) => warn!("Discarding changes to the script store in {}: {}", guild_id, e),
+        }
+
+        if result? {
+            Ok(Some(Match::certain(String::from("matched the script"))))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// a copy of the message's details for the script. changing it has no effect on anything
+fn message_view(event: &MessageEvent) -> Map {
+    let msg = &event.msg;
+    let now = Utc::now();
+
+    let mut author = Map::new();
+    author.insert("id".into(), Dynamic::from(msg.author.id.0 as i64));
+    author.insert("name".into(), Dynamic::from(msg.author.name.clone()));
+    author.insert("bot".into(), Dynamic::from(msg.author.bot));
+    author.insert(
+        "account_age".into(),
+        Dynamic::from((now - msg.author.id.created_at()).num_seconds()),
+    );
+
+    if let Some(member) = &msg.member {
+        let nick = member.nick.clone().map_or(Dynamic::UNIT, Dynamic::from);
+        let member_age = member.joined_at.map_or(Dynamic::UNIT, |joined_at| {
+            Dynamic::from((now - joined_at).num_seconds())
+        });
+        let roles = member
+            .roles
+            .iter()
+            .map(|role| Dynamic::from(role.0 as i64))
+            .collect::<Array>();
+
+        author.insert("nick".into(), nick);
+        author.insert("member_age".into(), member_age);
+        author.insert("roles".into(), Dynamic::from(roles));
+    }
+
+    let attachments = msg
+        .attachments
+        .iter()
+        .map(|attachment| {
+            let mut view = Map::new();
+            view.insert("filename".into(), Dynamic::from(attachment.filename.clone()));
+            view.insert("size".into(), Dynamic::from(attachment.size as i64));
+            view.insert(
+                "content_type".into(),
+                attachment.content_type.clone().map_or(Dynamic::UNIT, Dynamic::from),
+            );
+            Dynamic::from(view)
+        })
+        .collect::<Array>();
+    let mentions = msg
+        .mentions
+        .iter()
+        .map(|user| Dynamic::from(user.id.0 as i64))
+        .collect::<Array>();
+    let role_mentions = msg
+        .mention_roles
+        .iter()
+        .map(|role| Dynamic::from(role.0 as i64))
+        .collect::<Array>();
+
+    let mut view = Map::new();
+    view.insert("id".into(), Dynamic::from(msg.id.0 as i64));
+    view.insert("channel".into(), Dynamic::from(event.channel().0 as i64));
+    view.insert("content".into(), Dynamic::from(msg.content.clone()));
+    view.insert("author".into(), Dynamic::from(author));
+    view.insert("attachments".into(), Dynamic::from(attachments));
+    view.insert("embeds".into(), Dynamic::from(msg.embeds.len() as i64));
+    view.insert("stickers".into(), Dynamic::from(msg.stickers.len() as i64));
+    view.insert("mentions".into(), Dynamic::from(mentions));
+    view.insert("role_mentions".into(), Dynamic::from(role_mentions));
+    view.insert("edited".into(), Dynamic::from(event.edited));
+    view.insert("thread_name".into(), Dynamic::from(event.thread_name));
+    view
+}
@@ -1,30 +1,356 @@
-use super::Matcher;
+use super::{
+    state::{self, StateSnapshot, UserState},
+    Match, Matcher, MessageEvent,
+};
 use crate::module::{settings::SelfbotSettings, ModuleKind};
-use serenity::{async_trait, model::channel::Message, prelude::TypeMap};
-use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
+use circular_queue::CircularQueue;
+use log::*;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    async_trait,
+    model::{
+        channel::Message,
+        id::{GuildId, UserId},
+    },
+    prelude::TypeMap,
+    CacheAndHttp,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
 use tokio::sync::RwLock;
 
 const RICH_EMBED: &str = "rich";
+// how many of the user's latest message timestamps are kept to compare the intervals between them
+const TIMESTAMP_HISTORY: usize = 5;
+// messages further apart than this aren't compared to each other, so a user's timestamps are forgotten once their
+// latest message is this old
+const TIMESTAMP_MAX_AGE_MINUTES: i64 = 60;
+// how often the timestamps of users who have gone quiet are forgotten
+const PRUNE_INTERVAL: StdDuration = StdDuration::from_secs(60);
+// typing speed is only meaningful for messages long enough that they couldn't have been typed in a blink
+const TYPING_MINIMUM_LENGTH: usize = 20;
 
-pub struct Selfbot {}
+// how much each signal contributes to the final score. the rich embed is the strongest single signal, the rest are
+// only suspicious in combination with each other
+const RICH_EMBED_SCORE: u32 = 2;
+const TYPING_SPEED_SCORE: u32 = 1;
+const MISSING_NONCE_SCORE: u32 = 1;
+const REGULAR_INTERVAL_SCORE: u32 = 2;
+const RAPID_EDIT_SCORE: u32 = 1;
+// the highest score a single message can get, which is what a certain match takes
+const MAX_SIGNAL_SCORE: u32 = RICH_EMBED_SCORE + TYPING_SPEED_SCORE + MISSING_NONCE_SCORE + REGULAR_INTERVAL_SCORE;
+
+pub struct Selfbot {
+    timestamps: HashMap<(GuildId, UserId), CircularQueue<DateTime<Utc>>>,
+    last_pruned: Instant,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TimestampsSnapshot {
+    timestamps: Vec<DateTime<Utc>>,
+}
 
 #[async_trait]
 impl Matcher for Selfbot {
     type SettingsType = SelfbotSettings;
-    async fn build(_: Arc<RwLock<TypeMap>>) -> (ModuleKind, Self) {
-        (ModuleKind::Selfbot, Self {})
-    }
-
-    async fn is_match(&mut self, _: Self::SettingsType, msg: &Message) -> anyhow::Result<bool> {
-        if let Some(embed) = msg.embeds.first() {
-            // TODO: this isn't exactly a good method
-            // the embed type is really just a loose nudge indicating what the embed might be, and it might be removed
-            // in a future Discord API version. in any case, every embed not generated by Discord is "rich", this
-            // includes embed's posted via the API (i.e. if a user's message has a rich embed in their message, they've
-            // very likely posted it through the API which is selfbotting)
-            Ok(embed.kind == RICH_EMBED)
+    async fn build(userdata: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        let timestamps = userdata
+            .read()
+            .await
+            .get::<StateSnapshot>()
+            .and_then(|snapshot| snapshot.load::<Vec<UserState<TimestampsSnapshot>>>(ModuleKind::Selfbot))
+            .map(restore_timestamps)
+            .unwrap_or_default();
+
+        Ok((
+            ModuleKind::Selfbot,
+            Self {
+                timestamps,
+                last_pruned: Instant::now(),
+            },
+        ))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        // a thread's name has none of the signals a message has
+        if event.thread_name {
+            return Ok(None);
+        }
+
+        let msg = &event.msg;
+        let mut score = 0;
+
+        // the embed type is really just a loose nudge indicating what the embed might be, and it might be removed in a
+        // future Discord API version. in any case, every embed not generated by Discord is "rich", this includes
+        // embed's posted via the API (i.e. if a user's message has a rich embed in their message, they've very likely
+        // posted it through the API which is selfbotting)
+        if !msg.author.bot && msg.embeds.iter().any(|embed| embed.kind == RICH_EMBED) {
+            debug!("Message {} has a rich embed", msg.id);
+            score += RICH_EMBED_SCORE;
+        }
+
+        // an edit isn't a new message so it doesn't say anything about the user's typing speed or message intervals,
+        // and the message it was sent as has already been scored for those
+        if event.edited {
+            if let Some(edited) = msg.edited_timestamp {
+                if edited - msg.timestamp < Duration::milliseconds(settings.edit_time as i64) {
+                    debug!("Message {} was edited right after it was sent", msg.id);
+                    score += RAPID_EDIT_SCORE;
+                }
+            }
+        } else {
+            score += self.score_new_message(msg, &settings);
+        }
+
+        debug!("Message {} selfbot score: {} / {}", msg.id, score, settings.threshold);
+        if score >= settings.threshold {
+            let reason = format!("has a selfbot score of {} out of {}", score, MAX_SIGNAL_SCORE);
+            Ok(Some(Match::scaled(
+                score as usize,
+                settings.threshold as usize,
+                MAX_SIGNAL_SCORE as usize,
+                reason,
+            )))
         } else {
-            Ok(false)
+            Ok(None)
+        }
+    }
+
+    fn save_state(&self, snapshot: &StateSnapshot) -> anyhow::Result<()> {
+        let timestamps = state::flatten_users(&self.timestamps, |timestamps| TimestampsSnapshot {
+            timestamps: timestamps.asc_iter().copied().collect(),
+        });
+
+        snapshot.save(ModuleKind::Selfbot, &timestamps)
+    }
+}
+
+impl Selfbot {
+    fn score_new_message(&mut self, msg: &Message, settings: &SelfbotSettings) -> u32 {
+        self.prune();
+
+        let timestamps = self
+            .timestamps
+            .entry((msg.guild_id.unwrap(), msg.author.id))
+            .or_insert_with(|| CircularQueue::with_capacity(TIMESTAMP_HISTORY));
+        let previous = timestamps.iter().next().copied();
+        if previous.is_some_and(|previous| is_stale(previous, msg.timestamp)) {
+            timestamps.clear();
+        }
+        timestamps.push(msg.timestamp);
+
+        let mut score = 0;
+
+        if let Some(previous) = previous {
+            if is_impossibly_fast(&msg.content, msg.timestamp - previous, settings.typing_speed) {
+                debug!("Message {} was typed impossibly fast", msg.id);
+                score += TYPING_SPEED_SCORE;
+            }
         }
+
+        // the official clients always send a nonce with each message, while most libraries don't unless explicitly told
+        if msg.nonce.is_null() {
+            debug!("Message {} has no nonce", msg.id);
+            score += MISSING_NONCE_SCORE;
+        }
+
+        if has_regular_intervals(timestamps, settings.interval_tolerance) {
+            debug!("Message {} continues a series of regular intervals", msg.id);
+            score += REGULAR_INTERVAL_SCORE;
+        }
+
+        score
+    }
+
+    fn prune(&mut self) {
+        if self.last_pruned.elapsed() < PRUNE_INTERVAL {
+            return;
+        }
+        self.last_pruned = Instant::now();
+
+        let now = Utc::now();
+        self.timestamps
+            .retain(|_, timestamps| timestamps.iter().next().is_some_and(|latest| !is_stale(*latest, now)));
+    }
+}
+
+fn restore_timestamps(
+    snapshots: Vec<UserState<TimestampsSnapshot>>,
+) -> HashMap<(GuildId, UserId), CircularQueue<DateTime<Utc>>> {
+    let now = Utc::now();
+    let restored = state::restore_users(snapshots, |snapshot| {
+        // the timestamps are in ascending order, so the last one is the latest
+        if snapshot.timestamps.last().is_none_or(|latest| is_stale(*latest, now)) {
+            return None;
+        }
+
+        let mut timestamps = CircularQueue::with_capacity(TIMESTAMP_HISTORY);
+        for timestamp in snapshot.timestamps {
+            timestamps.push(timestamp);
+        }
+        Some(timestamps)
+    });
+
+    debug!("Restored {} users' message timestamps from snapshot", restored.len());
+    restored
+}
+
+fn is_stale(timestamp: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    now - timestamp > Duration::minutes(TIMESTAMP_MAX_AGE_MINUTES)
+}
+
+fn is_impossibly_fast(content: &str, since_previous: Duration, typing_speed: u32) -> bool {
+    let length = content.chars().count();
+    if length < TYPING_MINIMUM_LENGTH || typing_speed == 0 {
+        return false;
+    }
+
+    // anything pasted in would be "typed" impossibly fast as well, but pasting a message right after sending the
+    // previous one over and over again is suspicious on its own
+    let millis = since_previous.num_milliseconds().max(1);
+    (length as i64 * 1000) / millis > typing_speed as i64
+}
+
+fn has_regular_intervals(timestamps: &CircularQueue<DateTime<Utc>>, tolerance: u64) -> bool {
+    if !timestamps.is_full() {
+        return false;
+    }
+
+    let intervals = timestamps
+        .asc_iter()
+        .zip(timestamps.asc_iter().skip(1))
+        .map(|(earlier, later)| (*later - *earlier).num_milliseconds())
+        .collect::<Vec<_>>();
+
+    match (intervals.iter().min(), intervals.iter().max()) {
+        (Some(min), Some(max)) => (max - min) as u64 <= tolerance,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(content: &str, timestamp: DateTime<Utc>, nonce: Option<&str>) -> Message {
+        serde_json::from_value(json!({
+            "id": "200",
+            "channel_id": "300",
+            "guild_id": "100",
+            "author": {
+                "id": "400",
+                "username": "someone",
+                "discriminator": "0001",
+                "avatar": null,
+            },
+            "content": content,
+            "timestamp": timestamp,
+            "edited_timestamp": null,
+            "nonce": nonce,
+            "type": 0,
+            "tts": false,
+            "pinned": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+        }))
+        .unwrap()
+    }
+
+    fn selfbot() -> Selfbot {
+        Selfbot {
+            timestamps: HashMap::new(),
+            last_pruned: Instant::now(),
+        }
+    }
+
+    fn queue(timestamps: &[DateTime<Utc>]) -> CircularQueue<DateTime<Utc>> {
+        let mut queue = CircularQueue::with_capacity(TIMESTAMP_HISTORY);
+        for timestamp in timestamps {
+            queue.push(*timestamp);
+        }
+        queue
+    }
+
+    #[test]
+    fn typing_speed_is_checked_for_long_messages() {
+        let content = "this message is long enough to count its typing speed";
+        assert!(is_impossibly_fast(content, Duration::seconds(1), 25));
+        assert!(!is_impossibly_fast(content, Duration::seconds(5), 25));
+        assert!(!is_impossibly_fast("short", Duration::milliseconds(1), 25));
+        assert!(!is_impossibly_fast(content, Duration::milliseconds(1), 0));
+    }
+
+    #[test]
+    fn regular_intervals_need_a_full_history() {
+        let start = Utc::now();
+        let regular = (0..TIMESTAMP_HISTORY as i64)
+            .map(|i| start + Duration::milliseconds(i * 2000 + i % 2 * 20))
+            .collect::<Vec<_>>();
+        let irregular = (0..TIMESTAMP_HISTORY as i64)
+            .map(|i| start + Duration::milliseconds(i * i * 1000))
+            .collect::<Vec<_>>();
+
+        assert!(has_regular_intervals(&queue(&regular), 50));
+        assert!(!has_regular_intervals(&queue(&regular), 10));
+        assert!(!has_regular_intervals(&queue(&regular[1..]), 50));
+        assert!(!has_regular_intervals(&queue(&irregular), 50));
+    }
+
+    #[test]
+    fn signals_add_up() {
+        let settings = SelfbotSettings::default();
+        let start = Utc::now();
+
+        let mut matcher = selfbot();
+        let scores = (0..TIMESTAMP_HISTORY as i64)
+            .map(|i| matcher.score_new_message(&message("hi", start + Duration::seconds(i), None), &settings))
+            .collect::<Vec<_>>();
+        assert_eq!(scores[0], MISSING_NONCE_SCORE);
+        assert_eq!(
+            scores[TIMESTAMP_HISTORY - 1],
+            MISSING_NONCE_SCORE + REGULAR_INTERVAL_SCORE
+        );
+
+        let mut matcher = selfbot();
+        let content = "this message is long enough to count its typing speed";
+        matcher.score_new_message(&message(content, start, Some("1")), &settings);
+        let score = matcher.score_new_message(
+            &message(content, start + Duration::milliseconds(500), Some("2")),
+            &settings,
+        );
+        assert_eq!(score, TYPING_SPEED_SCORE);
+    }
+
+    #[test]
+    fn stale_users_are_not_restored() {
+        let now = Utc::now();
+        let snapshots = vec![
+            UserState {
+                guild: GuildId(1),
+                user: UserId(2),
+                state: TimestampsSnapshot {
+                    timestamps: vec![now - Duration::minutes(5), now - Duration::minutes(1)],
+                },
+            },
+            UserState {
+                guild: GuildId(1),
+                user: UserId(3),
+                state: TimestampsSnapshot {
+                    timestamps: vec![now - Duration::minutes(TIMESTAMP_MAX_AGE_MINUTES + 1)],
+                },
+            },
+        ];
+
+        let restored = restore_timestamps(snapshots);
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[&(GuildId(1), UserId(2))].len(), 2);
     }
 }
@@ -0,0 +1,172 @@
+use crate::module::ModuleKind;
+use chrono::{DateTime, Duration, Utc};
+use log::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serenity::{
+    model::id::{GuildId, UserId},
+    prelude::TypeMapKey,
+};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter, ErrorKind},
+    path::PathBuf,
+};
+
+#[derive(Debug, Clone)]
+pub struct StateSnapshot {
+    dir: PathBuf,
+    max_age: Duration,
+}
+
+impl TypeMapKey for StateSnapshot {
+    type Value = StateSnapshot;
+}
+
+// most matchers keep their state per user in each guild, but JSON only allows string keys in maps so the state is saved
+// as a list of entries instead. windows are set per guild, so state counted within one keeps its window next to it,
+// both in memory and in the snapshot
+#[derive(Serialize, Deserialize)]
+pub struct UserState<T> {
+    pub guild: GuildId,
+    pub user: UserId,
+    #[serde(flatten)]
+    pub state: T,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile<T> {
+    saved_at: DateTime<Utc>,
+    state: T,
+}
+
+impl StateSnapshot {
+    pub fn new(dir: PathBuf, max_age: Duration) -> Self {
+        Self { dir, max_age }
+    }
+
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    // a missing, unreadable or stale snapshot is never an error; the matcher simply starts from a clean slate like it
+    // would without snapshots at all
+    pub fn load<T>(&self, kind: ModuleKind) -> Option<T>
+    where
+        T: DeserializeOwned,
+    {
+        let path = self.path_for(kind);
+        let file = match File::open(&path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                debug!("{}: no state snapshot at {}", kind, path.display());
+                return None;
+            }
+            Err(e) => {
+                warn!("{}: failed to open state snapshot {}: {}", kind, path.display(), e);
+                return None;
+            }
+        };
+
+        let snapshot: SnapshotFile<T> = match serde_json::from_reader(BufReader::new(file)) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("{}: failed to read state snapshot {}: {}", kind, path.display(), e);
+                return None;
+            }
+        };
+
+        let age = Utc::now() - snapshot.saved_at;
+        if age > self.max_age {
+            info!(
+                "{}: ignoring stale state snapshot saved at {} ({}s old)",
+                kind,
+                snapshot.saved_at,
+                age.num_seconds()
+            );
+            return None;
+        }
+
+        info!("{}: loaded state snapshot saved at {}", kind, snapshot.saved_at);
+        Some(snapshot.state)
+    }
+
+    pub fn save<T>(&self, kind: ModuleKind, state: &T) -> anyhow::Result<()>
+    where
+        T: Serialize,
+    {
+        fs::create_dir_all(&self.dir)?;
+
+        // the snapshot is written next to the old one and then moved over it, so a crash mid-write can't leave a
+        // truncated snapshot behind
+        let path = self.path_for(kind);
+        let temp_path = path.with_extension("json.tmp");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        serde_json::to_writer(
+            &mut writer,
+            &SnapshotFile {
+                saved_at: Utc::now(),
+                state,
+            },
+        )?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&temp_path, &path)?;
+
+        info!("{}: saved state snapshot to {}", kind, path.display());
+        Ok(())
+    }
+
+    fn path_for(&self, kind: ModuleKind) -> PathBuf {
+        self.dir.join(format!("{}.json", kind))
+    }
+}
+
+pub fn flatten_users<V, T>(users: &HashMap<(GuildId, UserId), V>, mut save: impl FnMut(&V) -> T) -> Vec<UserState<T>> {
+    users
+        .iter()
+        .map(|((guild, user), value)| UserState {
+            guild: *guild,
+            user: *user,
+            state: save(value),
+        })
+        .collect()
+}
+
+// the state that's no longer relevant, e.g. because it's gone past its window since it was saved, is left out by
+// returning None for it
+pub fn restore_users<T, V>(
+    states: Vec<UserState<T>>,
+    mut restore: impl FnMut(T) -> Option<V>,
+) -> HashMap<(GuildId, UserId), V> {
+    states
+        .into_iter()
+        .filter_map(|entry| Some(((entry.guild, entry.user), restore(entry.state)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Counted {
+        count: usize,
+    }
+
+    #[test]
+    fn users_survive_json_round_trip() {
+        let users = [((GuildId(1), UserId(2)), 3), ((GuildId(1), UserId(4)), 0)]
+            .iter()
+            .copied()
+            .collect::<HashMap<_, _>>();
+
+        let json = serde_json::to_string(&flatten_users(&users, |count| Counted { count: *count })).unwrap();
+        // the state's fields sit next to the guild and the user
+        assert!(json.contains(r#""count":3"#) && !json.contains("state"));
+
+        let states = serde_json::from_str::<Vec<UserState<Counted>>>(&json).unwrap();
+        let restored = restore_users(states, |counted| Some(counted.count).filter(|count| *count > 0));
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[&(GuildId(1), UserId(2))], 3);
+    }
+}
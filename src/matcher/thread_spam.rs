@@ -0,0 +1,218 @@
+use super::{
+    state::{self, StateSnapshot, UserState},
+    Match, Matcher, MessageEvent,
+};
+use crate::module::{settings::ThreadSpamSettings, ModuleKind};
+use chrono::{DateTime, Duration, Utc};
+use log::*;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    async_trait,
+    model::id::{ChannelId, GuildId, UserId},
+    prelude::TypeMap,
+    CacheAndHttp,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
+use tokio::sync::RwLock;
+
+// how often the users and channels with no threads created within their window are forgotten
+const PRUNE_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+pub struct ThreadSpam {
+    // when each user created threads
+    users: HashMap<(GuildId, UserId), CreatedThreads>,
+    // when threads were created in each channel
+    channels: HashMap<ChannelId, CreatedThreads>,
+    last_pruned: Instant,
+}
+
+// one entry per thread
+struct CreatedThreads {
+    window: Duration,
+    created: VecDeque<DateTime<Utc>>,
+}
+
+// the channels are saved as a list like the users are
+#[derive(Serialize, Deserialize)]
+struct ThreadSpamSnapshot {
+    users: Vec<UserState<CreatedThreadsSnapshot>>,
+    channels: Vec<(ChannelId, CreatedThreadsSnapshot)>,
+}
+
+// the window is in seconds, since chrono's durations can't be serialized
+#[derive(Serialize, Deserialize)]
+struct CreatedThreadsSnapshot {
+    window: i64,
+    created: Vec<DateTime<Utc>>,
+}
+
+#[async_trait]
+impl Matcher for ThreadSpam {
+    type SettingsType = ThreadSpamSettings;
+
+    async fn build(userdata: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        let snapshot = userdata
+            .read()
+            .await
+            .get::<StateSnapshot>()
+            .and_then(|snapshot| snapshot.load::<ThreadSpamSnapshot>(ModuleKind::ThreadSpam));
+
+        let (users, channels) = if let Some(snapshot) = snapshot {
+            let now = Utc::now();
+            let users = state::restore_users(snapshot.users, |created| CreatedThreads::restore(created, now));
+            let channels = snapshot
+                .channels
+                .into_iter()
+                .filter_map(|(channel, created)| Some((channel, CreatedThreads::restore(created, now)?)))
+                .collect::<HashMap<_, _>>();
+            debug!(
+                "Restored {} users' and {} channels' created threads from snapshot",
+                users.len(),
+                channels.len()
+            );
+            (users, channels)
+        } else {
+            (HashMap::new(), HashMap::new())
+        };
+
+        Ok((
+            ModuleKind::ThreadSpam,
+            Self {
+                users,
+                channels,
+                last_pruned: Instant::now(),
+            },
+        ))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        // regular messages in threads are for the other matchers
+        if !event.thread_name {
+            return Ok(None);
+        }
+
+        let msg = &event.msg;
+        let mut matched: Option<Match> = None;
+        self.prune();
+
+        if settings.user_threads > 0 {
+            let window = Duration::seconds(settings.user_window as i64);
+            let created = CreatedThreads::entry(&mut self.users, (msg.guild_id.unwrap(), msg.author.id), window);
+
+            let count = created.push(msg.timestamp);
+            if count > settings.user_threads {
+                debug!("User {} created {} threads within the window", msg.author.id, count);
+                let reason = format!("created {} threads within {} seconds", count, settings.user_window);
+                created.clear();
+                matched = Some(Match::over_limit(count, settings.user_threads, reason));
+            }
+        }
+
+        // the channel's threads are counted even if the user already matched, otherwise a spammer's threads wouldn't
+        // add up towards the channel's limit. every thread past the limit matches until the burst slows down
+        if settings.channel_threads > 0 {
+            let window = Duration::seconds(settings.channel_window as i64);
+            let created = CreatedThreads::entry(&mut self.channels, event.channel(), window);
+
+            let count = created.push(msg.timestamp);
+            if count > settings.channel_threads {
+                debug!(
+                    "{} threads created in channel {} within the window",
+                    count,
+                    event.channel()
+                );
+                let reason = format!(
+                    "{} threads were created in the channel within {} seconds",
+                    count, settings.channel_window
+                );
+                let channel_match = Match::over_limit(count, settings.channel_threads, reason);
+
+                // the stronger of the two matches wins
+                if matched
+                    .as_ref()
+                    .is_none_or(|matched| channel_match.score > matched.score)
+                {
+                    matched = Some(channel_match);
+                }
+            }
+        }
+
+        Ok(matched)
+    }
+
+    fn save_state(&self, snapshot: &StateSnapshot) -> anyhow::Result<()> {
+        snapshot.save(
+            ModuleKind::ThreadSpam,
+            &ThreadSpamSnapshot {
+                users: state::flatten_users(&self.users, CreatedThreads::snapshot),
+                channels: self
+                    .channels
+                    .iter()
+                    .map(|(channel, created)| (*channel, created.snapshot()))
+                    .collect(),
+            },
+        )
+    }
+}
+
+impl ThreadSpam {
+    fn prune(&mut self) {
+        if self.last_pruned.elapsed() < PRUNE_INTERVAL {
+            return;
+        }
+        self.last_pruned = Instant::now();
+
+        let now = Utc::now();
+        self.users.retain(|_, created| !created.is_stale(now));
+        self.channels.retain(|_, created| !created.is_stale(now));
+    }
+}
+
+impl CreatedThreads {
+    fn entry<K: Eq + Hash>(map: &mut HashMap<K, CreatedThreads>, key: K, window: Duration) -> &mut CreatedThreads {
+        let created = map.entry(key).or_insert_with(|| CreatedThreads {
+            window,
+            created: VecDeque::new(),
+        });
+        created.window = window;
+        created
+    }
+
+    // drops the timestamps older than the window and returns how many are left after adding the new one
+    fn push(&mut self, timestamp: DateTime<Utc>) -> usize {
+        while self.created.front().is_some_and(|old| timestamp - *old > self.window) {
+            self.created.pop_front();
+        }
+        self.created.push_back(timestamp);
+        self.created.len()
+    }
+
+    fn clear(&mut self) {
+        self.created.clear();
+    }
+
+    fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.created.back().is_none_or(|latest| now - *latest > self.window)
+    }
+
+    fn snapshot(&self) -> CreatedThreadsSnapshot {
+        CreatedThreadsSnapshot {
+            window: self.window.num_seconds(),
+            created: self.created.iter().copied().collect(),
+        }
+    }
+
+    // the snapshot may hold users and channels whose threads have since gone past their window
+    fn restore(snapshot: CreatedThreadsSnapshot, now: DateTime<Utc>) -> Option<CreatedThreads> {
+        let created = CreatedThreads {
+            window: Duration::seconds(snapshot.window),
+            created: snapshot.created.into(),
+        };
+        Some(created).filter(|created| !created.is_stale(now))
+    }
+}
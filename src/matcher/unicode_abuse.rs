@@ -0,0 +1,156 @@
+use super::{Match, Matcher, MessageEvent};
+use crate::{
+    module::{settings::UnicodeAbuseSettings, ModuleKind},
+    text,
+};
+use log::*;
+use serenity::{async_trait, prelude::TypeMap, CacheAndHttp};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use unicode_normalization::char::is_combining_mark;
+
+// the combining mark density isn't meaningful for a couple of characters
+const DENSITY_MINIMUM_LENGTH: usize = 10;
+const TAG_CANCEL: char = '\u{e007f}';
+
+pub struct UnicodeAbuse {}
+
+#[derive(Debug, Default)]
+struct CharacterCounts {
+    base: usize,
+    combining: usize,
+    max_stacked: usize,
+    invisible: usize,
+    bidi: usize,
+    newlines: usize,
+}
+
+#[async_trait]
+impl Matcher for UnicodeAbuse {
+    type SettingsType = UnicodeAbuseSettings;
+    async fn build(_: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        Ok((ModuleKind::UnicodeAbuse, Self {}))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+        let counts = CharacterCounts::count(&msg.content);
+        debug!("Message {} character counts: {:?}", msg.id, counts);
+
+        let density = if counts.base >= DENSITY_MINIMUM_LENGTH {
+            counts.combining * 100 / counts.base
+        } else {
+            0
+        };
+
+        let checks = [
+            (counts.max_stacked, settings.stacked_marks, "stacked combining marks"),
+            (
+                density,
+                settings.combining_density,
+                "combining marks per 100 characters",
+            ),
+            (counts.invisible, settings.invisible_characters, "invisible characters"),
+            (counts.bidi, settings.bidi_overrides, "text direction overrides"),
+            (counts.newlines, settings.newlines, "newlines"),
+        ];
+
+        // the check furthest past its threshold decides the score
+        Ok(checks
+            .iter()
+            .filter(|(count, threshold, _)| exceeds(*count, *threshold))
+            .map(|(count, threshold, what)| Match::over_limit(*count, *threshold, format!("has {} {}", count, what)))
+            .max_by_key(|matched| matched.score))
+    }
+}
+
+impl CharacterCounts {
+    fn count(content: &str) -> Self {
+        let mut counts = Self::default();
+        let mut stacked = 0;
+
+        let chars = content.chars().collect::<Vec<_>>();
+        for (idx, &c) in chars.iter().enumerate() {
+            if is_combining_mark(c) {
+                counts.combining += 1;
+                stacked += 1;
+                counts.max_stacked = counts.max_stacked.max(stacked);
+                continue;
+            }
+
+            stacked = 0;
+            if is_bidi_control(c) {
+                counts.bidi += 1;
+            } else if text::is_invisible(c) && !is_in_emoji_sequence(&chars, idx) {
+                counts.invisible += 1;
+            } else if c == '\n' {
+                counts.newlines += 1;
+            } else if !c.is_whitespace() {
+                counts.base += 1;
+            }
+        }
+
+        counts
+    }
+}
+
+// the embeddings, overrides and isolates that change the direction of the text after them. the plain directional
+// marks (LRM, RLM) only affect the characters next to them and are counted as invisible characters instead
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+// the zero-width joiner and tags are invisible, but they're also used in perfectly normal emoji such as family emoji
+// and subdivision flags. they're only exempt between the emoji they join, anywhere else they break up words just like
+// any other invisible character
+fn is_in_emoji_sequence(chars: &[char], idx: usize) -> bool {
+    let previous = idx.checked_sub(1).map(|idx| chars[idx]);
+    let next = chars.get(idx + 1).copied();
+
+    match chars[idx] {
+        // the joiner may follow the skin tone or presentation selector of the emoji before it
+        '\u{200d}' => {
+            previous.is_some_and(|c| text::is_emoji(c) || is_emoji_suffix(c)) && next.is_some_and(text::is_emoji)
+        }
+        // the tags spelling out a subdivision follow a flag and end with the cancel tag
+        c if is_tag(c) || c == TAG_CANCEL => {
+            let before = chars[..idx].iter().rev().find(|c| !is_tag(**c));
+            let after = chars[idx..].iter().find(|c| !is_tag(**c));
+            before.is_some_and(|c| text::is_emoji(*c)) && after == Some(&TAG_CANCEL)
+        }
+        _ => false,
+    }
+}
+
+fn is_tag(c: char) -> bool {
+    matches!(c, '\u{e0020}'..='\u{e007e}')
+}
+
+fn is_emoji_suffix(c: char) -> bool {
+    matches!(c, '\u{fe0f}' | '\u{1f3fb}'..='\u{1f3ff}')
+}
+
+// every threshold is inclusive and 0 disables its check
+fn exceeds(count: usize, threshold: usize) -> bool {
+    threshold > 0 && count >= threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emoji_sequences_are_not_invisible() {
+        // family, heart on fire and the flag of Scotland
+        let emoji = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467} \u{2764}\u{fe0f}\u{200d}\u{1f525} \
+                     \u{1f3f4}\u{e0067}\u{e0062}\u{e0073}\u{e0063}\u{e0074}\u{e007f}";
+        assert_eq!(CharacterCounts::count(emoji).invisible, 0);
+    }
+
+    #[test]
+    fn joiners_and_tags_outside_emoji_are_invisible() {
+        assert_eq!(CharacterCounts::count("fr\u{200d}ee ni\u{200d}tro").invisible, 2);
+        assert_eq!(CharacterCounts::count("\u{1f600}\u{200d}nitro").invisible, 1);
+        assert_eq!(CharacterCounts::count("free\u{e0067}\u{e007f}nitro").invisible, 2);
+    }
+}
@@ -0,0 +1,80 @@
+use super::{Match, Matcher, MessageEvent};
+use crate::module::{settings::WordFilterSettings, word_filter::CompiledFilter, ModuleKind};
+use log::*;
+use serenity::{
+    async_trait,
+    model::id::{ChannelId, GuildId},
+    prelude::TypeMap,
+    CacheAndHttp,
+};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::RwLock;
+
+pub struct WordFilter {
+    filters: HashMap<(GuildId, ChannelId), CachedFilter>,
+}
+
+// the settings are read from the database for every message, so the compiled filter is kept around as long as the
+// settings it was compiled from stay the same. the patterns can be overridden per channel, so the filter is kept per
+// channel like the custom rules
+struct CachedFilter {
+    source: String,
+    word_boundary: bool,
+    normalize: bool,
+    filter: CompiledFilter,
+}
+
+#[async_trait]
+impl Matcher for WordFilter {
+    type SettingsType = WordFilterSettings;
+    async fn build(_: Arc<RwLock<TypeMap>>, _: Arc<CacheAndHttp>) -> anyhow::Result<(ModuleKind, Self)> {
+        Ok((
+            ModuleKind::WordFilter,
+            Self {
+                filters: HashMap::new(),
+            },
+        ))
+    }
+
+    async fn is_match(&mut self, settings: Self::SettingsType, event: &MessageEvent) -> anyhow::Result<Option<Match>> {
+        let msg = &event.msg;
+        if settings.patterns.is_empty() || msg.content.is_empty() {
+            return Ok(None);
+        }
+
+        let key = (msg.guild_id.unwrap(), event.channel());
+        let source = settings.patterns.to_string();
+
+        let up_to_date = self
+            .filters
+            .get(&key)
+            .map_or(false, |cached| cached.is_compiled_from(&source, &settings));
+
+        if !up_to_date {
+            debug!("Compiling word filter for {} in {}", key.0, key.1);
+            let filter = settings.patterns.compile(settings.normalize, settings.word_boundary)?;
+            self.filters.insert(
+                key,
+                CachedFilter {
+                    source,
+                    word_boundary: settings.word_boundary,
+                    normalize: settings.normalize,
+                    filter,
+                },
+            );
+        }
+
+        let cached = &self.filters[&key];
+        if cached.filter.is_match(&msg.content) {
+            Ok(Some(Match::certain(String::from("contains a filtered word"))))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl CachedFilter {
+    fn is_compiled_from(&self, source: &str, settings: &WordFilterSettings) -> bool {
+        self.source == source && self.word_boundary == settings.word_boundary && self.normalize == settings.normalize
+    }
+}
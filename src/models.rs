@@ -1,4 +1,7 @@
-use super::schema::{actions, guild_settings, module_exclusions, module_settings, modules};
+use super::schema::{
+    actions, guild_settings, lockdown_channels, module_channel_settings, module_exclusions, module_settings, modules,
+    script_stores,
+};
 use crate::module::{action::ActionKind, ExclusionKind, ModuleKind};
 
 #[derive(Queryable, Insertable, AsChangeset, Debug)]
@@ -16,6 +19,7 @@ pub struct Action {
     pub action: ActionKind,
     pub in_channel: Option<i64>,
     pub message: Option<String>,
+    pub min_score: i16,
 }
 
 #[derive(Insertable, Debug)]
@@ -26,6 +30,7 @@ pub struct NewAction<'a> {
     pub action: ActionKind,
     pub in_channel: Option<i64>,
     pub message: Option<&'a str>,
+    pub min_score: i16,
 }
 
 #[derive(Queryable, Debug)]
@@ -45,6 +50,37 @@ pub struct NewModuleSetting<'a> {
     pub value: &'a str,
 }
 
+#[derive(Queryable, Debug, Clone)]
+pub struct ModuleChannelSetting {
+    pub guild: i64,
+    pub module: ModuleKind,
+    pub channel: i64,
+    pub setting: String,
+    pub value: String,
+}
+
+#[derive(Insertable, AsChangeset, Debug)]
+#[table_name = "module_channel_settings"]
+pub struct NewModuleChannelSetting<'a> {
+    pub guild: i64,
+    pub module: ModuleKind,
+    pub channel: i64,
+    pub setting: &'a str,
+    pub value: &'a str,
+}
+
+// channel overrides are merged on top of the guild's settings, so they're parsed as if they were guild settings
+impl From<ModuleChannelSetting> for ModuleSetting {
+    fn from(s: ModuleChannelSetting) -> Self {
+        Self {
+            guild: s.guild,
+            module: s.module,
+            setting: s.setting,
+            value: s.value,
+        }
+    }
+}
+
 #[derive(Queryable, Debug)]
 pub struct GuildSettings {
     pub guild: i64,
@@ -76,3 +112,19 @@ pub struct NewModuleExclusion {
     pub kind: ExclusionKind,
     pub id: i64,
 }
+
+#[derive(Queryable, Insertable, Debug)]
+#[table_name = "lockdown_channels"]
+pub struct LockdownChannel {
+    pub guild: i64,
+    pub channel: i64,
+    pub allow: Option<i64>,
+    pub deny: Option<i64>,
+}
+
+#[derive(Queryable, Insertable, Debug)]
+#[table_name = "script_stores"]
+pub struct ScriptStore {
+    pub guild: i64,
+    pub store: String,
+}
@@ -5,22 +5,31 @@ pub mod dbimport {
     pub use super::{action::Action_kind, Exclusion_kind, Module_kind};
 }
 pub mod exclusion;
+pub mod link_policy;
+pub mod rule;
+pub mod script;
+pub mod word_filter;
 
 use self::{
     action::{Action, ActionKind},
+    cache::ModuleCache,
     exclusion::{Exclusion, ModuleExclusion},
     settings::{ModuleSettings, Settings},
 };
 use crate::{
     error::{ArgumentError, InternalError},
+    ext::UserdataExt,
     models::{self, NewModuleExclusion},
-    schema, DbConn,
+    schema, DbConn, DbPool,
 };
 use diesel::prelude::*;
 use diesel_derive_enum::DbEnum;
 use log::*;
-use serenity::model::id::{ChannelId, GuildId};
-use std::{borrow::Cow, collections::HashMap};
+use serenity::{
+    model::id::{ChannelId, GuildId, RoleId, UserId},
+    prelude::TypeMap,
+};
+use std::{borrow::Cow, collections::HashMap, convert::TryInto};
 use strum::{Display, EnumIter, EnumString, EnumVariantNames, IntoEnumIterator};
 
 // the database schema holds its own version of this enum, remember to modify it as well if modying this one
@@ -36,6 +45,33 @@ pub enum ModuleKind {
     InviteLink,
     ChannelActivity,
     UserActivity,
+    WordFilter,
+    ScamLink,
+    LinkPolicy,
+    Caps,
+    UnicodeAbuse,
+    Attachments,
+    SecretLeak,
+    NewAccount,
+    GhostPing,
+    Raid,
+    NameFilter,
+    Impersonation,
+    ReactionSpam,
+    ThreadSpam,
+    CustomRule,
+    Script,
+}
+
+impl ModuleKind {
+    // the settings that apply to the whole guild and can't be overridden per channel
+    pub fn guild_only_settings(self) -> &'static [&'static str] {
+        match self {
+            // the script's store is shared by the whole guild, so every channel has to run the same script against it
+            ModuleKind::Script => &["script"],
+            _ => &[],
+        }
+    }
 }
 
 // the database schema holds its own version of this enum, remember to modify it as well if modying this one
@@ -82,6 +118,12 @@ impl Module {
             .collect())
     }
 
+    pub fn get_all_channel_overrides(db: &DbConn) -> anyhow::Result<Vec<models::ModuleChannelSetting>> {
+        use schema::module_channel_settings;
+
+        Ok(module_channel_settings::table.load::<models::ModuleChannelSetting>(db)?)
+    }
+
     pub fn get_all_modules_for_guild(guild: GuildId, db: &DbConn) -> anyhow::Result<HashMap<ModuleKind, Module>> {
         use schema::modules;
 
@@ -167,14 +209,32 @@ impl Module {
             )
             .load::<models::Action>(db)?
             .into_iter()
-            .map::<Result<Action, InternalError>, _>(|m| match m.action {
-                ActionKind::RemoveMessage => Ok(Action::remove_message()),
-                ActionKind::Notify => Ok(Action::notify(
-                    m.in_channel.map(|c| ChannelId(c as u64)),
-                    m.message
-                        .map(Cow::Owned)
-                        .ok_or(InternalError::MissingField("message"))?,
-                )),
+            .map::<Result<Action, InternalError>, _>(|m| {
+                let action = match m.action {
+                    ActionKind::RemoveMessage => Action::remove_message(),
+                    ActionKind::Notify => Action::notify(
+                        m.in_channel.map(|c| ChannelId(c as u64)),
+                        m.message
+                            .map(Cow::Owned)
+                            .ok_or(InternalError::MissingField("message"))?,
+                    ),
+                    ActionKind::DirectMessage => Action::direct_message(
+                        m.message
+                            .map(Cow::Owned)
+                            .ok_or(InternalError::MissingField("message"))?,
+                    ),
+                    ActionKind::Lockdown => {
+                        Action::lockdown(m.in_channel.map(|c| ChannelId(c as u64)), m.message.map(Cow::Owned))
+                    }
+                    ActionKind::Kick => Action::kick(m.message.map(Cow::Owned)),
+                    ActionKind::ResetNickname => Action::reset_nickname(),
+                };
+
+                let min_score = m
+                    .min_score
+                    .try_into()
+                    .map_err(|_| InternalError::InvalidField("min_score"))?;
+                Ok(action.with_min_score(min_score))
             })
             .collect::<Result<_, _>>()?;
 
@@ -186,19 +246,29 @@ impl Module {
         use schema::actions;
 
         let action_model = match action.kind {
-            ActionKind::RemoveMessage => models::NewAction {
+            ActionKind::RemoveMessage | ActionKind::ResetNickname => models::NewAction {
                 guild: self.guild.0 as i64,
                 action: action.kind,
                 module: self.kind,
                 in_channel: None,
                 message: None,
+                min_score: i16::from(action.min_score),
             },
-            ActionKind::Notify => models::NewAction {
+            ActionKind::Notify | ActionKind::Lockdown => models::NewAction {
                 guild: self.guild.0 as i64,
                 action: action.kind,
                 module: self.kind,
                 in_channel: action.channel.map(|c| c.0 as i64),
                 message: action.message.as_deref(),
+                min_score: i16::from(action.min_score),
+            },
+            ActionKind::DirectMessage | ActionKind::Kick => models::NewAction {
+                guild: self.guild.0 as i64,
+                action: action.kind,
+                module: self.kind,
+                in_channel: None,
+                message: action.message.as_deref(),
+                min_score: i16::from(action.min_score),
             },
         };
 
@@ -266,6 +336,14 @@ impl Module {
         Ok(settings)
     }
 
+    // the settings of a specific module, for the callers that know which module they're working with
+    pub fn get_settings_as<T>(self, db: &DbConn) -> anyhow::Result<T>
+    where
+        ModuleSettings: TryInto<T>,
+    {
+        Ok(convert_settings(self.get_settings(db)?)?)
+    }
+
     pub fn set_settings(self, settings: &ModuleSettings, db: &DbConn) -> anyhow::Result<()> {
         use diesel::pg::upsert::excluded;
         use schema::module_settings;
@@ -302,6 +380,109 @@ impl Module {
         Ok(())
     }
 
+    // the module's settings in the given channel: the guild's settings with the channel's overrides applied on top. the
+    // overrides are passed in since they're usually already cached in the module cache
+    pub fn get_channel_settings(
+        self,
+        channel: ChannelId,
+        overrides: Vec<models::ModuleChannelSetting>,
+        db: &DbConn,
+    ) -> anyhow::Result<ModuleSettings> {
+        use schema::module_settings;
+
+        let mut rows = module_settings::table
+            .filter(
+                module_settings::guild
+                    .eq(self.guild.0 as i64)
+                    .and(module_settings::module.eq(self.kind)),
+            )
+            .load::<models::ModuleSetting>(db)?;
+        // the rows are parsed in order so the overrides coming after the guild's settings replace them
+        rows.extend(overrides.into_iter().map(models::ModuleSetting::from));
+        let settings = ModuleSettings::from_db_rows(self.kind, &rows)?;
+
+        debug!("{:?} settings in {}: {:?}", self, channel, settings);
+        Ok(settings)
+    }
+
+    pub fn get_channel_overrides(
+        self,
+        channel: ChannelId,
+        db: &DbConn,
+    ) -> anyhow::Result<Vec<models::ModuleChannelSetting>> {
+        use schema::module_channel_settings;
+
+        let rows = module_channel_settings::table
+            .filter(
+                module_channel_settings::guild
+                    .eq(self.guild.0 as i64)
+                    .and(module_channel_settings::module.eq(self.kind))
+                    .and(module_channel_settings::channel.eq(channel.0 as i64)),
+            )
+            .load::<models::ModuleChannelSetting>(db)?;
+
+        debug!("{:?} overrides in {}: {:?}", self, channel, rows);
+        Ok(rows)
+    }
+
+    pub fn set_channel_override(
+        self,
+        channel: ChannelId,
+        setting: &str,
+        value: &str,
+        db: &DbConn,
+    ) -> anyhow::Result<()> {
+        use schema::module_channel_settings;
+
+        let row = models::NewModuleChannelSetting {
+            guild: self.guild.0 as i64,
+            module: self.kind,
+            channel: channel.0 as i64,
+            setting,
+            value,
+        };
+
+        // return the inserted row's guild ID but don't store it anywhere, because this way diesel will error if the
+        // insert affected no rows
+        diesel::insert_into(module_channel_settings::table)
+            .values(&row)
+            .on_conflict((
+                module_channel_settings::guild,
+                module_channel_settings::module,
+                module_channel_settings::channel,
+                module_channel_settings::setting,
+            ))
+            .do_update()
+            .set(module_channel_settings::value.eq(value))
+            .returning(module_channel_settings::guild)
+            .get_result::<i64>(db)?;
+
+        debug!("{:?}: insert channel override {:?}", self, row);
+        Ok(())
+    }
+
+    // returns whether there was an override to remove
+    pub fn remove_channel_override(self, channel: ChannelId, setting: &str, db: &DbConn) -> anyhow::Result<bool> {
+        use schema::module_channel_settings;
+
+        let removed = diesel::delete(
+            module_channel_settings::table.filter(
+                module_channel_settings::guild
+                    .eq(self.guild.0 as i64)
+                    .and(module_channel_settings::module.eq(self.kind))
+                    .and(module_channel_settings::channel.eq(channel.0 as i64))
+                    .and(module_channel_settings::setting.eq(setting)),
+            ),
+        )
+        .execute(db)?;
+
+        debug!(
+            "{:?}: delete channel override {} in {}: {} rows",
+            self, setting, channel, removed
+        );
+        Ok(removed > 0)
+    }
+
     pub fn get_exclusions(self, db: &DbConn) -> anyhow::Result<ModuleExclusion> {
         use schema::module_exclusions;
 
@@ -318,6 +499,49 @@ impl Module {
         Ok(exclusions)
     }
 
+    // the guild's settings for matching an event by the given user, or None if the module is disabled or the user is
+    // excluded from it. events in a channel get the channel's overrides applied, and users without known roles (such
+    // as webhooks) can't be excluded
+    pub async fn load_for_matching<T>(
+        data: &TypeMap,
+        guild: GuildId,
+        kind: ModuleKind,
+        channel: Option<ChannelId>,
+        user: UserId,
+        roles: Option<&[RoleId]>,
+    ) -> anyhow::Result<Option<T>>
+    where
+        ModuleSettings: TryInto<T>,
+    {
+        let module_cache = data.get_userdata::<ModuleCache>()?;
+        let module = module_cache.get(guild, kind).await;
+
+        if !module.is_enabled() {
+            debug!("{} in {}: module disabled, not matching", kind, guild);
+            return Ok(None);
+        }
+
+        let overrides = match channel {
+            Some(channel) => module_cache.get_channel_overrides(module, channel).await,
+            None => Vec::new(),
+        };
+
+        let db = data.get_userdata::<DbPool>()?.get()?;
+        let settings = match channel {
+            Some(channel) => module.get_channel_settings(channel, overrides, &db)?,
+            None => module.get_settings(&db)?,
+        };
+
+        if let Some(roles) = roles {
+            if module.get_exclusions(&db)?.should_exclude(user, roles) {
+                debug!("{} in {}: user {} is excluded", kind, guild, user);
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(convert_settings(settings)?))
+    }
+
     pub fn add_exclusion(self, excl: Exclusion, db: &DbConn) -> anyhow::Result<()> {
         use schema::module_exclusions;
 
@@ -369,3 +593,14 @@ impl Module {
         Ok(())
     }
 }
+
+fn convert_settings<T>(settings: ModuleSettings) -> Result<T, InternalError>
+where
+    ModuleSettings: TryInto<T>,
+{
+    // the author of enum_dispatch is an idiot so their TryInto impl returns a 'static &str as an error, which is
+    // everything but (it doesn't impl Error)
+    settings
+        .try_into()
+        .map_err(|_| InternalError::ConversionFailed("tried to convert ModuleSettings variant to invalid type"))
+}
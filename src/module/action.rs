@@ -1,15 +1,30 @@
-use crate::error::{ArgumentError, InternalError};
+use crate::{
+    error::{ArgumentError, InternalError},
+    lockdown::{self, SkipReason},
+    matcher::Match,
+    DbConnPool,
+};
+use chrono::Utc;
 use diesel_derive_enum::DbEnum;
 use dynfmt::{Format, SimpleCurlyFormat};
 use erased_serde::Serialize;
+use log::*;
 use serenity::{
-    model::{channel::Message, id::ChannelId},
+    model::{
+        channel::Message,
+        guild::Member,
+        id::{ChannelId, GuildId},
+        user::User,
+    },
     prelude::*,
     CacheAndHttp,
 };
-use std::{borrow::Cow, collections::HashMap};
+use std::{borrow::Cow, collections::HashMap, fmt::Display, sync::Arc};
 use strum::{Display, EnumMessage, EnumString};
 
+// the name a thread is given once its original name is removed
+const REMOVED_THREAD_NAME: &str = "Removed thread name";
+
 // the database schema holds its own version of this enum, remember to modify it as well if modying this one
 #[derive(Debug, EnumString, EnumMessage, Display, Copy, Clone, DbEnum)]
 #[strum(serialize_all = "kebab-case")]
@@ -21,6 +36,37 @@ pub enum ActionKind {
     /// Notify about the message in a certain channel
     #[strum(message = "Notify about the message")]
     Notify,
+    /// Send the user a direct message about their message
+    #[strum(message = "Direct message the user")]
+    DirectMessage,
+    /// Lock down the channel the message was sent in, or a certain channel
+    #[strum(message = "Lock down a channel")]
+    Lockdown,
+    /// Kick the user from the guild
+    #[strum(message = "Kick the user")]
+    Kick,
+    /// Remove the member's nickname
+    #[strum(message = "Reset the user's nickname")]
+    ResetNickname,
+}
+
+// what a module matched: a message for message matchers and a member for member matchers. not every action makes sense
+// for both, e.g. a member has no message to remove. a new thread is matched as a message in the thread with the
+// thread's name as its content, and removing it either removes the whole thread or only its name. a message carries
+// the channel it's considered to be in, which for messages in threads is the thread's parent channel
+#[derive(Debug, Clone)]
+pub enum ActionTarget {
+    Message(Arc<Message>, ChannelId),
+    Member(Arc<Member>),
+    Thread(Arc<Message>, ThreadRemoval),
+}
+
+// only thread spam removes the whole thread. a thread whose name matched some other module may well have replies that
+// have nothing wrong with them, so only its name is removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadRemoval {
+    Delete,
+    Rename,
 }
 
 #[derive(Debug)]
@@ -28,6 +74,8 @@ pub struct Action<'a> {
     pub kind: ActionKind,
     pub channel: Option<ChannelId>,
     pub message: Option<Cow<'a, str>>,
+    // the action is only run for matches scoring at least this much
+    pub min_score: u8,
 }
 
 impl<'a> Action<'a> {
@@ -36,6 +84,7 @@ impl<'a> Action<'a> {
             kind: ActionKind::RemoveMessage,
             channel: None,
             message: None,
+            min_score: 0,
         }
     }
 
@@ -44,9 +93,50 @@ impl<'a> Action<'a> {
             kind: ActionKind::Notify,
             channel,
             message: Some(message),
+            min_score: 0,
+        }
+    }
+
+    pub fn direct_message(message: Cow<'a, str>) -> Self {
+        Self {
+            kind: ActionKind::DirectMessage,
+            channel: None,
+            message: Some(message),
+            min_score: 0,
+        }
+    }
+
+    pub fn lockdown(channel: Option<ChannelId>, reason: Option<Cow<'a, str>>) -> Self {
+        Self {
+            kind: ActionKind::Lockdown,
+            channel,
+            message: reason,
+            min_score: 0,
         }
     }
 
+    pub fn kick(reason: Option<Cow<'a, str>>) -> Self {
+        Self {
+            kind: ActionKind::Kick,
+            channel: None,
+            message: reason,
+            min_score: 0,
+        }
+    }
+
+    pub fn reset_nickname() -> Self {
+        Self {
+            kind: ActionKind::ResetNickname,
+            channel: None,
+            message: None,
+            min_score: 0,
+        }
+    }
+
+    pub fn with_min_score(self, min_score: u8) -> Self {
+        Self { min_score, ..self }
+    }
+
     pub fn friendly_name(&self) -> &str {
         self.kind
             .get_message()
@@ -54,7 +144,7 @@ impl<'a> Action<'a> {
     }
 
     pub fn description(&self) -> String {
-        match self.kind {
+        let description = match self.kind {
             ActionKind::RemoveMessage => {
                 // Discord requires the embed field to always have *some* value but they don't document the requirement
                 // anywhere. omitting the value has Discord respond with a very unhelpful error message that Serenity
@@ -66,51 +156,195 @@ impl<'a> Action<'a> {
                 (Some(msg), None) => format!("In the same channel with `{}`", msg),
                 (Some(msg), Some(channel)) => format!("In <#{}> with `{}`", channel, msg),
             },
+            ActionKind::DirectMessage => match &self.message {
+                None => panic!("invalid action: kind is {} but message is None", self.kind),
+                Some(msg) => format!("With `{}`", msg),
+            },
+            ActionKind::Lockdown => {
+                let channel = match self.channel {
+                    Some(channel) => format!("<#{}>", channel),
+                    None => String::from("The same channel"),
+                };
+
+                match &self.message {
+                    Some(reason) => format!("{} with the reason `{}`", channel, reason),
+                    None => channel,
+                }
+            }
+            ActionKind::Kick => match &self.message {
+                Some(reason) => format!("With the reason `{}`", reason),
+                None => String::from("Kick the user, nothing special about it"),
+            },
+            ActionKind::ResetNickname => String::from("Reset the nickname, nothing special about it"),
+        };
+
+        if self.min_score > 0 {
+            format!("{}, when the match scores at least {}", description, self.min_score)
+        } else {
+            description
         }
     }
 
-    pub async fn run(self, cache_http: &CacheAndHttp, msg: &Message) -> anyhow::Result<()> {
-        match self.kind {
-            ActionKind::RemoveMessage => {
+    pub async fn run(
+        self,
+        cache_http: &CacheAndHttp,
+        db_pool: &DbConnPool,
+        target: &ActionTarget,
+        matched: &Match,
+    ) -> anyhow::Result<()> {
+        let guild = target.guild_id().ok_or(InternalError::MissingGuildID)?;
+
+        match (self.kind, target) {
+            (ActionKind::RemoveMessage, ActionTarget::Message(msg, _)) => {
                 msg.delete(cache_http).await?;
             }
-            ActionKind::Notify => {
-                let message = self
-                    .message
-                    .ok_or_else(|| InternalError::ImpossibleCase(String::from("missing message in action")))?;
-                let formatted = SimpleCurlyFormat
-                    .format(message.as_ref(), build_format_args(msg))
-                    .map_err(|e| ArgumentError::InvalidNotifyFormat(e.to_string()))?;
+            (ActionKind::RemoveMessage, ActionTarget::Thread(thread, ThreadRemoval::Delete)) => {
+                thread.channel_id.delete(&cache_http.http).await?;
+            }
+            (ActionKind::RemoveMessage, ActionTarget::Thread(thread, ThreadRemoval::Rename)) => {
+                thread
+                    .channel_id
+                    .edit(&cache_http.http, |c| c.name(REMOVED_THREAD_NAME))
+                    .await?;
+            }
+            (ActionKind::Notify, _) => {
+                let formatted = format_message(self.message, target, matched)?;
 
                 // messages can only be replied to if they're in the same channel. in case the target channel is
-                // specified, don't reply to the offending message
-                let (channel, reply) = match self.channel {
-                    Some(notify_channel) => (notify_channel, false),
-                    None => (msg.channel_id, true),
+                // specified, don't reply to the offending message. members aren't in any channel so they always need
+                // the target channel, and a thread's name isn't a message that could be replied to
+                let (channel, reply) = match (self.channel, target) {
+                    (Some(notify_channel), _) => (notify_channel, None),
+                    (None, ActionTarget::Message(msg, _)) => (msg.channel_id, Some(msg)),
+                    (None, ActionTarget::Thread(thread, _)) => (thread.channel_id, None),
+                    (None, ActionTarget::Member(_)) => return Err(InternalError::ActionNeedsChannel(self.kind).into()),
                 };
 
                 channel
                     .send_message(&cache_http.http, |m| {
                         m.content(formatted);
-                        if reply {
-                            m.reference_message(msg)
+                        if let Some(msg) = reply {
+                            m.reference_message(msg.as_ref())
                         } else {
                             m
                         }
                     })
                     .await?;
             }
+            (ActionKind::DirectMessage, _) => {
+                let formatted = format_message(self.message, target, matched)?;
+                let user = target.user();
+
+                // users can have their DMs closed, which isn't really an error on the bot's side
+                if let Err(e) = user.direct_message(&cache_http.http, |m| m.content(formatted)).await {
+                    warn!("Failed to direct message {}: {}", user.id, e);
+                }
+            }
+            (ActionKind::Lockdown, _) => {
+                let channel = match (self.channel, target) {
+                    (Some(channel), _) => channel,
+                    // threads can't be locked down on their own, so a message in a thread locks down the thread's
+                    // parent
+                    (None, ActionTarget::Message(_, channel)) => *channel,
+                    (None, ActionTarget::Member(_) | ActionTarget::Thread(..)) => {
+                        return Err(InternalError::ActionNeedsChannel(self.kind).into())
+                    }
+                };
+                let reason = match self.message {
+                    Some(reason) => Some(format_message(Some(reason), target, matched)?),
+                    None => None,
+                };
+
+                let result =
+                    lockdown::start(&cache_http.http, db_pool, guild, Some(&[channel]), reason.as_deref()).await?;
+                // the channel might not be lockable at all, e.g. a voice channel, or locking it failed. one that's
+                // already locked down is fine
+                match result.skipped.first() {
+                    Some((_, SkipReason::AlreadyLocked)) | None => (),
+                    Some((_, reason)) => return Err(InternalError::ChannelNotLocked(channel, *reason).into()),
+                }
+            }
+            (ActionKind::Kick, _) => {
+                let user = target.user().id;
+                match self.message {
+                    Some(reason) => {
+                        let reason = format_message(Some(reason), target, matched)?;
+                        guild.kick_with_reason(&cache_http.http, user, &reason).await?;
+                    }
+                    None => guild.kick(&cache_http.http, user).await?,
+                }
+            }
+            (ActionKind::ResetNickname, _) => {
+                guild
+                    .edit_member(&cache_http.http, target.user().id, |m| m.nickname(""))
+                    .await?;
+            }
+            (kind, ActionTarget::Member(_)) => return Err(InternalError::ActionNeedsMessage(kind).into()),
         }
 
         Ok(())
     }
 }
 
-fn build_format_args<'a>(msg: &'a Message) -> HashMap<&'static str, Box<dyn Serialize + 'a>> {
+impl ActionTarget {
+    pub fn guild_id(&self) -> Option<GuildId> {
+        match self {
+            ActionTarget::Message(msg, _) | ActionTarget::Thread(msg, _) => msg.guild_id,
+            ActionTarget::Member(member) => Some(member.guild_id),
+        }
+    }
+
+    pub fn user(&self) -> &User {
+        match self {
+            ActionTarget::Message(msg, _) | ActionTarget::Thread(msg, _) => &msg.author,
+            ActionTarget::Member(member) => &member.user,
+        }
+    }
+}
+
+impl Display for ActionTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActionTarget::Message(msg, _) => write!(
+                f,
+                "message {} in channel {} by {}",
+                msg.id, msg.channel_id, msg.author.id
+            ),
+            ActionTarget::Member(member) => write!(f, "member {}", member.user.id),
+            ActionTarget::Thread(thread, _) => write!(f, "thread {} by {}", thread.channel_id, thread.author.id),
+        }
+    }
+}
+
+fn format_message(message: Option<Cow<'_, str>>, target: &ActionTarget, matched: &Match) -> anyhow::Result<String> {
+    let message = message.ok_or_else(|| InternalError::ImpossibleCase(String::from("missing message in action")))?;
+    let formatted = SimpleCurlyFormat
+        .format(message.as_ref(), build_format_args(target, matched))
+        .map_err(|e| ArgumentError::InvalidNotifyFormat(e.to_string()))?;
+    Ok(formatted.into_owned())
+}
+
+// members aren't tied to any channel or message, so formats using {channel} or {link} only work for message matches.
+// a thread has a channel but its name isn't a message that could be linked to. every match has a score and a reason
+fn build_format_args<'a>(target: &'a ActionTarget, matched: &Match) -> HashMap<&'static str, Box<dyn Serialize + 'a>> {
     let mut args: HashMap<&'static str, Box<dyn Serialize>> = HashMap::new();
-    args.insert("user", Box::new(msg.author.mention().to_string()));
-    args.insert("channel", Box::new(msg.channel_id.mention().to_string()));
-    args.insert("timestamp", Box::new(msg.timestamp));
-    args.insert("link", Box::new(msg.link()));
+    args.insert("user", Box::new(target.user().mention().to_string()));
+    args.insert("score", Box::new(matched.score));
+    args.insert("reason", Box::new(matched.reason.clone()));
+
+    match target {
+        ActionTarget::Message(msg, _) => {
+            args.insert("channel", Box::new(msg.channel_id.mention().to_string()));
+            args.insert("timestamp", Box::new(msg.timestamp));
+            args.insert("link", Box::new(msg.link()));
+        }
+        ActionTarget::Member(_) => {
+            args.insert("timestamp", Box::new(Utc::now()));
+        }
+        ActionTarget::Thread(thread, _) => {
+            args.insert("channel", Box::new(thread.channel_id.mention().to_string()));
+            args.insert("timestamp", Box::new(thread.timestamp));
+        }
+    }
     args
 }
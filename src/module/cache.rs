@@ -1,16 +1,23 @@
 use super::{Module, ModuleKind};
-use crate::DbConn;
+use crate::{models::ModuleChannelSetting, DbConn};
 use log::*;
-use serenity::{model::id::GuildId, prelude::TypeMapKey};
+use serenity::{
+    model::id::{ChannelId, GuildId},
+    prelude::TypeMapKey,
+};
 use std::{
     collections::{hash_map::Entry, HashMap},
     sync::Arc,
 };
 use tokio::sync::RwLock;
 
+type ChannelOverrides = HashMap<(GuildId, ModuleKind, ChannelId), Vec<ModuleChannelSetting>>;
+
 #[derive(Debug, Clone)]
 pub struct ModuleCache {
     guilds: Arc<RwLock<HashMap<GuildId, HashMap<ModuleKind, Module>>>>,
+    // every message needs the overrides of the channel it was sent in, only channels with any overrides have an entry
+    channel_overrides: Arc<RwLock<ChannelOverrides>>,
 }
 
 impl TypeMapKey for ModuleCache {
@@ -36,14 +43,24 @@ impl ModuleCache {
             }
         }
 
+        let mut channel_overrides: ChannelOverrides = HashMap::new();
+        for row in Module::get_all_channel_overrides(db)? {
+            channel_overrides
+                .entry((GuildId(row.guild as u64), row.module, ChannelId(row.channel as u64)))
+                .or_default()
+                .push(row);
+        }
+
         info!(
-            "Module cache populated. {} modules in total across {} guilds",
+            "Module cache populated. {} modules in total across {} guilds, channel overrides in {} channels",
             module_count,
-            guilds.len()
+            guilds.len(),
+            channel_overrides.len()
         );
 
         Ok(Self {
             guilds: Arc::new(RwLock::new(guilds)),
+            channel_overrides: Arc::new(RwLock::new(channel_overrides)),
         })
     }
 
@@ -74,4 +91,29 @@ impl ModuleCache {
 
         Module::default_with_kind_and_guild(kind, guild)
     }
+
+    pub async fn update_channel_overrides(
+        &self,
+        module: Module,
+        channel: ChannelId,
+        overrides: Vec<ModuleChannelSetting>,
+    ) -> anyhow::Result<()> {
+        let mut channel_overrides = self.channel_overrides.write().await;
+        let key = (module.guild(), module.kind(), channel);
+        if overrides.is_empty() {
+            channel_overrides.remove(&key);
+        } else {
+            channel_overrides.insert(key, overrides);
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_channel_overrides(&self, module: Module, channel: ChannelId) -> Vec<ModuleChannelSetting> {
+        let channel_overrides = self.channel_overrides.read().await;
+        channel_overrides
+            .get(&(module.guild(), module.kind(), channel))
+            .cloned()
+            .unwrap_or_default()
+    }
 }
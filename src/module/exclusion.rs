@@ -1,10 +1,6 @@
 use crate::{models, module::ExclusionKind};
 use log::*;
-use serenity::model::{
-    guild::PartialMember,
-    id::{RoleId, UserId},
-    prelude::*,
-};
+use serenity::model::id::{RoleId, UserId};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Exclusion {
@@ -48,18 +44,18 @@ impl ModuleExclusion {
 
     // when a message arrives, the user info is separate from the member info so to avoid a cache/HTTP hit, separate
     // them here
-    pub fn should_exclude(&self, user: &User, member: &PartialMember) -> bool {
+    pub fn should_exclude(&self, user: UserId, roles: &[RoleId]) -> bool {
         for excl in &self.exclusions {
             match excl {
                 Exclusion::User(id) => {
-                    if user.id == *id {
+                    if user == *id {
                         debug!("Matched user exclusion: {}", id);
                         return true;
                     }
                 }
                 Exclusion::Role(id) => {
-                    if member.roles.contains(id) {
-                        debug!("Matched role exclusion: {} in {:?}", id, member.roles);
+                    if roles.contains(id) {
+                        debug!("Matched role exclusion: {} in {:?}", id, roles);
                         return true;
                     }
                 }
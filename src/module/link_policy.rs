@@ -0,0 +1,57 @@
+use crate::error::ArgumentError;
+use std::{fmt::Display, str::FromStr};
+
+const WILDCARD_PREFIX: &str = "*.";
+
+// a domain in a link policy. a plain domain matches only itself, while *.example.com matches example.com and every
+// one of its subdomains
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainPattern {
+    domain: String,
+    subdomains: bool,
+}
+
+impl FromStr for DomainPattern {
+    type Err = ArgumentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let (domain, subdomains) = match s.strip_prefix(WILDCARD_PREFIX) {
+            Some(domain) => (domain, true),
+            None => (s.as_str(), false),
+        };
+        // the hosts the pattern is compared against are stripped of a leading www. so the pattern has to be as well,
+        // the same way the settings' domains are
+        let domain = domain.strip_prefix("www.").unwrap_or(domain);
+
+        if domain.is_empty() || domain.contains(|c: char| c.is_whitespace() || c == '/' || c == '*') {
+            return Err(ArgumentError::InvalidDomainPattern(s.clone()));
+        }
+
+        Ok(Self {
+            domain: String::from(domain),
+            subdomains,
+        })
+    }
+}
+
+impl Display for DomainPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.subdomains {
+            write!(f, "{}{}", WILDCARD_PREFIX, self.domain)
+        } else {
+            write!(f, "{}", self.domain)
+        }
+    }
+}
+
+impl DomainPattern {
+    // the host is expected to be normalized, i.e. lowercase and without a leading www.
+    pub fn matches(&self, host: &str) -> bool {
+        host == self.domain
+            || (self.subdomains
+                && host
+                    .strip_suffix(&self.domain)
+                    .is_some_and(|prefix| prefix.ends_with('.')))
+    }
+}
@@ -0,0 +1,803 @@
+use super::settings::ValidateSetting;
+use crate::error::ArgumentError;
+use chrono::{Duration, Utc};
+use regex::{Regex, RegexBuilder};
+use serenity::model::channel::Message;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt::Display,
+    iter::Peekable,
+    str::{Chars, FromStr},
+};
+use strum::{Display, EnumString};
+
+// a rule is evaluated against every message in the guild, so its size is limited to keep the evaluation cheap: every
+// node in the expression is visited at most once, and regexes match in time linear to the message's length
+const MAX_RULE_LENGTH: usize = 500;
+const MAX_NODES: usize = 32;
+const MAX_DEPTH: usize = 8;
+// limits the compiled size of a single regex, like the word filter's limit
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+const DURATION_UNITS: [(char, i64); 5] = [
+    ('s', 1),
+    ('m', 60),
+    ('h', 60 * 60),
+    ('d', 24 * 60 * 60),
+    ('w', 7 * 24 * 60 * 60),
+];
+
+// a rule such as `content matches /free nitro/ AND author.account_age < 1d AND attachments > 0`. comparisons are
+// combined with AND, OR and NOT, in that order of precedence from lowest to highest, and grouped with parentheses
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule(Expr);
+
+// the rules are stored one per line, like the word filter's patterns
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rules(Vec<Rule>);
+
+#[derive(Debug)]
+pub struct CompiledRules {
+    rules: Vec<Rule>,
+    regexes: HashMap<String, Regex>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, Op, Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
+enum Field {
+    #[strum(serialize = "content")]
+    Content,
+    #[strum(serialize = "content.length")]
+    ContentLength,
+    #[strum(serialize = "content.lines")]
+    ContentLines,
+    #[strum(serialize = "author.name")]
+    AuthorName,
+    #[strum(serialize = "author.nick")]
+    AuthorNick,
+    #[strum(serialize = "author.account_age")]
+    AccountAge,
+    #[strum(serialize = "author.member_age")]
+    MemberAge,
+    #[strum(serialize = "attachments")]
+    Attachments,
+    #[strum(serialize = "embeds")]
+    Embeds,
+    #[strum(serialize = "stickers")]
+    Stickers,
+    #[strum(serialize = "mentions")]
+    Mentions,
+    #[strum(serialize = "role_mentions")]
+    RoleMentions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldKind {
+    Text,
+    Number,
+    Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+enum Op {
+    #[strum(serialize = "==")]
+    Eq,
+    #[strum(serialize = "!=")]
+    Ne,
+    #[strum(serialize = "<")]
+    Lt,
+    #[strum(serialize = "<=")]
+    Le,
+    #[strum(serialize = ">")]
+    Gt,
+    #[strum(serialize = ">=")]
+    Ge,
+    #[strum(serialize = "contains")]
+    Contains,
+    #[strum(serialize = "matches")]
+    Matches,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    Regex(String),
+    Number(u64),
+    // in seconds
+    Duration(i64),
+}
+
+enum FieldValue<'a> {
+    Text(&'a str),
+    Number(u64),
+    Duration(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    And,
+    Or,
+    Not,
+    Op(Op),
+    LeftParen,
+    RightParen,
+    Value(Value),
+}
+
+struct Parser {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+    nodes: usize,
+}
+
+impl FromStr for Rule {
+    type Err = ArgumentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(invalid("the rule is empty"));
+        } else if s.chars().count() > MAX_RULE_LENGTH {
+            return Err(invalid(format!(
+                "the rule is longer than {} characters",
+                MAX_RULE_LENGTH
+            )));
+        }
+
+        let mut parser = Parser {
+            tokens: tokenize(s)?.into_iter().peekable(),
+            nodes: 0,
+        };
+        let expr = parser.parse_or(0)?;
+
+        match parser.tokens.next() {
+            Some(token) => Err(invalid(format!("unexpected {} after the end of the rule", token))),
+            None => Ok(Self(expr)),
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Rules {
+    type Err = ArgumentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            s.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(Rule::from_str)
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+}
+
+impl ValidateSetting for Rules {
+    // the rules can be set as a whole through the generic settings command too, so rules with invalid regexes are
+    // rejected when they're set
+    fn validate(&self) -> anyhow::Result<()> {
+        self.compile()?;
+        Ok(())
+    }
+}
+
+impl Display for Rules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, rule) in self.0.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+impl Rules {
+    pub fn iter(&self) -> impl Iterator<Item = &Rule> + '_ {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, rule: &Rule) -> bool {
+        self.0.contains(rule)
+    }
+
+    pub fn push(&mut self, rule: Rule) {
+        self.0.push(rule);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Rule> {
+        if index < self.0.len() {
+            Some(self.0.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn compile(&self) -> Result<CompiledRules, ArgumentError> {
+        let mut regexes = HashMap::new();
+        for rule in &self.0 {
+            rule.0.compile_regexes(&mut regexes)?;
+        }
+
+        Ok(CompiledRules {
+            rules: self.0.clone(),
+            regexes,
+        })
+    }
+}
+
+impl CompiledRules {
+    // the index of the first rule that matches the message
+    pub fn find_match(&self, msg: &Message) -> Option<usize> {
+        self.rules.iter().position(|rule| rule.0.eval(msg, &self.regexes))
+    }
+}
+
+impl Expr {
+    fn eval(&self, msg: &Message, regexes: &HashMap<String, Regex>) -> bool {
+        match self {
+            Expr::Or(left, right) => left.eval(msg, regexes) || right.eval(msg, regexes),
+            Expr::And(left, right) => left.eval(msg, regexes) && right.eval(msg, regexes),
+            Expr::Not(expr) => !expr.eval(msg, regexes),
+            Expr::Compare(field, op, value) => {
+                // fields that aren't available, e.g. the nickname of a member who doesn't have one, never match
+                let field = match field.value(msg) {
+                    Some(field) => field,
+                    None => return false,
+                };
+
+                match (field, value) {
+                    (FieldValue::Text(text), Value::Regex(regex)) => {
+                        regexes.get(regex).is_some_and(|regex| regex.is_match(text))
+                    }
+                    (FieldValue::Text(text), Value::Text(value)) => {
+                        let (text, value) = (text.to_lowercase(), value.to_lowercase());
+                        match op {
+                            Op::Eq => text == value,
+                            Op::Ne => text != value,
+                            Op::Contains => text.contains(&value),
+                            _ => false,
+                        }
+                    }
+                    (FieldValue::Number(number), Value::Number(value)) => op.compare(&number, value),
+                    (FieldValue::Duration(duration), Value::Duration(value)) => {
+                        op.compare(&duration, &Duration::seconds(*value))
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    fn compile_regexes(&self, regexes: &mut HashMap<String, Regex>) -> Result<(), ArgumentError> {
+        match self {
+            Expr::Or(left, right) | Expr::And(left, right) => {
+                left.compile_regexes(regexes)?;
+                right.compile_regexes(regexes)
+            }
+            Expr::Not(expr) => expr.compile_regexes(regexes),
+            Expr::Compare(_, _, Value::Regex(source)) if !regexes.contains_key(source) => {
+                let regex = RegexBuilder::new(source)
+                    .case_insensitive(true)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .build()
+                    .map_err(|e| invalid(e.to_string()))?;
+                regexes.insert(source.clone(), regex);
+                Ok(())
+            }
+            Expr::Compare(..) => Ok(()),
+        }
+    }
+
+    // OR binds the loosest and NOT the tightest, so parentheses are only needed around an expression that binds looser
+    // than the one it's in
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(..) => 0,
+            Expr::And(..) => 1,
+            Expr::Not(_) => 2,
+            Expr::Compare(..) => 3,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut std::fmt::Formatter<'_>, parent: u8) -> std::fmt::Result {
+        if self.precedence() < parent {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let precedence = self.precedence();
+        match self {
+            Expr::Or(left, right) | Expr::And(left, right) => {
+                left.fmt_operand(f, precedence)?;
+                write!(f, " {} ", if precedence == 0 { "OR" } else { "AND" })?;
+                // the operators are left-associative, so a right operand of the same kind was parenthesized
+                right.fmt_operand(f, precedence + 1)
+            }
+            Expr::Not(expr) => {
+                write!(f, "NOT ")?;
+                expr.fmt_operand(f, precedence)
+            }
+            Expr::Compare(field, op, value) => write!(f, "{} {} {}", field, op, value),
+        }
+    }
+}
+
+impl Field {
+    fn kind(self) -> FieldKind {
+        match self {
+            Field::Content | Field::AuthorName | Field::AuthorNick => FieldKind::Text,
+            Field::AccountAge | Field::MemberAge => FieldKind::Duration,
+            Field::ContentLength
+            | Field::ContentLines
+            | Field::Attachments
+            | Field::Embeds
+            | Field::Stickers
+            | Field::Mentions
+            | Field::RoleMentions => FieldKind::Number,
+        }
+    }
+
+    fn value(self, msg: &Message) -> Option<FieldValue<'_>> {
+        let value = match self {
+            Field::Content => FieldValue::Text(&msg.content),
+            Field::ContentLength => FieldValue::Number(msg.content.chars().count() as u64),
+            Field::ContentLines => FieldValue::Number(msg.content.lines().count() as u64),
+            Field::AuthorName => FieldValue::Text(&msg.author.name),
+            Field::AuthorNick => FieldValue::Text(msg.member.as_ref()?.nick.as_deref()?),
+            Field::AccountAge => FieldValue::Duration(Utc::now() - msg.author.id.created_at()),
+            Field::MemberAge => FieldValue::Duration(Utc::now() - msg.member.as_ref()?.joined_at?),
+            Field::Attachments => FieldValue::Number(msg.attachments.len() as u64),
+            Field::Embeds => FieldValue::Number(msg.embeds.len() as u64),
+            Field::Stickers => FieldValue::Number(msg.stickers.len() as u64),
+            Field::Mentions => FieldValue::Number(msg.mentions.len() as u64),
+            Field::RoleMentions => FieldValue::Number(msg.mention_roles.len() as u64),
+        };
+        Some(value)
+    }
+}
+
+impl Op {
+    fn compare<T: PartialOrd>(self, left: &T, right: &T) -> bool {
+        match self {
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+            Op::Contains | Op::Matches => false,
+        }
+    }
+
+    fn is_ordering(self) -> bool {
+        !matches!(self, Op::Contains | Op::Matches)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Text(text) => write!(f, "\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+            Value::Regex(regex) => write!(f, "/{}/", regex.replace('/', "\\/")),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Duration(seconds) => {
+                // the largest unit the duration is a whole multiple of, so e.g. 86400 seconds is shown as 1d
+                let (amount, unit) = DURATION_UNITS
+                    .iter()
+                    .rev()
+                    .find(|(_, unit_seconds)| seconds % unit_seconds == 0)
+                    .map_or((*seconds, 's'), |(unit, unit_seconds)| (seconds / unit_seconds, *unit));
+                write!(f, "{}{}", amount, unit)
+            }
+        }
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "`{}`", ident),
+            Token::And => write!(f, "AND"),
+            Token::Or => write!(f, "OR"),
+            Token::Not => write!(f, "NOT"),
+            Token::Op(op) => write!(f, "`{}`", op),
+            Token::LeftParen => write!(f, "`(`"),
+            Token::RightParen => write!(f, "`)`"),
+            Token::Value(value) => write!(f, "`{}`", value),
+        }
+    }
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<Token, ArgumentError> {
+        self.tokens.next().ok_or_else(|| invalid("the rule ends unexpectedly"))
+    }
+
+    fn node(&mut self, expr: Expr) -> Result<Expr, ArgumentError> {
+        self.nodes += 1;
+        if self.nodes > MAX_NODES {
+            Err(invalid(format!(
+                "the rule has more than {} comparisons and operators",
+                MAX_NODES
+            )))
+        } else {
+            Ok(expr)
+        }
+    }
+
+    fn parse_or(&mut self, depth: usize) -> Result<Expr, ArgumentError> {
+        let mut expr = self.parse_and(depth)?;
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            let right = self.parse_and(depth)?;
+            expr = self.node(Expr::Or(Box::new(expr), Box::new(right)))?;
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self, depth: usize) -> Result<Expr, ArgumentError> {
+        let mut expr = self.parse_not(depth)?;
+        while self.tokens.next_if_eq(&Token::And).is_some() {
+            let right = self.parse_not(depth)?;
+            expr = self.node(Expr::And(Box::new(expr), Box::new(right)))?;
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self, depth: usize) -> Result<Expr, ArgumentError> {
+        if depth > MAX_DEPTH {
+            return Err(invalid(format!("the rule is nested deeper than {} levels", MAX_DEPTH)));
+        }
+
+        match self.next()? {
+            Token::Not => {
+                let expr = self.parse_not(depth + 1)?;
+                self.node(Expr::Not(Box::new(expr)))
+            }
+            Token::LeftParen => {
+                let expr = self.parse_or(depth + 1)?;
+                match self.next()? {
+                    Token::RightParen => Ok(expr),
+                    token => Err(invalid(format!("expected `)` but found {}", token))),
+                }
+            }
+            Token::Ident(ident) => self.parse_comparison(&ident),
+            token => Err(invalid(format!("expected a field but found {}", token))),
+        }
+    }
+
+    fn parse_comparison(&mut self, ident: &str) -> Result<Expr, ArgumentError> {
+        let field = Field::from_str(ident).map_err(|_| invalid(format!("unknown field `{}`", ident)))?;
+        let op = match self.next()? {
+            Token::Op(op) => op,
+            token => {
+                return Err(invalid(format!(
+                    "expected an operator after `{}` but found {}",
+                    field, token
+                )))
+            }
+        };
+        let value = match self.next()? {
+            Token::Value(value) => value,
+            token => {
+                return Err(invalid(format!(
+                    "expected a value after `{} {}` but found {}",
+                    field, op, token
+                )))
+            }
+        };
+
+        let valid = match (field.kind(), op, &value) {
+            (FieldKind::Text, Op::Eq | Op::Ne | Op::Contains, Value::Text(_)) => true,
+            (FieldKind::Text, Op::Matches, Value::Regex(_)) => true,
+            (FieldKind::Number, op, Value::Number(_)) | (FieldKind::Duration, op, Value::Duration(_)) => {
+                op.is_ordering()
+            }
+            _ => false,
+        };
+
+        if valid {
+            self.node(Expr::Compare(field, op, value))
+        } else {
+            Err(invalid(format!(
+                "`{} {} {}` isn't a valid comparison",
+                field, op, value
+            )))
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, ArgumentError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '=' | '!' | '<' | '>' => {
+                let equals = chars.next_if_eq(&'=').is_some();
+                Token::Op(match (c, equals) {
+                    ('=', true) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    _ => return Err(invalid(format!("unknown operator `{}`", c))),
+                })
+            }
+            '"' => Token::Value(Value::Text(read_delimited(&mut chars, '"')?)),
+            '/' => Token::Value(Value::Regex(read_delimited(&mut chars, '/')?)),
+            c if c.is_ascii_digit() => {
+                let mut number = String::from(c);
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    number.push(digit);
+                }
+                let number = number
+                    .parse::<u64>()
+                    .map_err(|_| invalid(format!("the number {} is too large", number)))?;
+
+                match chars.next_if(char::is_ascii_alphabetic) {
+                    Some(unit) => {
+                        let unit_seconds = DURATION_UNITS
+                            .iter()
+                            .find(|(u, _)| *u == unit)
+                            .map(|(_, seconds)| *seconds)
+                            .ok_or_else(|| invalid(format!("unknown duration unit `{}`", unit)))?;
+                        let seconds = i64::try_from(number)
+                            .ok()
+                            .and_then(|number| number.checked_mul(unit_seconds))
+                            .ok_or_else(|| invalid(format!("the duration {}{} is too long", number, unit)))?;
+                        Token::Value(Value::Duration(seconds))
+                    }
+                    None => Token::Value(Value::Number(number)),
+                }
+            }
+            c if c.is_ascii_alphabetic() => {
+                let mut ident = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '.') {
+                    ident.push(c);
+                }
+
+                match ident.to_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "contains" => Token::Op(Op::Contains),
+                    "matches" => Token::Op(Op::Matches),
+                    _ => Token::Ident(ident),
+                }
+            }
+            c => return Err(invalid(format!("unexpected character `{}`", c))),
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+// reads a string or a regex up to its closing delimiter. the delimiter is escaped with a backslash, and in strings so
+// is the backslash itself. in regexes any other escape is kept as-is for the regex to handle
+fn read_delimited(chars: &mut Peekable<Chars<'_>>, delimiter: char) -> Result<String, ArgumentError> {
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => match chars.next() {
+                Some(c) if c == delimiter || (c == '\\' && delimiter == '"') => value.push(c),
+                Some(c) => {
+                    value.push('\\');
+                    value.push(c);
+                }
+                None => value.push('\\'),
+            },
+            Some(c) if c == delimiter => return Ok(value),
+            Some(c) => value.push(c),
+            None => return Err(invalid(format!("missing the closing `{}`", delimiter))),
+        }
+    }
+}
+
+fn invalid<S: Into<String>>(reason: S) -> ArgumentError {
+    ArgumentError::InvalidRule(reason.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(rule: &str) -> Rule {
+        rule.parse()
+            .unwrap_or_else(|e| panic!("{} failed to parse: {}", rule, e))
+    }
+
+    fn parse_err(rule: &str) -> String {
+        match rule.parse::<Rule>() {
+            Ok(parsed) => panic!("{} parsed as {:?}", rule, parsed),
+            Err(ArgumentError::InvalidRule(reason)) => reason,
+            Err(e) => panic!("{} failed with an unexpected error: {}", rule, e),
+        }
+    }
+
+    fn round_trip(rule: &str) -> String {
+        let parsed = parse(rule);
+        let displayed = parsed.to_string();
+        assert_eq!(parse(&displayed), parsed, "{} didn't parse back the same", displayed);
+        displayed
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse("embeds > 0 OR stickers > 0 AND attachments > 0"),
+            parse("embeds > 0 OR (stickers > 0 AND attachments > 0)")
+        );
+        assert_ne!(
+            parse("embeds > 0 OR stickers > 0 AND attachments > 0"),
+            parse("(embeds > 0 OR stickers > 0) AND attachments > 0")
+        );
+    }
+
+    #[test]
+    fn not_binds_tightest() {
+        assert_eq!(
+            parse("NOT embeds > 0 AND stickers > 0"),
+            parse("(NOT embeds > 0) AND stickers > 0")
+        );
+        assert_ne!(
+            parse("NOT embeds > 0 AND stickers > 0"),
+            parse("NOT (embeds > 0 AND stickers > 0)")
+        );
+    }
+
+    #[test]
+    fn parentheses_are_only_displayed_where_needed() {
+        assert_eq!(
+            round_trip("(embeds > 0 OR stickers > 0) AND attachments > 0"),
+            "(embeds > 0 OR stickers > 0) AND attachments > 0"
+        );
+        assert_eq!(
+            round_trip("embeds > 0 OR (stickers > 0 AND attachments > 0)"),
+            "embeds > 0 OR stickers > 0 AND attachments > 0"
+        );
+        assert_eq!(
+            round_trip("embeds > 0 AND (stickers > 0 AND attachments > 0)"),
+            "embeds > 0 AND (stickers > 0 AND attachments > 0)"
+        );
+        assert_eq!(
+            round_trip("NOT (embeds > 0 OR stickers > 0)"),
+            "NOT (embeds > 0 OR stickers > 0)"
+        );
+    }
+
+    #[test]
+    fn too_many_nodes() {
+        // every comparison and every operator is a node, so n comparisons joined together are 2n - 1 nodes
+        let within = vec!["embeds>0"; MAX_NODES / 2].join(" AND ");
+        let over = vec!["embeds>0"; MAX_NODES / 2 + 1].join(" AND ");
+
+        parse(&within);
+        assert!(parse_err(&over).contains("comparisons and operators"));
+    }
+
+    #[test]
+    fn too_deep() {
+        let within = format!("{}embeds > 0", "NOT ".repeat(MAX_DEPTH));
+        let over_not = format!("{}embeds > 0", "NOT ".repeat(MAX_DEPTH + 1));
+        let over_parens = format!("{}embeds > 0{}", "(".repeat(MAX_DEPTH + 1), ")".repeat(MAX_DEPTH + 1));
+
+        parse(&within);
+        assert!(parse_err(&over_not).contains("nested deeper"));
+        assert!(parse_err(&over_parens).contains("nested deeper"));
+    }
+
+    #[test]
+    fn invalid_comparisons() {
+        for rule in &[
+            "content > 3",
+            "content == 3",
+            "content matches \"nitro\"",
+            "content contains /nitro/",
+            "attachments contains \"a\"",
+            "attachments > 1d",
+            "author.account_age < 3",
+            "author.account_age matches /a/",
+        ] {
+            assert!(parse_err(rule).contains("isn't a valid comparison"), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        let rule = parse(r#"content == "say \"hi\" \\ bye""#);
+        assert_eq!(
+            rule.0,
+            Expr::Compare(Field::Content, Op::Eq, Value::Text(String::from(r#"say "hi" \ bye"#)))
+        );
+        assert_eq!(rule.to_string(), r#"content == "say \"hi\" \\ bye""#);
+        round_trip(&rule.to_string());
+    }
+
+    #[test]
+    fn regexes_are_escaped() {
+        // the delimiter is unescaped, every other escape is left for the regex
+        let rule = parse(r"content matches /https?:\/\/\S+\.gift\b/");
+        assert_eq!(
+            rule.0,
+            Expr::Compare(
+                Field::Content,
+                Op::Matches,
+                Value::Regex(String::from(r"https?://\S+\.gift\b"))
+            )
+        );
+        assert_eq!(
+            round_trip(&rule.to_string()),
+            r"content matches /https?:\/\/\S+\.gift\b/"
+        );
+
+        // an escaped backslash right before the closing delimiter
+        let rule = parse(r"content matches /a\\/");
+        assert_eq!(
+            rule.0,
+            Expr::Compare(Field::Content, Op::Matches, Value::Regex(String::from(r"a\\")))
+        );
+        round_trip(&rule.to_string());
+    }
+
+    #[test]
+    fn durations_are_displayed_in_the_largest_unit() {
+        assert_eq!(round_trip("author.account_age < 24h"), "author.account_age < 1d");
+        assert_eq!(round_trip("author.member_age >= 90m"), "author.member_age >= 90m");
+    }
+
+    #[test]
+    fn rules_round_trip() {
+        let rules = "content contains \"nitro\" AND author.account_age < 1d\nembeds > 0 OR NOT stickers == 0"
+            .parse::<Rules>()
+            .unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules.to_string().parse::<Rules>().unwrap(), rules);
+    }
+
+    #[test]
+    fn invalid_regexes_are_rejected_when_set() {
+        use crate::module::settings::{CustomRuleSettings, Settings};
+
+        // a broken regex still parses, since the rules aren't compiled every time they're read
+        let rules = "content matches /(/".parse::<Rules>().unwrap();
+        assert!(rules.validate().is_err());
+
+        let mut settings = CustomRuleSettings::default();
+        assert!(settings.set("rules", "content matches /(/").is_err());
+        assert!(settings.rules.is_empty());
+        settings.set("rules", "content matches /(a)/").unwrap();
+        assert_eq!(settings.rules.len(), 1);
+    }
+}
@@ -0,0 +1,324 @@
+use super::settings::ValidateSetting;
+use crate::{error::ArgumentError, models, DbConn};
+use diesel::prelude::*;
+use rhai::{CallFnOptions, Dynamic, Engine, ImmutableString, Map, Scope, AST};
+use serenity::model::id::GuildId;
+use std::{
+    fmt::Display,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const ENTRY_POINT: &str = "is_match";
+const MAX_SCRIPT_LENGTH: usize = 4000;
+// a script is run for every message in its guild in the same task as the rest of the module, so a single run is
+// limited both in how many operations it may do and in how long it may take
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_RUN_TIME: Duration = Duration::from_millis(50);
+// checking the clock on every operation would be needlessly slow
+const PROGRESS_INTERVAL: u64 = 1000;
+const MAX_CALL_LEVELS: usize = 16;
+const MAX_EXPR_DEPTH: usize = 32;
+const MAX_STRING_SIZE: usize = 4096;
+const MAX_COLLECTION_SIZE: usize = 256;
+// the serialized size of a guild's key-value store
+const MAX_STORE_SIZE: usize = 16 * 1024;
+
+// the script's source as-is. it's only compiled when it's needed, since the settings are read for every message
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptSource(String);
+
+pub struct ScriptEngine {
+    engine: Engine,
+    started: Instant,
+    // milliseconds since the engine was started after which the running script is terminated
+    deadline: Arc<AtomicU64>,
+}
+
+impl FromStr for ScriptSource {
+    type Err = ArgumentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().count() > MAX_SCRIPT_LENGTH {
+            Err(ArgumentError::InvalidScript(format!(
+                "the script is longer than {} characters",
+                MAX_SCRIPT_LENGTH
+            )))
+        } else {
+            Ok(Self(String::from(s.trim())))
+        }
+    }
+}
+
+impl ValidateSetting for ScriptSource {
+    // the script can be set as a whole through the generic settings command too, so scripts that don't compile are
+    // rejected when they're set. an empty script disables the module
+    fn validate(&self) -> anyhow::Result<()> {
+        if !self.is_empty() {
+            ScriptEngine::new().compile(self)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ScriptSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ScriptSource {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl ScriptEngine {
+    pub fn new() -> Self {
+        let started = Instant::now();
+        let deadline = Arc::new(AtomicU64::new(0));
+        let mut engine = Engine::new();
+
+        let progress_deadline = Arc::clone(&deadline);
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH)
+            .set_max_string_size(MAX_STRING_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE)
+            .on_progress(move |ops| {
+                let elapsed = started.elapsed().as_millis() as u64;
+                if ops % PROGRESS_INTERVAL == 0 && elapsed > progress_deadline.load(Ordering::Relaxed) {
+                    Some(Dynamic::UNIT)
+                } else {
+                    None
+                }
+            })
+            // scripts don't get to write to the bot's output
+            .on_print(|_| ())
+            .on_debug(|_, _, _| ())
+            .disable_symbol("eval");
+
+        Self {
+            engine,
+            started,
+            deadline,
+        }
+    }
+
+    // compiling a script checks its syntax and that it defines the is_match(msg) function
+    pub fn compile(&self, source: &ScriptSource) -> Result<AST, ArgumentError> {
+        let ast = self
+            .engine
+            .compile(&source.0)
+            .map_err(|e| ArgumentError::InvalidScript(e.to_string()))?;
+
+        if ast
+            .iter_functions()
+            .any(|f| f.name == ENTRY_POINT && f.params.len() == 1)
+        {
+            Ok(ast)
+        } else {
+            Err(ArgumentError::InvalidScript(format!(
+                "the script doesn't define the function {}(msg)",
+                ENTRY_POINT
+            )))
+        }
+    }
+
+    // the store is bound to `this` in the script, so the script can both read and modify it. only the is_match function
+    // is called, anything at the script's top level is never run
+    pub fn run(&self, ast: &AST, msg: Map, store: &mut Map) -> Result<bool, ArgumentError> {
+        let deadline = self.started.elapsed() + MAX_RUN_TIME;
+        self.deadline.store(deadline.as_millis() as u64, Ordering::Relaxed);
+
+        let mut this = Dynamic::from(store.clone());
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut this);
+        let result = self
+            .engine
+            .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), ast, ENTRY_POINT, (msg,));
+
+        // the store is kept even if the script failed, since the modifications made before failing are already done.
+        // a store replaced with something else than a map is left as it was
+        *store = this
+            .try_cast::<Map>()
+            .ok_or_else(|| ArgumentError::ScriptFailed(String::from("the store was replaced with something else")))?;
+
+        result
+            .map_err(|e| ArgumentError::ScriptFailed(e.to_string()))?
+            .as_bool()
+            .map_err(|t| ArgumentError::ScriptFailed(format!("{} returned {} instead of a bool", ENTRY_POINT, t)))
+    }
+}
+
+impl Default for ScriptEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn load_store(guild: GuildId, db: &DbConn) -> anyhow::Result<Map> {
+    use crate::schema::script_stores;
+
+    let store = script_stores::table
+        .find(guild.0 as i64)
+        .select(script_stores::store)
+        .first::<String>(db)
+        .optional()?;
+
+    match store {
+        Some(store) => Ok(store_from_json(&store)?),
+        None => Ok(Map::new()),
+    }
+}
+
+// the store is saved as the JSON from store_to_json, since the caller already needs it to see if the store changed
+pub fn save_store(guild: GuildId, store: String, db: &DbConn) -> anyhow::Result<()> {
+    use crate::schema::script_stores;
+
+    let new_store = models::ScriptStore {
+        guild: guild.0 as i64,
+        store,
+    };
+
+    // return the inserted row's guild ID but don't store it anywhere, because this way diesel will error if the insert
+    // affected no rows
+    diesel::insert_into(script_stores::table)
+        .values(&new_store)
+        .on_conflict(script_stores::guild)
+        .do_update()
+        .set(script_stores::store.eq(&new_store.store))
+        .returning(script_stores::guild)
+        .get_result::<i64>(db)?;
+
+    Ok(())
+}
+
+pub fn delete_store(guild: GuildId, db: &DbConn) -> anyhow::Result<()> {
+    use crate::schema::script_stores;
+
+    diesel::delete(script_stores::table.find(guild.0 as i64)).execute(db)?;
+    Ok(())
+}
+
+// the store is saved as a JSON object. only strings, integers and booleans can be stored so they survive the round
+// trip unchanged
+pub fn store_to_json(store: &Map) -> Result<String, ArgumentError> {
+    let mut object = serde_json::Map::new();
+    for (key, value) in store {
+        let value = if let Ok(value) = value.as_bool() {
+            serde_json::Value::from(value)
+        } else if let Ok(value) = value.as_int() {
+            serde_json::Value::from(value)
+        } else if value.is::<ImmutableString>() {
+            serde_json::Value::from(value.clone().cast::<ImmutableString>().as_str())
+        } else {
+            return Err(ArgumentError::ScriptFailed(format!(
+                "the stored value `{}` isn't a string, an integer or a bool",
+                key
+            )));
+        };
+        object.insert(key.to_string(), value);
+    }
+
+    let json = serde_json::Value::Object(object).to_string();
+    if json.len() > MAX_STORE_SIZE {
+        Err(ArgumentError::ScriptFailed(format!(
+            "the store is larger than {} bytes",
+            MAX_STORE_SIZE
+        )))
+    } else {
+        Ok(json)
+    }
+}
+
+fn store_from_json(json: &str) -> anyhow::Result<Map> {
+    let object = serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(json)?;
+    Ok(object
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::Bool(value) => Dynamic::from(value),
+                serde_json::Value::Number(value) => Dynamic::from(value.as_i64()?),
+                serde_json::Value::String(value) => Dynamic::from(value),
+                _ => return None,
+            };
+            Some((key.into(), value))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str, store: &mut Map) -> Result<bool, ArgumentError> {
+        let engine = ScriptEngine::new();
+        let ast = engine.compile(&ScriptSource::from_str(source)?)?;
+        engine.run(&ast, Map::new(), store)
+    }
+
+    #[test]
+    fn endless_scripts_are_terminated() {
+        let started = Instant::now();
+        let result = run("fn is_match(msg) { loop {} }", &mut Map::new());
+
+        assert!(matches!(result, Err(ArgumentError::ScriptFailed(_))));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn oversized_strings_fail_the_script() {
+        let result = run(r#"fn is_match(msg) { let s = "a"; loop { s += s; } }"#, &mut Map::new());
+        assert!(matches!(result, Err(ArgumentError::ScriptFailed(_))));
+    }
+
+    #[test]
+    fn store_changes_are_kept() {
+        let mut store = Map::new();
+        for _ in 0..2 {
+            run(
+                "fn is_match(msg) { this.count = (this.count ?? 0) + 1; false }",
+                &mut store,
+            )
+            .unwrap();
+        }
+        assert_eq!(store["count"].as_int(), Ok(2));
+    }
+
+    #[test]
+    fn replaced_store_is_left_as_it_was() {
+        let mut store = Map::new();
+        store.insert("kept".into(), Dynamic::from(true));
+
+        let result = run("fn is_match(msg) { this = 1; true }", &mut store);
+        assert!(matches!(result, Err(ArgumentError::ScriptFailed(_))));
+        assert_eq!(store["kept"].as_bool(), Ok(true));
+    }
+
+    #[test]
+    fn store_survives_json_round_trip() {
+        let mut store = Map::new();
+        store.insert("flag".into(), Dynamic::from(true));
+        store.insert("count".into(), Dynamic::from(-3_i64));
+        store.insert("name".into(), Dynamic::from(String::from("spam")));
+
+        let restored = store_from_json(&store_to_json(&store).unwrap()).unwrap();
+        assert_eq!(restored["flag"].as_bool(), Ok(true));
+        assert_eq!(restored["count"].as_int(), Ok(-3));
+        assert_eq!(restored["name"].clone().into_string().unwrap(), "spam");
+        assert_eq!(restored.len(), 3);
+    }
+
+    #[test]
+    fn unsupported_store_values_are_rejected() {
+        let mut store = Map::new();
+        store.insert("list".into(), Dynamic::from(rhai::Array::new()));
+        assert!(store_to_json(&store).is_err());
+    }
+}
@@ -1,9 +1,10 @@
-use super::ModuleKind;
+use super::{link_policy::DomainPattern, rule::Rules, script::ScriptSource, word_filter::FilterPatterns, ModuleKind};
 use crate::{
     error::{ArgumentError, InternalError},
     models,
 };
 use enum_dispatch::enum_dispatch;
+use std::{convert::Infallible, fmt::Display, str::FromStr};
 
 pub trait FromDbRows: Sized {
     fn from_db_rows(rows: &[models::ModuleSetting]) -> anyhow::Result<Self>;
@@ -13,11 +14,48 @@ pub trait FromDbRows: Sized {
 pub trait Settings {
     fn get_all(&self) -> Vec<(&'static str, String)>;
     fn description_for(&self, setting: &str) -> Result<&'static str, ArgumentError>;
-    fn default_for(&self, setting: &str) -> Result<&'static str, ArgumentError>;
+    fn default_for(&self, setting: &str) -> Result<String, ArgumentError>;
     fn set(&mut self, setting: &str, value: &str) -> anyhow::Result<()>;
     fn reset(&mut self, setting: &str) -> Result<(), ArgumentError>;
 }
 
+// checks a setting's value when it's set. the settings are parsed from the database for every message, so checks too
+// expensive to run on each parse, such as compiling the value, are done here instead of in the value's FromStr
+pub trait ValidateSetting {
+    fn validate(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+macro_rules! impl_validate_setting {
+    ($($setting_type:ty),+) => {
+        $(impl ValidateSetting for $setting_type {})+
+    };
+}
+
+impl_validate_setting!(
+    bool,
+    u8,
+    u32,
+    u64,
+    usize,
+    i16,
+    f64,
+    String,
+    ImageDistance,
+    Domain,
+    DomainPattern
+);
+
+impl<T> ValidateSetting for SettingList<T>
+where
+    T: ValidateSetting,
+{
+    fn validate(&self) -> anyhow::Result<()> {
+        self.0.iter().try_for_each(T::validate)
+    }
+}
+
 #[enum_dispatch(Settings)]
 #[derive(Debug)]
 pub enum ModuleSettings {
@@ -29,6 +67,22 @@ pub enum ModuleSettings {
     InviteLink(InviteLinkSettings),
     ChannelActivity(ChannelActivitySettings),
     UserActivity(UserActivitySettings),
+    WordFilter(WordFilterSettings),
+    ScamLink(ScamLinkSettings),
+    LinkPolicy(LinkPolicySettings),
+    Caps(CapsSettings),
+    UnicodeAbuse(UnicodeAbuseSettings),
+    Attachments(AttachmentsSettings),
+    SecretLeak(SecretLeakSettings),
+    NewAccount(NewAccountSettings),
+    GhostPing(GhostPingSettings),
+    Raid(RaidSettings),
+    NameFilter(NameFilterSettings),
+    Impersonation(ImpersonationSettings),
+    ReactionSpam(ReactionSpamSettings),
+    ThreadSpam(ThreadSpamSettings),
+    CustomRule(CustomRuleSettings),
+    Script(ScriptSettings),
 }
 
 impl ModuleSettings {
@@ -42,7 +96,146 @@ impl ModuleSettings {
             ModuleKind::InviteLink => Ok(Self::InviteLink(InviteLinkSettings::from_db_rows(rows)?)),
             ModuleKind::ChannelActivity => Ok(Self::ChannelActivity(ChannelActivitySettings::from_db_rows(rows)?)),
             ModuleKind::UserActivity => Ok(Self::UserActivity(UserActivitySettings::from_db_rows(rows)?)),
+            ModuleKind::WordFilter => Ok(Self::WordFilter(WordFilterSettings::from_db_rows(rows)?)),
+            ModuleKind::ScamLink => Ok(Self::ScamLink(ScamLinkSettings::from_db_rows(rows)?)),
+            ModuleKind::LinkPolicy => Ok(Self::LinkPolicy(LinkPolicySettings::from_db_rows(rows)?)),
+            ModuleKind::Caps => Ok(Self::Caps(CapsSettings::from_db_rows(rows)?)),
+            ModuleKind::UnicodeAbuse => Ok(Self::UnicodeAbuse(UnicodeAbuseSettings::from_db_rows(rows)?)),
+            ModuleKind::Attachments => Ok(Self::Attachments(AttachmentsSettings::from_db_rows(rows)?)),
+            ModuleKind::SecretLeak => Ok(Self::SecretLeak(SecretLeakSettings::from_db_rows(rows)?)),
+            ModuleKind::NewAccount => Ok(Self::NewAccount(NewAccountSettings::from_db_rows(rows)?)),
+            ModuleKind::GhostPing => Ok(Self::GhostPing(GhostPingSettings::from_db_rows(rows)?)),
+            ModuleKind::Raid => Ok(Self::Raid(RaidSettings::from_db_rows(rows)?)),
+            ModuleKind::NameFilter => Ok(Self::NameFilter(NameFilterSettings::from_db_rows(rows)?)),
+            ModuleKind::Impersonation => Ok(Self::Impersonation(ImpersonationSettings::from_db_rows(rows)?)),
+            ModuleKind::ReactionSpam => Ok(Self::ReactionSpam(ReactionSpamSettings::from_db_rows(rows)?)),
+            ModuleKind::ThreadSpam => Ok(Self::ThreadSpam(ThreadSpamSettings::from_db_rows(rows)?)),
+            ModuleKind::CustomRule => Ok(Self::CustomRule(CustomRuleSettings::from_db_rows(rows)?)),
+            ModuleKind::Script => Ok(Self::Script(ScriptSettings::from_db_rows(rows)?)),
+        }
+    }
+}
+
+// a comma-separated list of values in a single setting. the separator is stripped of surrounding whitespace and empty
+// values are ignored, so both "a,b" and "a, b, " are the same list
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingList<T>(Vec<T>);
+
+impl<T> Default for SettingList<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T> FromStr for SettingList<T>
+where
+    T: FromStr,
+{
+    type Err = T::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            s.split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(T::from_str)
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+}
+
+impl<T> Display for SettingList<T>
+where
+    T: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, value) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", value)?;
         }
+        Ok(())
+    }
+}
+
+impl<T> SettingList<T> {
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<T> SettingList<T>
+where
+    T: PartialEq,
+{
+    pub fn contains(&self, value: &T) -> bool {
+        self.0.contains(value)
+    }
+}
+
+// the maximum amount of differing bits between two similar image hashes. the hashes are 64 bits long, so a larger
+// distance would make every image similar to every other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageDistance(u32);
+
+impl ImageDistance {
+    const MAX: u32 = 64;
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+}
+
+impl FromStr for ImageDistance {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let distance = s.parse()?;
+        if distance > Self::MAX {
+            return Err(ArgumentError::InvalidImageDistance(distance, Self::MAX).into());
+        }
+
+        Ok(Self(distance))
+    }
+}
+
+impl Display for ImageDistance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// a domain compared against the normalized hosts of links, so it's lowercased and stripped of a leading www. the same
+// way they are
+#[derive(Debug, Clone, PartialEq)]
+pub struct Domain(String);
+
+impl Domain {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Domain {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let domain = s.trim().to_lowercase();
+        match domain.strip_prefix("www.") {
+            Some(stripped) => Ok(Self(String::from(stripped))),
+            None => Ok(Self(domain)),
+        }
+    }
+}
+
+impl Display for Domain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
@@ -66,7 +259,7 @@ macro_rules! create_empty_settings {
                 Err(ArgumentError::NoSuchSetting(String::from(setting)))
             }
 
-            fn default_for(&self, setting: &str) -> Result<&'static str, ArgumentError> {
+            fn default_for(&self, setting: &str) -> Result<String, ArgumentError> {
                 Err(ArgumentError::NoSuchSetting(String::from(setting)))
             }
 
@@ -119,16 +312,23 @@ macro_rules! create_settings {
                 }
             }
 
-            fn default_for(&self, setting: &str) -> Result<&'static str, ArgumentError> {
+            fn default_for(&self, setting: &str) -> Result<String, ArgumentError> {
+                // the default has to be built and formatted instead of just stringified, since otherwise non-literal
+                // defaults would show up as their expression, e.g. "SettingList :: default()"
                 match setting {
-                    $(stringify!($setting_name) => Ok(stringify!($default)),)+
+                    $(stringify!($setting_name) => Ok(Self::default().$setting_name.to_string()),)+
                     _ => Err(ArgumentError::NoSuchSetting(String::from(setting)))
                 }
             }
 
             fn set(&mut self, setting: &str, value: &str) -> anyhow::Result<()> {
                 match setting {
-                    $(stringify!($setting_name) => Ok(self.$setting_name = value.parse()?),)+
+                    $(stringify!($setting_name) => {
+                        let value = value.parse::<$setting_type>()?;
+                        value.validate()?;
+                        self.$setting_name = value;
+                        Ok(())
+                    })+
                     _ => Err(ArgumentError::NoSuchSetting(String::from(setting)).into()),
                 }
             }
@@ -148,19 +348,172 @@ macro_rules! create_settings {
 
 // there have to be identical empty settings for each type instead of them all sharing one empty settings type because
 // enum_dispatch requires each variant in the settings enum to contain an unique type
-create_empty_settings!(
+create_empty_settings!(MentionSpamSettings, ChannelActivitySettings, UserActivitySettings);
+
+create_settings!(
     MassPingSettings,
-    EmojiSpamSettings,
-    MentionSpamSettings,
-    SelfbotSettings,
-    InviteLinkSettings,
-    ChannelActivitySettings,
-    UserActivitySettings
+    (match_text: bool => false, "Also match the text @everyone or @here, even if it didn't ping anyone"),
+    (role_mentions: usize => 0, "Also match messages that mention at least this many roles. 0 disables role mention matching"),
+    (only_without_permission: bool => false, "Only match users who don't have the Mention Everyone permission")
 );
 
 create_settings!(
     CrosspostSettings,
-    (minimum_length: usize => 5, "Ignore messages below this length"),
+    (minimum_length: usize => 5, "Ignore message content below this length"),
     (threshold: i16 => 80, "The similarity threshold. Must be an integer between -128 and 128 where 128 means entirely similar, i.e. equal"),
-    (timeout: u32 => 3600, "Ignore older messages than this timeout. The value is in seconds")
+    (timeout: u32 => 3600, "Ignore older messages than this timeout. The value is in seconds"),
+    (attachments: bool => true, "Also compare the messages' attachments by their name, size and type"),
+    (image_hash: bool => false, "Also compare image attachments by their perceptual hash. Requires downloading each image"),
+    (image_distance: ImageDistance => ImageDistance(5), "The maximum amount of differing bits between two similar image hashes. Must be an integer between 0 and 64")
+);
+
+create_settings!(
+    InviteLinkSettings,
+    (extra_domains: SettingList<String> => SettingList::default(), "Additional comma-separated invite link domains, such as vanity invite services. Links are matched as <domain>/<code>"),
+    (allowed_codes: SettingList<String> => SettingList::default(), "Comma-separated invite codes that are always allowed"),
+    (allowed_guilds: SettingList<u64> => SettingList::default(), "Comma-separated guild IDs whose invites are always allowed"),
+    (allow_own_guild: bool => false, "Allow invites to this guild")
+);
+
+create_settings!(
+    SelfbotSettings,
+    (threshold: u32 => 3, "The score at or above which a message is considered to be sent by a selfbot. Rich embeds and regular message intervals score 2, fast typing, a missing nonce and rapid edits score 1"),
+    (typing_speed: u32 => 25, "Typing faster than this many characters per second is considered impossible. 0 disables the check"),
+    (interval_tolerance: u64 => 50, "Consecutive messages whose intervals differ by at most this many milliseconds are considered automated"),
+    (edit_time: u64 => 500, "Editing a message within this many milliseconds of sending it is considered automated")
+);
+
+create_settings!(
+    WordFilterSettings,
+    (patterns: FilterPatterns => FilterPatterns::default(), "The filtered words. Modify them with the `/module filter` commands"),
+    (normalize: bool => true, "Match lookalike characters, accents and leetspeak as the letters they stand for"),
+    (word_boundary: bool => true, "Only match whole words instead of anywhere within words")
+);
+
+create_settings!(
+    ScamLinkSettings,
+    (blocked_domains: SettingList<Domain> => SettingList::default(), "Comma-separated domains that are always matched, in addition to the global blocklist. Subdomains are matched as well"),
+    (allowed_domains: SettingList<Domain> => SettingList::default(), "Comma-separated domains that are never matched. Subdomains are allowed as well"),
+    (typosquat_distance: usize => 1, "Match domains within this many typos of well-known domains such as discord.com or steamcommunity.com. Short names such as discord are only matched within one typo. 0 disables typosquat matching")
+);
+
+create_settings!(
+    LinkPolicySettings,
+    (allowed_domains: SettingList<DomainPattern> => SettingList::default(), "Comma-separated domains links are allowed to. If set, links to any other domain are matched. Use *.example.com to include subdomains"),
+    (denied_domains: SettingList<DomainPattern> => SettingList::default(), "Comma-separated domains links are never allowed to, even if they're allowed otherwise. Use *.example.com to include subdomains"),
+    (bare_domains: bool => false, "Treat bare domains without http:// or https://, such as example.com, as links"),
+    (expand_shorteners: bool => false, "Check where links from URL shorteners such as bit.ly lead to. Shortened links that can't be expanded are checked as-is")
+);
+
+create_settings!(
+    CapsSettings,
+    (minimum_length: usize => 10, "Ignore messages with fewer letters than this. Mentions, emoji and links aren't counted"),
+    (caps_ratio: u8 => 70, "The percentage of uppercase letters at or above which a message is matched. 0 disables caps matching"),
+    (repetition_ratio: u8 => 60, "The percentage of repeated characters (\"aaaaaa\") or repeated words (\"lol lol lol\") at or above which a message is matched. 0 disables repetition matching")
+);
+
+create_settings!(
+    UnicodeAbuseSettings,
+    (stacked_marks: usize => 4, "Match messages with at least this many combining marks (zalgo) stacked on a single character. 0 disables the check"),
+    (combining_density: usize => 100, "Match messages with at least this many combining marks per 100 other characters. 0 disables the check"),
+    (invisible_characters: usize => 10, "Match messages with at least this many invisible characters, such as zero-width spaces. 0 disables the check"),
+    (bidi_overrides: usize => 1, "Match messages with at least this many text direction overrides. 0 disables the check"),
+    (newlines: usize => 30, "Match messages with at least this many line breaks. 0 disables the check")
+);
+
+create_settings!(
+    AttachmentsSettings,
+    (block_executables: bool => true, "Match executables and scripts, such as .exe, .scr and .bat files"),
+    (blocked_extensions: SettingList<String> => SettingList::default(), "Additional comma-separated file extensions to match, such as zip, rar"),
+    (double_extensions: bool => true, "Match files pretending to be documents or media with a double extension, such as invoice.pdf.exe"),
+    (max_attachments: usize => 0, "Match messages with more attachments than this. 0 disables the check"),
+    (max_size: u64 => 0, "Match attachments larger than this many kilobytes. 0 disables the check")
+);
+
+create_settings!(
+    SecretLeakSettings,
+    (discord_tokens: bool => true, "Match Discord bot and user tokens"),
+    (github_tokens: bool => true, "Match GitHub personal access tokens and other GitHub tokens"),
+    (aws_keys: bool => true, "Match AWS access key IDs and labeled secret access keys"),
+    (private_keys: bool => true, "Match PEM-encoded private keys, such as SSH and TLS keys"),
+    (api_keys: bool => true, "Match Slack, Google and Stripe API keys"),
+    (min_entropy: f64 => 3.0, "The minimum randomness of a token, in bits per character. Keeps placeholders such as ghp_xxxx from matching")
+);
+
+create_settings!(
+    NewAccountSettings,
+    (account_age: u64 => 1440, "Accounts created less than this many minutes ago are considered new. 0 disables the check"),
+    (join_age: u64 => 10, "Members who joined less than this many minutes ago are considered new. 0 disables the check"),
+    (first_messages: usize => 0, "Members' first this many messages after joining are considered new. Only members who joined while the bot was running are counted. 0 disables the check"),
+    (block_links: bool => true, "Match links posted by new users"),
+    (block_attachments: bool => true, "Match attachments posted by new users"),
+    (block_mentions: bool => false, "Match new users mentioning other users or roles")
+);
+
+create_settings!(
+    GhostPingSettings,
+    (max_age: u64 => 120, "Notify about messages with mentions deleted at most this many seconds after they were sent"),
+    (role_mentions: bool => true, "Also notify about deleted role mentions")
+);
+
+create_settings!(
+    RaidSettings,
+    (join_window: u64 => 60, "The window in seconds recent joins are looked at in"),
+    (join_count: usize => 10, "Consider this many joins within the window a raid. 0 disables the check"),
+    (new_account_age: u64 => 1440, "Accounts created less than this many minutes ago are considered new"),
+    (new_account_count: usize => 5, "Consider this many new accounts joining within the window a raid. 0 disables the check"),
+    (similar_count: usize => 4, "Consider this many members joining within the window with names similar to the latest joiner's or the same avatar a raid. 0 disables the check"),
+    (similar_minimum_length: usize => 5, "Don't compare names shorter than this many characters, not counting numbers, since short names are easily similar by accident"),
+    (alert_channel: u64 => 0, "The ID of the channel to send raid alerts to. 0 disables alerts"),
+    (kick_new_joins: bool => false, "Kick the members that joined during the raid, and everyone joining until it is ended"),
+    (raise_verification: bool => false, "Raise the server's verification level to High during the raid"),
+    (lockdown: bool => false, "Lock down all text channels during the raid")
+);
+
+create_settings!(
+    NameFilterSettings,
+    (blocked_words: SettingList<String> => SettingList::default(), "Match usernames and nicknames containing any of these words. Lookalike characters, accents and leetspeak match the letters they stand for"),
+    (hoisting: bool => true, "Match names starting with punctuation such as ! to show up at the top of the member list"),
+    (invisible_characters: bool => true, "Match names containing invisible characters or nothing visible at all"),
+    (protected_names: SettingList<String> => SettingList::default(), "Match names that look the same as these, such as the staff's names spelled with lookalike characters. Exclude the staff themselves with an exclusion")
+);
+
+create_settings!(
+    ImpersonationSettings,
+    (name_distance: usize => 1, "Match names at most this many characters different from the name of a member with the admin role, after lookalike characters are folded"),
+    (minimum_length: usize => 4, "Don't compare names shorter than this many characters, since short names are easily similar by accident"),
+    (avatars: bool => true, "Match users with the same avatar as a member with the admin role")
+);
+
+create_settings!(
+    EmojiSpamSettings,
+    (max_emoji: usize => 10, "Match messages with more emoji than this, counting both custom and regular emoji. 0 disables the check"),
+    (emoji_only: usize => 0, "Match messages consisting of nothing but at least this many emoji. 0 disables the check"),
+    (sticker_count: usize => 5, "Match users sending more stickers than this within the sticker window. 0 disables the check"),
+    (sticker_window: u64 => 30, "The window in seconds stickers are counted in")
+);
+
+create_settings!(
+    ReactionSpamSettings,
+    (reaction_count: usize => 10, "Match users adding more reactions than this within the reaction window. 0 disables the check"),
+    (reaction_window: u64 => 10, "The window in seconds reactions are counted in"),
+    (remove_reactions: bool => true, "Remove all the reactions the user added within the window when they match")
+);
+
+create_settings!(
+    ThreadSpamSettings,
+    (user_threads: usize => 3, "Match users creating more threads than this within the user window. 0 disables the check"),
+    (user_window: u64 => 300, "The window in seconds threads created by a user are counted in"),
+    (channel_threads: usize => 10, "Match threads created in a channel after this many were created within the channel window. 0 disables the check"),
+    (channel_window: u64 => 60, "The window in seconds threads created in a channel are counted in")
+);
+
+create_settings!(
+    CustomRuleSettings,
+    (rules: Rules => Rules::default(), "The rules matched against messages. Modify them with the `/module rule` commands")
+);
+
+create_settings!(
+    ScriptSettings,
+    (script: ScriptSource => ScriptSource::default(), "The Rhai script defining is_match(msg). Modify it with the `/module script` commands")
 );
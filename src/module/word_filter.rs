@@ -0,0 +1,177 @@
+use super::settings::ValidateSetting;
+use crate::{error::ArgumentError, text};
+use regex::{RegexSet, RegexSetBuilder};
+use std::{fmt::Display, str::FromStr};
+
+const REGEX_PREFIX: &str = "re:";
+const WILDCARD: char = '*';
+// limits the compiled size of the filter so a handful of pathological regexes can't eat all the memory
+const FILTER_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterPattern {
+    /// A literal word or phrase
+    Literal(String),
+    /// A word where * matches any amount of word characters
+    Wildcard(String),
+    /// A regular expression, given with the re: prefix
+    Regex(String),
+}
+
+// the patterns are stored one per line since commas are common in regexes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilterPatterns(Vec<FilterPattern>);
+
+#[derive(Debug)]
+pub struct CompiledFilter {
+    set: RegexSet,
+    normalize: bool,
+}
+
+impl FromStr for FilterPattern {
+    type Err = ArgumentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            Err(ArgumentError::InvalidFilterPattern(String::from(
+                "the pattern is empty",
+            )))
+        } else if let Some(regex) = s.strip_prefix(REGEX_PREFIX) {
+            Ok(Self::Regex(String::from(regex)))
+        } else if s.contains(WILDCARD) {
+            Ok(Self::Wildcard(String::from(s)))
+        } else {
+            Ok(Self::Literal(String::from(s)))
+        }
+    }
+}
+
+impl Display for FilterPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterPattern::Literal(s) | FilterPattern::Wildcard(s) => write!(f, "{}", s),
+            FilterPattern::Regex(s) => write!(f, "{}{}", REGEX_PREFIX, s),
+        }
+    }
+}
+
+impl FilterPattern {
+    fn to_regex(&self, normalize: bool, word_boundary: bool) -> String {
+        // literals and wildcards are normalized the same way the matched text is, so e.g. a filtered "café" matches
+        // "cafe" and "CAFÉ" alike. user-given regexes are used as-is
+        let fold = |s: &str| {
+            if normalize {
+                text::fold_confusables(s)
+            } else {
+                s.to_lowercase()
+            }
+        };
+        let regex = match self {
+            FilterPattern::Literal(s) => regex::escape(&fold(s)),
+            FilterPattern::Wildcard(s) => fold(s)
+                .split(WILDCARD)
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(r"\w*"),
+            FilterPattern::Regex(s) => return format!("(?i){}", s),
+        };
+
+        if word_boundary {
+            format!(r"\b{}\b", regex)
+        } else {
+            regex
+        }
+    }
+}
+
+impl FromStr for FilterPatterns {
+    type Err = ArgumentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            s.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(FilterPattern::from_str)
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+}
+
+impl ValidateSetting for FilterPatterns {
+    // the patterns can be set as a whole through the generic settings command too, so invalid regexes and oversized
+    // filters are rejected when they're set. normalizing and word boundaries make the compiled filter the largest it
+    // can be
+    fn validate(&self) -> anyhow::Result<()> {
+        self.compile(true, true)?;
+        Ok(())
+    }
+}
+
+impl Display for FilterPatterns {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, pattern) in self.0.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", pattern)?;
+        }
+        Ok(())
+    }
+}
+
+impl FilterPatterns {
+    pub fn iter(&self) -> impl Iterator<Item = &FilterPattern> + '_ {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, pattern: &FilterPattern) -> bool {
+        self.0.contains(pattern)
+    }
+
+    pub fn push(&mut self, pattern: FilterPattern) {
+        self.0.push(pattern);
+    }
+
+    pub fn remove(&mut self, pattern: &FilterPattern) -> bool {
+        let len = self.0.len();
+        self.0.retain(|p| p != pattern);
+        len != self.0.len()
+    }
+
+    pub fn compile(&self, normalize: bool, word_boundary: bool) -> Result<CompiledFilter, ArgumentError> {
+        let set = RegexSetBuilder::new(self.0.iter().map(|p| p.to_regex(normalize, word_boundary)))
+            .size_limit(FILTER_SIZE_LIMIT)
+            .build()
+            .map_err(|e| ArgumentError::InvalidFilterPattern(e.to_string()))?;
+
+        Ok(CompiledFilter { set, normalize })
+    }
+}
+
+impl CompiledFilter {
+    pub fn is_match(&self, content: &str) -> bool {
+        if self.set.is_match(&content.to_lowercase()) {
+            return true;
+        }
+
+        if self.normalize {
+            // replacing every symbol catches e.g. "$hit" but can glue punctuation onto a word and break its
+            // boundaries, so the text is also tried with only the symbols inside words replaced
+            let folded = text::fold_confusables(content);
+            return self.set.is_match(&folded)
+                || self.set.is_match(&text::fold_leetspeak(&folded))
+                || self.set.is_match(&text::fold_leetspeak_within_words(&folded));
+        }
+
+        false
+    }
+}
@@ -0,0 +1,504 @@
+use crate::{
+    ext::UserdataExt,
+    lockdown,
+    module::{settings::RaidSettings, Module, ModuleKind},
+    text, DbConnPool, DbPool,
+};
+use chrono::{DateTime, Duration, Utc};
+use log::*;
+use serenity::{
+    client::Context,
+    model::{
+        guild::{Member, VerificationLevel},
+        id::{ChannelId, GuildId, UserId},
+    },
+    prelude::TypeMapKey,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+use tokio::sync::RwLock;
+
+// no sensible join window needs more joins than this to be remembered
+const MAX_RECENT_JOINS: usize = 500;
+// usernames are considered similar when they're within one edit of each other for every this many characters, so a
+// long name allows more differences than a short one
+const SIMILAR_NAME_CHARACTERS_PER_EDIT: usize = 4;
+const KICK_REASON: &str = "Raid protection";
+
+#[derive(Debug, Clone, Default)]
+pub struct RaidTracker {
+    guilds: Arc<RwLock<HashMap<GuildId, GuildJoins>>>,
+}
+
+impl TypeMapKey for RaidTracker {
+    type Value = RaidTracker;
+}
+
+#[derive(Debug, Default)]
+struct GuildJoins {
+    recent: VecDeque<Join>,
+    raid: Option<Raid>,
+}
+
+#[derive(Debug, Clone)]
+struct Join {
+    user: UserId,
+    name: String,
+    avatar: Option<String>,
+    created_at: DateTime<Utc>,
+    joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Raid {
+    pub started_at: DateTime<Utc>,
+    pub reason: String,
+    pub kicked: usize,
+    previous_verification_level: Option<VerificationLevel>,
+    locked_channels: Vec<ChannelId>,
+}
+
+// what should be done about a join once it's been tracked
+enum JoinOutcome {
+    Normal,
+    RaidStarted { reason: String, joiners: Vec<UserId> },
+    DuringRaid,
+}
+
+pub async fn process_join(ctx: &Context, guild: GuildId, member: &Member) {
+    if let Err(e) = try_process_join(ctx, guild, member).await {
+        error!("Processing member {} join in {} failed: {:?}", member.user.id, guild, e);
+    }
+}
+
+async fn try_process_join(ctx: &Context, guild: GuildId, member: &Member) -> anyhow::Result<()> {
+    let (tracker, db_pool, settings) = {
+        let data = ctx.data.read().await;
+        // excluded members aren't counted towards a raid nor kicked during one
+        let settings: RaidSettings = match Module::load_for_matching(
+            &data,
+            guild,
+            ModuleKind::Raid,
+            None,
+            member.user.id,
+            Some(&member.roles),
+        )
+        .await?
+        {
+            Some(settings) => settings,
+            None => return Ok(()),
+        };
+        (
+            data.get_userdata::<RaidTracker>()?.clone(),
+            data.get_userdata::<DbPool>()?.clone(),
+            settings,
+        )
+    };
+
+    let join = Join {
+        user: member.user.id,
+        name: member.user.name.clone(),
+        avatar: member.user.avatar.clone(),
+        created_at: member.user.id.created_at(),
+        joined_at: member.joined_at.unwrap_or_else(Utc::now),
+    };
+
+    match tracker.track(guild, join, &settings).await {
+        JoinOutcome::Normal => Ok(()),
+        JoinOutcome::RaidStarted { reason, joiners } => {
+            warn!("Raid detected in {}: {}", guild, reason);
+
+            if settings.raise_verification {
+                if let Err(e) = raise_verification_level(ctx, &tracker, guild).await {
+                    warn!("Failed to raise verification level in {} during a raid: {}", guild, e);
+                }
+            }
+
+            if settings.lockdown {
+                let reason = format!("Raid detected: {}", reason);
+                match lockdown::start(&ctx.http, &db_pool, guild, None, Some(&reason)).await {
+                    Ok(result) => tracker.set_locked_channels(guild, result.locked).await,
+                    Err(e) => warn!("Failed to lock down {} during a raid: {}", guild, e),
+                }
+            }
+
+            let alert = format!(
+                "**Raid detected**: {}. Use `/raid end` once it's over.{}",
+                reason,
+                if settings.kick_new_joins {
+                    " New members will be kicked until then."
+                } else {
+                    ""
+                }
+            );
+            send_alert(ctx, guild, &settings, &alert).await;
+
+            if settings.kick_new_joins {
+                for user in joiners {
+                    kick(ctx, &tracker, guild, user).await;
+                }
+            }
+            Ok(())
+        }
+        JoinOutcome::DuringRaid => {
+            if settings.kick_new_joins {
+                kick(ctx, &tracker, guild, member.user.id).await;
+            }
+            Ok(())
+        }
+    }
+}
+
+// the level from before the raid is remembered so it can be restored once the raid is over. the level isn't touched if
+// it's already High or above
+async fn raise_verification_level(ctx: &Context, tracker: &RaidTracker, mut guild: GuildId) -> anyhow::Result<()> {
+    let previous = match ctx.cache.guild_field(guild, |g| g.verification_level).await {
+        Some(level) => level,
+        None => guild.to_partial_guild(&ctx.http).await?.verification_level,
+    };
+
+    if previous >= VerificationLevel::High {
+        debug!(
+            "Verification level in {} is already {:?}, not raising it",
+            guild, previous
+        );
+        return Ok(());
+    }
+
+    guild
+        .edit(&ctx.http, |g| g.verification_level(VerificationLevel::High))
+        .await?;
+    tracker.set_previous_verification_level(guild, previous).await;
+    Ok(())
+}
+
+async fn kick(ctx: &Context, tracker: &RaidTracker, guild: GuildId, user: UserId) {
+    match guild.kick_with_reason(&ctx.http, user, KICK_REASON).await {
+        Ok(_) => {
+            info!("Kicked {} from {} during a raid", user, guild);
+            tracker.count_kick(guild).await;
+        }
+        Err(e) => warn!("Failed to kick {} from {} during a raid: {}", user, guild, e),
+    }
+}
+
+async fn send_alert(ctx: &Context, guild: GuildId, settings: &RaidSettings, content: &str) {
+    if settings.alert_channel == 0 {
+        return;
+    }
+
+    // the channel is set as a plain ID, so it has to be checked to be in the guild instead of in some other guild
+    let channel = ChannelId(settings.alert_channel);
+    match guild.channels(ctx).await {
+        Ok(channels) if channels.contains_key(&channel) => (),
+        Ok(_) => {
+            warn!(
+                "Raid alert channel {} isn't in {}, not sending the alert",
+                channel, guild
+            );
+            return;
+        }
+        Err(e) => {
+            warn!("Failed to get the channels in {} to send a raid alert: {}", guild, e);
+            return;
+        }
+    }
+
+    if let Err(e) = channel.send_message(&ctx.http, |m| m.content(content)).await {
+        warn!("Failed to send raid alert to {}: {}", channel, e);
+    }
+}
+
+impl RaidTracker {
+    async fn track(&self, guild: GuildId, join: Join, settings: &RaidSettings) -> JoinOutcome {
+        let mut guilds = self.guilds.write().await;
+        let joins = guilds.entry(guild).or_default();
+
+        let window_start = join.joined_at - Duration::seconds(settings.join_window as i64);
+        while joins
+            .recent
+            .front()
+            .is_some_and(|oldest| oldest.joined_at < window_start)
+        {
+            joins.recent.pop_front();
+        }
+        if joins.recent.len() >= MAX_RECENT_JOINS {
+            joins.recent.pop_front();
+        }
+        joins.recent.push_back(join);
+
+        if joins.raid.is_some() {
+            return JoinOutcome::DuringRaid;
+        }
+
+        match detect(&joins.recent, settings) {
+            Some(reason) => {
+                joins.raid = Some(Raid {
+                    started_at: Utc::now(),
+                    reason: reason.clone(),
+                    kicked: 0,
+                    previous_verification_level: None,
+                    locked_channels: Vec::new(),
+                });
+                JoinOutcome::RaidStarted {
+                    reason,
+                    joiners: joins.recent.iter().map(|join| join.user).collect(),
+                }
+            }
+            None => JoinOutcome::Normal,
+        }
+    }
+
+    // ends the raid, restores the verification level from before it if it was raised and lifts the lockdown from the
+    // channels the raid locked. channels locked by hand are left locked
+    pub async fn end_raid(&self, ctx: &Context, db_pool: &DbConnPool, guild: GuildId) -> anyhow::Result<Option<Raid>> {
+        let raid = match self
+            .guilds
+            .write()
+            .await
+            .get_mut(&guild)
+            .and_then(|joins| joins.raid.take())
+        {
+            Some(raid) => raid,
+            None => return Ok(None),
+        };
+
+        // if restoring either fails, the raid is put back so ending it can be tried again
+        if let Some(level) = raid.previous_verification_level {
+            let mut guild = guild;
+            if let Err(e) = guild.edit(&ctx.http, |g| g.verification_level(level)).await {
+                self.restore_raid(guild, raid).await;
+                return Err(e.into());
+            }
+        }
+
+        if !raid.locked_channels.is_empty() {
+            if let Err(e) = lockdown::end(&ctx.http, db_pool, guild, Some(&raid.locked_channels)).await {
+                self.restore_raid(
+                    guild,
+                    Raid {
+                        previous_verification_level: None,
+                        ..raid
+                    },
+                )
+                .await;
+                return Err(e);
+            }
+        }
+
+        info!("Raid in {} ended. {} members were kicked", guild, raid.kicked);
+        Ok(Some(raid))
+    }
+
+    async fn restore_raid(&self, guild: GuildId, raid: Raid) {
+        self.guilds.write().await.entry(guild).or_default().raid = Some(raid);
+    }
+
+    async fn set_previous_verification_level(&self, guild: GuildId, level: VerificationLevel) {
+        if let Some(raid) = self
+            .guilds
+            .write()
+            .await
+            .get_mut(&guild)
+            .and_then(|joins| joins.raid.as_mut())
+        {
+            raid.previous_verification_level = Some(level);
+        }
+    }
+
+    async fn set_locked_channels(&self, guild: GuildId, channels: Vec<ChannelId>) {
+        if let Some(raid) = self
+            .guilds
+            .write()
+            .await
+            .get_mut(&guild)
+            .and_then(|joins| joins.raid.as_mut())
+        {
+            raid.locked_channels = channels;
+        }
+    }
+
+    async fn count_kick(&self, guild: GuildId) {
+        if let Some(raid) = self
+            .guilds
+            .write()
+            .await
+            .get_mut(&guild)
+            .and_then(|joins| joins.raid.as_mut())
+        {
+            raid.kicked += 1;
+        }
+    }
+}
+
+// checks the joins within the window for each of the signs of a raid. returns a description of the first one found
+fn detect(recent: &VecDeque<Join>, settings: &RaidSettings) -> Option<String> {
+    if settings.join_count > 0 && recent.len() >= settings.join_count {
+        return Some(format!("{} joins in {} seconds", recent.len(), settings.join_window));
+    }
+
+    if settings.new_account_count > 0 {
+        let now = Utc::now();
+        let new_accounts = recent
+            .iter()
+            .filter(|join| now - join.created_at < Duration::minutes(settings.new_account_age as i64))
+            .count();
+
+        if new_accounts >= settings.new_account_count {
+            return Some(format!(
+                "{} accounts younger than {} minutes joined in {} seconds",
+                new_accounts, settings.new_account_age, settings.join_window
+            ));
+        }
+    }
+
+    if settings.similar_count > 0 {
+        let latest = recent.back()?;
+        // the latest joiner is always similar to themselves, and someone joining over and over again isn't a crowd
+        let similar = recent
+            .iter()
+            .filter(|join| join.user != latest.user && join.is_similar_to(latest, settings.similar_minimum_length))
+            .count();
+
+        if similar >= settings.similar_count {
+            return Some(format!(
+                "{} members with names or avatars similar to {} joined in {} seconds",
+                similar, latest.name, settings.join_window
+            ));
+        }
+    }
+
+    None
+}
+
+impl Join {
+    // the same avatar or names that differ by only a few characters once lookalikes and numbers are taken out, such as
+    // spambot1234 and spambot5678. names shorter than the minimum length are never similar, since short names such as
+    // bob and tom are only a couple of edits apart by accident
+    fn is_similar_to(&self, other: &Join, minimum_length: usize) -> bool {
+        if self.avatar.is_some() && self.avatar == other.avatar {
+            return true;
+        }
+
+        let normalize = |name: &str| {
+            text::fold_confusables(name)
+                .chars()
+                .filter(|c| !c.is_numeric())
+                .collect::<String>()
+        };
+        let (name, other_name) = (normalize(&self.name), normalize(&other.name));
+        let length = name.chars().count().min(other_name.chars().count());
+        if length == 0 || length < minimum_length {
+            return false;
+        }
+
+        text::edit_distance(&name, &other_name) <= length / SIMILAR_NAME_CHARACTERS_PER_EDIT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(user: u64, name: &str, account_age: Duration) -> Join {
+        let now = Utc::now();
+        Join {
+            user: UserId(user),
+            name: String::from(name),
+            avatar: None,
+            created_at: now - account_age,
+            joined_at: now,
+        }
+    }
+
+    fn settings_with(join_count: usize, new_account_count: usize, similar_count: usize) -> RaidSettings {
+        RaidSettings {
+            join_count,
+            new_account_count,
+            similar_count,
+            ..RaidSettings::default()
+        }
+    }
+
+    #[test]
+    fn join_count_is_a_raid() {
+        let settings = settings_with(3, 0, 0);
+        let mut recent = (0..2)
+            .map(|i| join(i, &format!("member{}", i), Duration::days(365)))
+            .collect::<VecDeque<_>>();
+
+        assert!(detect(&recent, &settings).is_none());
+        recent.push_back(join(3, "someone", Duration::days(365)));
+        assert!(detect(&recent, &settings).is_some());
+    }
+
+    #[test]
+    fn new_accounts_are_a_raid() {
+        let settings = settings_with(0, 2, 0);
+        let recent = VecDeque::from(vec![
+            join(1, "alice", Duration::minutes(5)),
+            join(2, "bob", Duration::days(365)),
+            join(3, "carol", Duration::minutes(10)),
+        ]);
+
+        let reason = detect(&recent, &settings).unwrap();
+        assert!(reason.starts_with("2 accounts"), "{}", reason);
+    }
+
+    #[test]
+    fn similar_names_are_a_raid() {
+        let settings = settings_with(0, 0, 2);
+        let age = Duration::days(365);
+        let mut recent = VecDeque::from(vec![join(1, "spambot1234", age), join(2, "spambot5678", age)]);
+
+        // the latest joiner doesn't count themselves
+        recent.push_back(join(2, "spambot5678", age));
+        assert!(detect(&recent, &settings).is_none());
+
+        recent.push_back(join(3, "spam_bot42", age));
+        assert!(detect(&recent, &settings).is_some());
+    }
+
+    #[test]
+    fn short_and_different_names_are_not_similar() {
+        let age = Duration::days(365);
+        assert!(!join(1, "bob", age).is_similar_to(&join(2, "tom", age), 3));
+        assert!(!join(1, "spambot", age).is_similar_to(&join(2, "moderator", age), 5));
+        assert!(join(1, "spambot", age).is_similar_to(&join(2, "spambots", age), 5));
+
+        let mut same_avatar = join(2, "bob", age);
+        same_avatar.avatar = Some(String::from("abc"));
+        let mut other = join(1, "tom", age);
+        other.avatar = Some(String::from("abc"));
+        assert!(same_avatar.is_similar_to(&other, 5));
+    }
+
+    #[tokio::test]
+    async fn joins_during_a_raid_are_reported_as_such() {
+        let tracker = RaidTracker::default();
+        let settings = settings_with(2, 0, 0);
+        let guild = GuildId(1);
+        let age = Duration::days(365);
+
+        assert!(matches!(
+            tracker.track(guild, join(1, "alice", age), &settings).await,
+            JoinOutcome::Normal
+        ));
+        match tracker.track(guild, join(2, "bob", age), &settings).await {
+            JoinOutcome::RaidStarted { joiners, .. } => assert_eq!(joiners, [UserId(1), UserId(2)]),
+            _ => panic!("raid didn't start"),
+        }
+        assert!(matches!(
+            tracker.track(guild, join(3, "carol", age), &settings).await,
+            JoinOutcome::DuringRaid
+        ));
+
+        // other guilds aren't affected
+        assert!(matches!(
+            tracker.track(GuildId(2), join(4, "dave", age), &settings).await,
+            JoinOutcome::Normal
+        ));
+    }
+}
@@ -9,6 +9,7 @@ table! {
         action -> Action_kind,
         in_channel -> Nullable<Int8>,
         message -> Nullable<Text>,
+        min_score -> Int2,
     }
 }
 
@@ -22,6 +23,30 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+
+    lockdown_channels (guild, channel) {
+        guild -> Int8,
+        channel -> Int8,
+        allow -> Nullable<Int8>,
+        deny -> Nullable<Int8>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::module::dbimport::*;
+
+    module_channel_settings (guild, module, channel, setting) {
+        guild -> Int8,
+        module -> Module_kind,
+        channel -> Int8,
+        setting -> Text,
+        value -> Text,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::module::dbimport::*;
@@ -57,10 +82,22 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+
+    script_stores (guild) {
+        guild -> Int8,
+        store -> Text,
+    }
+}
+
 allow_tables_to_appear_in_same_query!(
     actions,
     guild_settings,
+    lockdown_channels,
+    module_channel_settings,
     module_exclusions,
     module_settings,
     modules,
+    script_stores,
 );
@@ -2,17 +2,29 @@ use crate::{
     error::InternalError,
     ext::UserdataExt,
     latency_counter::LatencyCounter,
-    matcher::MatcherResponse,
-    module::{action::Action, cache::ModuleCache},
-    DbPool,
+    matcher::{
+        ghost_ping::GhostPingStore, reaction_spam::ReactionTracker, state::StateSnapshot, Match, MatcherResponse,
+    },
+    module::{
+        action::{Action, ActionKind, ActionTarget},
+        cache::ModuleCache,
+    },
+    DbConnPool, DbPool,
 };
 use log::*;
-use serenity::{model::channel::Message, CacheAndHttp, Client};
+use serenity::{CacheAndHttp, Client};
 use std::{
     sync::Arc,
     time::{Duration, Instant},
 };
-use tokio::{sync::mpsc, time};
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+    time,
+};
+
+// how long to wait for each matcher to save its state before giving up on it
+const MATCHER_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub fn spawn_shard_latency_ticker(client: &Client, update_freq: u64) {
     info!("Spawning shard latency update ticker...");
@@ -48,13 +60,36 @@ pub fn spawn_shard_latency_ticker(client: &Client, update_freq: u64) {
     });
 }
 
-pub fn spawn_termination_waiter(client: &Client) {
+pub fn spawn_termination_waiter(client: &Client, shutdown_tx: broadcast::Sender<()>, matchers: Vec<JoinHandle<()>>) {
     info!("Spawning termination waiter...");
 
     let shard_manager = client.shard_manager.clone();
+    let client_data = client.data.clone();
     tokio::spawn(async move {
         tokio::signal::ctrl_c().await.expect("failed to listen for SIGTERM");
-        info!("Caught SIGTERM, shutting down all shards");
+        info!("Caught SIGTERM, shutting down matchers");
+
+        // the send fails only if every matcher has already exited, in which case there's nothing to wait for anyways
+        if shutdown_tx.send(()).is_ok() {
+            for matcher in matchers {
+                match time::timeout(MATCHER_SHUTDOWN_TIMEOUT, matcher).await {
+                    Ok(Ok(())) => (),
+                    Ok(Err(e)) => error!("Matcher task failed during shutdown: {}", e),
+                    Err(_) => warn!("Matcher didn't shut down in {:?}", MATCHER_SHUTDOWN_TIMEOUT),
+                }
+            }
+        }
+
+        // the reaction tracker isn't a matcher, so it's saved here along with them
+        let data = client_data.read().await;
+        if let (Some(snapshot), Some(tracker)) = (data.get::<StateSnapshot>(), data.get::<ReactionTracker>()) {
+            if let Err(e) = tracker.save_state(snapshot).await {
+                error!("Failed to save reaction tracker state: {}", e);
+            }
+        }
+        drop(data);
+
+        info!("Shutting down all shards");
         shard_manager.lock().await.shutdown_all().await;
     });
 }
@@ -66,19 +101,20 @@ pub async fn spawn_action_handler(client: &Client, mut rx: mpsc::Receiver<Matche
     let module_cache = data.get_userdata::<ModuleCache>()?.clone();
     let db_pool = data.get_userdata::<DbPool>()?.clone();
     let latency = data.get_userdata::<LatencyCounter>()?.clone();
+    let ghost_pings = data.get_userdata::<GhostPingStore>()?.clone();
     let cache_http = Arc::clone(&client.cache_and_http);
 
     tokio::spawn(async move {
         info!("Starting action handler loop");
         loop {
-            let (kind, msg) = if let Some(r) = rx.recv().await {
+            let (kind, target, matched) = if let Some(r) = rx.recv().await {
                 r
             } else {
                 error!("Matcher response channel closed");
                 return;
             };
 
-            let guild_id = match msg.guild_id.ok_or(InternalError::MissingGuildID) {
+            let guild_id = match target.guild_id().ok_or(InternalError::MissingGuildID) {
                 Ok(id) => id,
                 Err(e) => {
                     error!("{}", e);
@@ -88,8 +124,8 @@ pub async fn spawn_action_handler(client: &Client, mut rx: mpsc::Receiver<Matche
 
             let module = module_cache.get(guild_id, kind).await;
             info!(
-                "Running {} actions for message {} in {} by {}",
-                kind, msg.id, guild_id, msg.author.id
+                "Running {} actions for {} in {} with score {}",
+                kind, target, guild_id, matched.score
             );
 
             let db = match db_pool.get() {
@@ -100,16 +136,38 @@ pub async fn spawn_action_handler(client: &Client, mut rx: mpsc::Receiver<Matche
                 }
             };
 
+            // actions requiring a higher score than the match has are skipped, so e.g. a borderline match can only
+            // notify while a strong one removes the message as well
             let actions = match module.get_actions(&db) {
-                Ok(a) => a,
+                Ok(a) => a
+                    .into_iter()
+                    .filter(|action| action.min_score <= matched.score)
+                    .collect::<Vec<_>>(),
                 Err(e) => {
                     error!("Failed to get module actions: {}", e);
                     continue;
                 }
             };
 
+            // the message is marked before any of the actions run so its deletion can't be mistaken for a ghost ping
+            if let ActionTarget::Message(msg, _) = &target {
+                if actions
+                    .iter()
+                    .any(|action| matches!(action.kind, ActionKind::RemoveMessage))
+                {
+                    ghost_pings.mark_removed(msg.id).await;
+                }
+            }
+
             for action in actions {
-                spawn_action_runner(action, Arc::clone(&cache_http), Arc::clone(&msg), latency.clone());
+                spawn_action_runner(
+                    action,
+                    Arc::clone(&cache_http),
+                    db_pool.clone(),
+                    target.clone(),
+                    matched.clone(),
+                    latency.clone(),
+                );
             }
         }
     });
@@ -120,25 +178,29 @@ pub async fn spawn_action_handler(client: &Client, mut rx: mpsc::Receiver<Matche
 fn spawn_action_runner(
     action: Action<'static>,
     cache_http: Arc<CacheAndHttp>,
-    msg: Arc<Message>,
+    db_pool: DbConnPool,
+    target: ActionTarget,
+    matched: Match,
     latency: LatencyCounter,
 ) {
     tokio::spawn(async move {
         let action_dbg_display = format!("{:?}", action);
         let start = Instant::now();
-        if let Err(e) = action.run(&cache_http, &msg).await {
+        if let Err(e) = action.run(&cache_http, &db_pool, &target, &matched).await {
             error!(
-                "Failed to run {} against guild {:?} channel {} message {}: {}",
-                action_dbg_display, msg.guild_id, msg.channel_id, msg.id, e
+                "Failed to run {} against guild {:?} {}: {}",
+                action_dbg_display,
+                target.guild_id(),
+                target,
+                e
             );
         }
 
         debug!(
-            "Running {} against guild {:?} channel {} message {} took {:?}",
+            "Running {} against guild {:?} {} took {:?}",
             action_dbg_display,
-            msg.guild_id,
-            msg.channel_id,
-            msg.id,
+            target.guild_id(),
+            target,
             start.elapsed()
         );
 
@@ -0,0 +1,164 @@
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+// characters that don't render as anything but can be used to break up words
+pub fn is_invisible(c: char) -> bool {
+    matches!(c,
+        '\u{00ad}' // soft hyphen
+        | '\u{034f}' // combining grapheme joiner
+        | '\u{061c}' // arabic letter mark
+        | '\u{115f}'..='\u{1160}' // hangul fillers
+        | '\u{17b4}'..='\u{17b5}' // khmer inherent vowels
+        | '\u{180e}' // mongolian vowel separator
+        | '\u{200b}'..='\u{200f}' // zero-width spaces, joiners and directional marks
+        | '\u{2060}'..='\u{2064}' // word joiner and invisible operators
+        | '\u{3164}' // hangul filler
+        | '\u{feff}' // zero-width no-break space
+        | '\u{ffa0}' // halfwidth hangul filler
+        | '\u{e0000}'..='\u{e007f}' // tags
+    )
+}
+
+// reduce text to a lowercase form where lookalike characters are the same: compatibility characters (fullwidth,
+// mathematical letters etc.) are decomposed, accents and other combining marks are stripped, invisible characters are
+// removed and common Cyrillic and Greek homoglyphs are replaced with their Latin counterparts
+pub fn fold_confusables(text: &str) -> String {
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c) && !is_invisible(*c))
+        .map(fold_homoglyph)
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// replace the usual number and symbol substitutions with the letters they stand for. this should be applied after
+// fold_confusables() since it only considers lowercase ASCII
+pub fn fold_leetspeak(text: &str) -> String {
+    text.chars().map(|c| fold_leet_char(c).unwrap_or(c)).collect()
+}
+
+// like fold_leetspeak(), but the symbols are only replaced between two word characters. that way punctuation next to a
+// word, e.g. the ! in "b4d!", doesn't become a part of it and word boundaries still match around it
+pub fn fold_leetspeak_within_words(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    chars
+        .iter()
+        .enumerate()
+        .map(|(idx, &c)| {
+            let within_word = idx > 0
+                && chars[idx - 1].is_alphanumeric()
+                && chars.get(idx + 1).is_some_and(|next| next.is_alphanumeric());
+            match fold_leet_char(c) {
+                Some(folded) if c.is_ascii_digit() || within_word => folded,
+                _ => c,
+            }
+        })
+        .collect()
+}
+
+fn fold_leet_char(c: char) -> Option<char> {
+    match c {
+        '0' => Some('o'),
+        '1' | '!' | '|' => Some('i'),
+        '3' => Some('e'),
+        '4' | '@' => Some('a'),
+        '5' | '$' => Some('s'),
+        '7' => Some('t'),
+        '8' => Some('b'),
+        '9' => Some('g'),
+        _ => None,
+    }
+}
+
+// reduce a name to the letters it looks like, so e.g. "Ädmin", "4dm1n" and "a.d.m.i.n" all end up as "admin"
+pub fn fold_name(name: &str) -> String {
+    fold_leetspeak(&fold_confusables(name))
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+fn fold_homoglyph(c: char) -> char {
+    match c {
+        // cyrillic
+        '\u{0430}' | '\u{0410}' => 'a',
+        '\u{0412}' => 'b',
+        '\u{0441}' | '\u{0421}' => 'c',
+        '\u{0501}' => 'd',
+        '\u{0435}' | '\u{0415}' => 'e',
+        '\u{04bb}' | '\u{041d}' | '\u{043d}' => 'h',
+        '\u{0456}' | '\u{0406}' => 'i',
+        '\u{0458}' | '\u{0408}' => 'j',
+        '\u{043a}' | '\u{041a}' => 'k',
+        '\u{043c}' | '\u{041c}' => 'm',
+        '\u{043e}' | '\u{041e}' => 'o',
+        '\u{0440}' | '\u{0420}' => 'p',
+        '\u{051b}' => 'q',
+        '\u{0455}' | '\u{0405}' => 's',
+        '\u{0442}' | '\u{0422}' => 't',
+        '\u{0443}' | '\u{0423}' => 'y',
+        '\u{051d}' => 'w',
+        '\u{0445}' | '\u{0425}' => 'x',
+        // greek
+        '\u{03b1}' | '\u{0391}' => 'a',
+        '\u{0392}' => 'b',
+        '\u{0395}' => 'e',
+        '\u{0397}' => 'h',
+        '\u{03b9}' | '\u{0399}' => 'i',
+        '\u{03ba}' | '\u{039a}' => 'k',
+        '\u{039c}' => 'm',
+        '\u{039d}' => 'n',
+        '\u{03bf}' | '\u{039f}' => 'o',
+        '\u{03c1}' | '\u{03a1}' => 'p',
+        '\u{03c4}' | '\u{03a4}' => 't',
+        '\u{03c5}' => 'u',
+        '\u{03bd}' => 'v',
+        '\u{03a7}' => 'x',
+        '\u{03a5}' => 'y',
+        '\u{0396}' => 'z',
+        // latin dotless i
+        '\u{0131}' => 'i',
+        c => c,
+    }
+}
+
+// emoji that stand on their own. this goes by the Unicode blocks emoji live in rather than the full emoji data, which
+// is close enough for counting them. modifiers that only change the emoji before them aren't counted, see
+// is_emoji_modifier()
+pub fn is_emoji(c: char) -> bool {
+    matches!(c,
+        '\u{1f000}'..='\u{1f3fa}' // mahjong, cards, enclosed characters, regional indicators and pictographs
+        | '\u{1f400}'..='\u{1faff}' // pictographs, emoticons, transport and symbols
+        | '\u{2600}'..='\u{27bf}' // miscellaneous symbols and dingbats
+        | '\u{2b00}'..='\u{2bff}' // arrows and shapes such as stars
+    )
+}
+
+// characters that are part of an emoji sequence without being an emoji themselves: the zero-width joiner, variation
+// selectors, skin tones, tags and the keycap
+pub fn is_emoji_modifier(c: char) -> bool {
+    matches!(c,
+        '\u{200d}'
+        | '\u{fe0e}'..='\u{fe0f}'
+        | '\u{1f3fb}'..='\u{1f3ff}'
+        | '\u{e0020}'..='\u{e007f}'
+        | '\u{20e3}'
+    )
+}
+
+// the Levenshtein distance between the two strings, i.e. how many single character insertions, deletions or
+// substitutions it takes to turn one into the other
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}